@@ -0,0 +1,179 @@
+// Typing latency of incremental highlighting as notes grow
+//
+// Run with `cargo bench --bench typing`. For each note size this types into
+// a paragraph and into a code block in the middle of the note, timing what
+// the editor and app do per keystroke outside GTK: restyle the blocks around
+// the edit and read the formats at the cursor for the toolbar. Both should
+// stay flat from the smallest note to the largest. Reading the note's text
+// and its tags, which takes a full parse, only happens on the debounced save.
+
+// The app's modules are compiled in whole; the bench uses only part of them
+#![allow(dead_code)]
+
+#[path = "../src/markdown/mod.rs"]
+mod markdown;
+#[path = "../src/utils/tags.rs"]
+mod tags;
+mod utils {
+    pub(crate) use super::tags;
+}
+
+use markdown::format;
+use markdown::incremental::{reparse, LineSource, Reparse};
+use markdown::SpanKind;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 4] = [1_000, 10_000, 50_000, 100_000];
+const KEYSTROKES: usize = 500;
+
+/// An in-memory stand-in for the editor's buffer, remembering which lines
+/// the last pass saw inside code blocks like the buffer's "code" tag does.
+struct Note {
+    lines: Vec<String>,
+    in_code: Vec<bool>,
+}
+
+impl LineSource for Note {
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn line(&self, n: usize) -> String {
+        self.lines[n].clone()
+    }
+
+    fn text(&self, lines: Range<usize>) -> String {
+        let mut text = String::new();
+        for n in lines {
+            text.push_str(&self.lines[n]);
+            if n + 1 < self.lines.len() {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    fn in_code(&self, n: usize) -> bool {
+        self.in_code[n]
+    }
+}
+
+impl Note {
+    /// A note of roughly `lines` lines mixing the constructs people write.
+    fn generate(lines: usize) -> Self {
+        let section = [
+            "## Meeting notes #work",
+            "",
+            "We talked about the **release** plan and _who_ owns which part,",
+            "see [the board](https://example.com/board) and `cargo build` output.",
+            "",
+            "- [ ] write the ==changelog==",
+            "- [x] tag the release",
+            "  - nested item with *emphasis*",
+            "",
+            "> Ship it when it's ready, not before.",
+            "",
+            "```rust",
+            "fn main() {",
+            "    let answer = 42; // the answer",
+            "    println!(\"{}\", answer);",
+            "}",
+            "```",
+            "",
+        ];
+        let lines: Vec<String> = section
+            .iter()
+            .cycle()
+            .take(lines)
+            .map(|line| line.to_string())
+            .collect();
+        let mut note = Note {
+            in_code: vec![false; lines.len()],
+            lines,
+        };
+        let all = reparse(&note, 0..note.lines.len());
+        note.restyle(&all);
+        note
+    }
+
+    /// Records which lines of `reparse` are inside code, as applying its tags would.
+    fn restyle(&mut self, reparse: &Reparse) {
+        let mut line_start = 0;
+        for (i, line) in reparse.text.split('\n').enumerate() {
+            let n = reparse.lines.start + i;
+            if n >= reparse.lines.end {
+                break;
+            }
+            self.in_code[n] = i > 0
+                && reparse.blocks.iter().any(|block| {
+                    matches!(block.kind, SpanKind::CodeBlock { .. })
+                        && block.range.contains(&(line_start - 1))
+                });
+            line_start += line.len() + 1;
+        }
+    }
+
+    /// Types `KEYSTROKES` characters at the end of line `n`, doing after
+    /// each one what a keystroke in the editor does. Returns the mean time
+    /// per keystroke.
+    fn type_into(&mut self, n: usize) -> Duration {
+        let mut parsing = Duration::ZERO;
+        for i in 0..KEYSTROKES {
+            self.lines[n].push(if i % 6 == 5 { ' ' } else { 'x' });
+
+            // Highlighting, then the toolbar's formats at the cursor, which
+            // is at the end of the line, read from the same parse
+            let start = Instant::now();
+            let result = reparse(self, n..n + 1);
+            self.restyle(&result);
+            let line_offset: usize = self.lines[result.lines.start..n]
+                .iter()
+                .map(|line| line.len() + 1)
+                .sum();
+            let cursor = line_offset + self.lines[n].len();
+            let formats = format::active(&result.blocks, cursor..cursor);
+            assert!(formats.len() <= format::Format::ALL.len());
+            parsing += start.elapsed();
+        }
+        parsing / KEYSTROKES as u32
+    }
+
+    fn find_line(&self, after: usize, line: &str) -> usize {
+        after + self.lines[after..].iter().position(|l| l == line).unwrap()
+    }
+}
+
+fn main() {
+    println!(
+        "{:>8}  {:>14}  {:>14}  {:>12}",
+        "lines", "paragraph/key", "code/key", "save"
+    );
+    for size in SIZES {
+        let mut note = Note::generate(size);
+
+        // What the save does: read the text, then its tags
+        let start = Instant::now();
+        let text = note.text(0..note.lines.len());
+        let note_tags = tags::extract_tags(&text);
+        let save = start.elapsed();
+        assert!(!note_tags.is_empty());
+
+        let middle = note.lines.len() / 2;
+        let paragraph = note.find_line(
+            middle,
+            "We talked about the **release** plan and _who_ owns which part,",
+        );
+        let code = note.find_line(middle, "    let answer = 42; // the answer");
+        let in_paragraph = note.type_into(paragraph);
+        let in_code = note.type_into(code);
+
+        println!(
+            "{:>8}  {:>12.1}µs  {:>12.1}µs  {:>10.2}ms",
+            size,
+            in_paragraph.as_secs_f64() * 1e6,
+            in_code.as_secs_f64() * 1e6,
+            save.as_secs_f64() * 1e3
+        );
+    }
+}
@@ -1,34 +1,81 @@
-use crate::components::editor::{Editor, EditorMsg};
-use crate::components::navigation::{Navigation, NavigationOutput};
-use crate::components::sidebar::{Sidebar, SidebarMsg};
+use crate::components::editor::{Editor, EditorMsg, EditorOutput};
+use crate::components::history::{History, HistoryMsg, HistoryOutput};
+use crate::components::navigation::{Category, Navigation, NavigationMsg, NavigationOutput};
+use crate::components::sidebar::{Sidebar, SidebarMsg, SidebarOutput};
+use crate::components::tasks::{Tasks, TasksMsg, TasksOutput};
+use crate::core::autosave::{
+    self, AutosaveMsg, AutosaveOutput, AutosaveWorker, SaveState, AUTOSAVE_DELAY, SYNC_TIMEOUT,
+};
 use crate::core::note_service::NoteService;
 use crate::models::note::Note;
+use crate::models::tag::{TagCount, TagMatch};
+use crate::utils::{links, paths, tags, tasks};
+use gtk::glib;
 use gtk::prelude::*;
 use relm4::prelude::*;
+use relm4::WorkerController;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::Duration;
+use uuid::Uuid;
 
 pub struct App {
     navigation: Controller<Navigation>,
     sidebar: Controller<Sidebar>,
     editor: Controller<Editor>,
+    history: Controller<History>,
+    tasks: Controller<Tasks>,
     notes: Vec<Note>,
     note_service: Rc<NoteService>,
-    selected_index: Option<usize>,
-    current_folder: String,
+    selected_id: Option<Uuid>,
+    category: Category,
+    tag_filter: Vec<String>,
+    tag_match: TagMatch,
+    trash_retention_days: u32,
+    window: gtk::Window,
+    autosave: WorkerController<AutosaveWorker>,
+    // Edited notes not yet handed to the worker
+    dirty: HashSet<Uuid>,
+    // Editor buffers whose text is newer than their note's content
+    edited: HashMap<Uuid, gtk::TextBuffer>,
+    // Saves handed to the worker and not yet acknowledged, per note
+    saving: HashMap<Uuid, usize>,
+    // Last error for notes whose most recent save failed
+    failed: HashMap<Uuid, String>,
+    edit_generation: u64,
+    // Failed saves since the last one that worked, for backing off retries
+    save_failures: u32,
+    // Closing waits for the worker to catch up before writing the rest
+    closing: bool,
+    save_state: SaveState,
 }
 
 #[derive(Debug)]
 pub enum AppMsg {
-    SidebarMsg(SidebarMsg),
-    EditorMsg(EditorMsg),
+    SidebarMsg(SidebarOutput),
+    EditorMsg(EditorOutput),
     NavigationMsg(NavigationOutput),
+    HistoryOutput(HistoryOutput),
+    TasksOutput(TasksOutput),
     CreateNote,
     DeleteNote,
+    RestoreNote,
+    PurgeNote(Uuid),
+    ConfirmEmptyTrash,
+    EmptyTrash,
+    SetTrashRetention(u32),
+    Autosave(AutosaveOutput),
+    AutosaveTick(u64),
+    CloseRequest,
+    /// The worker didn't catch up in time for closing
+    CloseTimedOut,
+    Quit,
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for App {
-    type Init = ();
+    type Init = PathBuf;
     type Input = AppMsg;
     type Output = ();
 
@@ -38,6 +85,11 @@ impl SimpleComponent for App {
             set_default_size: (1000, 700),
             set_size_request: (1200, 800),
 
+            connect_close_request[sender] => move |_| {
+                sender.input(AppMsg::CloseRequest);
+                glib::Propagation::Stop
+            },
+
             #[wrap(Some)]
             set_titlebar = &gtk::HeaderBar {
                 pack_start = &gtk::Button {
@@ -47,9 +99,66 @@ impl SimpleComponent for App {
                 },
                 pack_start = &gtk::Button {
                     set_icon_name: "user-trash-symbolic",
-                    set_tooltip_text: Some("Delete Note"),
+                    #[watch]
+                    set_tooltip_text: Some(if model.in_trash() { "Delete Permanently" } else { "Move to Trash" }),
                     connect_clicked => AppMsg::DeleteNote,
-                }
+                },
+                pack_start = &gtk::Button {
+                    set_icon_name: "edit-undo-symbolic",
+                    set_tooltip_text: Some("Restore Note"),
+                    #[watch]
+                    set_visible: model.in_trash(),
+                    connect_clicked => AppMsg::RestoreNote,
+                },
+
+                pack_end = &gtk::Label {
+                    set_css_classes: &["dim-label", "save-state"],
+                    #[watch]
+                    set_label: model.save_state.label(),
+                    #[watch]
+                    set_tooltip_text: match &model.save_state {
+                        SaveState::Failed(e) => Some(e.as_str()),
+                        _ => None,
+                    },
+                },
+
+                pack_end = &gtk::MenuButton {
+                    set_icon_name: "emblem-system-symbolic",
+                    set_tooltip_text: Some("Trash Settings"),
+                    #[watch]
+                    set_visible: model.in_trash(),
+
+                    #[wrap(Some)]
+                    set_popover = &gtk::Popover {
+                        #[wrap(Some)]
+                        set_child = &gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 8,
+                            set_margin_all: 8,
+
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Horizontal,
+                                set_spacing: 8,
+
+                                gtk::Label {
+                                    set_label: "Delete notes in the trash after (days, 0 = never)",
+                                },
+                                gtk::SpinButton::with_range(0.0, 3650.0, 1.0) {
+                                    set_value: model.trash_retention_days as f64,
+                                    connect_value_changed[sender] => move |spin| {
+                                        sender.input(AppMsg::SetTrashRetention(spin.value_as_int() as u32));
+                                    },
+                                },
+                            },
+
+                            gtk::Button {
+                                set_label: "Empty Trash",
+                                set_css_classes: &["destructive-action"],
+                                connect_clicked => AppMsg::ConfirmEmptyTrash,
+                            },
+                        },
+                    },
+                },
             },
 
             gtk::Paned {
@@ -61,32 +170,64 @@ impl SimpleComponent for App {
                 #[wrap(Some)]
                 set_start_child = model.navigation.widget(),
 
+                // The Tasks category replaces the note list and editor
                 #[wrap(Some)]
-                set_end_child = &gtk::Paned {
-                    set_orientation: gtk::Orientation::Horizontal,
-                    set_position: 250,
-                    set_shrink_start_child: false,
-                    set_resize_start_child: false,
+                set_end_child = &gtk::Stack {
+                    add_named[Some("notes")] = &gtk::Paned {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_position: 250,
+                        set_shrink_start_child: false,
+                        set_resize_start_child: false,
 
-                    #[wrap(Some)]
-                    set_start_child = model.sidebar.widget(),
+                        #[wrap(Some)]
+                        set_start_child = model.sidebar.widget(),
 
-                    #[wrap(Some)]
-                    set_end_child = model.editor.widget(),
+                        #[wrap(Some)]
+                        set_end_child = model.editor.widget(),
+                    },
+
+                    add_named[Some("tasks")] = model.tasks.widget(),
+
+                    #[watch]
+                    set_visible_child_name: if model.category == Category::Tasks { "tasks" } else { "notes" },
                 }
             }
         }
     }
 
     fn init(
-        _: Self::Init,
+        db_path: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         // Initialize DB
-        let conn = crate::db::init_db("watermelon.db").expect("Failed to init DB");
+        let conn = crate::db::init_db(&db_path).expect("Failed to init DB");
+        let storage_dir = paths::storage_dir(&db_path);
+        let autosave = AutosaveWorker::builder()
+            .detach_worker(db_path)
+            .forward(sender.input_sender(), AppMsg::Autosave);
         let repo = crate::db::note_repository::NoteRepository::new(conn);
-        let note_service = Rc::new(NoteService::new(repo));
+        let note_service = Rc::new(NoteService::new(repo, storage_dir));
+
+        // Drop notes that outlived the trash retention period
+        match note_service.purge_expired_trash() {
+            Ok(0) => {}
+            Ok(count) => println!("Purged {} expired notes from the trash", count),
+            Err(e) => eprintln!("Failed to purge trash: {}", e),
+        }
+        // Tags and links are filled in here rather than by the migrations
+        match note_service.reindex_if_stale() {
+            Ok(0) => {}
+            Ok(count) => println!("Indexed tags and links of {} notes", count),
+            Err(e) => eprintln!("Failed to index notes: {}", e),
+        }
+        // Deleted notes and removed links leave attachment files behind
+        match note_service.clean_up_attachments() {
+            Ok(0) => {}
+            Ok(count) => println!("Removed {} unused attachments", count),
+            Err(e) => eprintln!("Failed to clean up attachments: {}", e),
+        }
+        let trash_retention_days = note_service.trash_retention_days().unwrap_or(30);
 
         // Load Notes
         let notes = note_service.get_all_notes().unwrap_or_default();
@@ -104,28 +245,54 @@ impl SimpleComponent for App {
             notes
         };
 
-        let current_folder = "All Notes".to_string();
-
         let sidebar = Sidebar::builder()
-            .launch(notes.clone())
+            .launch((notes.clone(), note_service.clone()))
             .forward(sender.input_sender(), AppMsg::SidebarMsg);
 
         let editor = Editor::builder()
-            .launch(())
+            .launch(note_service.clone())
             .forward(sender.input_sender(), AppMsg::EditorMsg);
 
         let navigation = Navigation::builder()
             .launch(note_service.clone())
             .forward(sender.input_sender(), AppMsg::NavigationMsg);
 
+        let history = History::builder()
+            .transient_for(&root)
+            .launch(note_service.clone())
+            .forward(sender.input_sender(), AppMsg::HistoryOutput);
+
+        let tasks = Tasks::builder()
+            .launch(note_service.get_folders().unwrap_or_default())
+            .forward(sender.input_sender(), AppMsg::TasksOutput);
+
+        // Trashed notes are kept in the same cache and filtered per view
+        let mut notes = notes;
+        notes.extend(note_service.get_trashed_notes().unwrap_or_default());
+
         let model = App {
             navigation,
             sidebar,
             editor,
+            history,
+            tasks,
             notes,
             note_service,
-            selected_index: None,
-            current_folder,
+            selected_id: None,
+            category: Category::AllNotes,
+            tag_filter: Vec::new(),
+            tag_match: TagMatch::Any,
+            trash_retention_days,
+            window: root.clone(),
+            autosave,
+            dirty: HashSet::new(),
+            edited: HashMap::new(),
+            saving: HashMap::new(),
+            failed: HashMap::new(),
+            edit_generation: 0,
+            save_failures: 0,
+            closing: false,
+            save_state: SaveState::Saved,
         };
 
         let widgets = view_output!();
@@ -133,7 +300,7 @@ impl SimpleComponent for App {
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             AppMsg::CreateNote => {
                 let new_note = self
@@ -141,175 +308,384 @@ impl SimpleComponent for App {
                     .create_note("New Note".to_string(), "".to_string())
                     .expect("Failed to create note");
 
+                // New notes never land in the trash, so leave that view
+                if self.in_trash() {
+                    self.category = Category::AllNotes;
+                    self.navigation
+                        .sender()
+                        .send(NavigationMsg::SelectCategory(self.category))
+                        .unwrap();
+                }
+
                 self.notes.insert(0, new_note.clone()); // Add to top
-                self.sidebar
-                    .sender()
-                    .send(SidebarMsg::UpdateNotes(self.notes.clone()))
-                    .unwrap();
+                self.update_sidebar_notes();
 
-                // Select the new note
-                self.selected_index = Some(0);
-                self.sidebar
-                    .sender()
-                    .send(SidebarMsg::SelectNote(0))
-                    .unwrap();
-                self.editor
-                    .sender()
-                    .send(EditorMsg::LoadNote(new_note.title, new_note.content))
-                    .unwrap();
+                self.select_note(Some(new_note.id));
             }
             AppMsg::DeleteNote => {
-                if let Some(index) = self.selected_index {
-                    if let Some(note) = self.notes.get(index) {
-                        let _ = self.note_service.delete_note(note.id);
-                        self.notes.remove(index);
-                        self.sidebar
-                            .sender()
-                            .send(SidebarMsg::UpdateNotes(self.notes.clone()))
-                            .unwrap();
+                let Some(note) = self.selected_note() else {
+                    return;
+                };
+                let id = note.id;
 
-                        // Select next note or clear
-                        if self.notes.is_empty() {
-                            self.selected_index = None;
-                            self.editor
-                                .sender()
-                                .send(EditorMsg::LoadNote("".to_string(), "".to_string()))
-                                .unwrap();
-                        } else {
-                            let new_index = if index >= self.notes.len() {
-                                self.notes.len() - 1
-                            } else {
-                                index
-                            };
-                            self.selected_index = Some(new_index);
-                            self.sidebar
-                                .sender()
-                                .send(SidebarMsg::SelectNote(new_index))
-                                .unwrap();
-                            // Note: Sidebar selection triggers LoadNote via AppMsg::SidebarMsg
+                if note.is_deleted {
+                    self.confirm(
+                        &format!("Delete “{}” permanently?", note.title),
+                        "This cannot be undone.",
+                        "Delete",
+                        sender,
+                        AppMsg::PurgeNote(id),
+                    );
+                    return;
+                }
+
+                match self.note_service.trash_note(id) {
+                    Ok(deleted_at) => {
+                        let position = self.visible_position(id);
+                        if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                            note.is_deleted = true;
+                            note.deleted_at = Some(deleted_at);
                         }
+                        self.select_after_removal(position);
+                        self.forget_notes(vec![id]);
                     }
+                    Err(e) => eprintln!("Failed to move note to trash: {}", e),
                 }
             }
-            AppMsg::SidebarMsg(SidebarMsg::SelectNote(index)) => {
-                // Save previous note if selected
-                if let Some(prev_index) = self.selected_index {
-                    if let Some(note) = self.notes.get(prev_index) {
-                        let _ = self.note_service.update_note(note);
+            AppMsg::RestoreNote => {
+                let Some(id) = self.selected_note().filter(|n| n.is_deleted).map(|n| n.id) else {
+                    return;
+                };
+                match self.note_service.restore_note(id) {
+                    Ok(()) => {
+                        let position = self.visible_position(id);
+                        if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                            note.is_deleted = false;
+                            note.deleted_at = None;
+                        }
+                        self.select_after_removal(position);
                     }
+                    Err(e) => eprintln!("Failed to restore note: {}", e),
                 }
-
-                self.selected_index = Some(index);
-                if let Some(note) = self.notes.get(index) {
+            }
+            AppMsg::PurgeNote(id) => match self.note_service.delete_note(id) {
+                Ok(()) => {
+                    let position = self.visible_position(id);
+                    self.notes.retain(|n| n.id != id);
+                    self.select_after_removal(position);
+                    self.forget_notes(vec![id]);
+                }
+                Err(e) => eprintln!("Failed to delete note: {}", e),
+            },
+            AppMsg::ConfirmEmptyTrash => {
+                if self.notes.iter().any(|n| n.is_deleted) {
+                    self.confirm(
+                        "Permanently delete all notes in the trash?",
+                        "This cannot be undone.",
+                        "Delete",
+                        sender,
+                        AppMsg::EmptyTrash,
+                    );
+                }
+            }
+            AppMsg::EmptyTrash => match self.note_service.empty_trash() {
+                Ok(_) => {
+                    let purged = self
+                        .notes
+                        .iter()
+                        .filter(|n| n.is_deleted)
+                        .map(|n| n.id)
+                        .collect();
+                    self.notes.retain(|n| !n.is_deleted);
+                    if self.in_trash() {
+                        self.select_after_removal(None);
+                    }
+                    self.forget_notes(purged);
+                }
+                Err(e) => eprintln!("Failed to empty trash: {}", e),
+            },
+            AppMsg::SetTrashRetention(days) => {
+                if days != self.trash_retention_days {
+                    self.trash_retention_days = days;
+                    if let Err(e) = self.note_service.set_trash_retention_days(days) {
+                        eprintln!("Failed to save trash retention: {}", e);
+                    }
+                }
+            }
+            AppMsg::SidebarMsg(SidebarOutput::NoteSelected(id, search)) => {
+                self.select_note(Some(id));
+                // Show where the search found it
+                if let Some(search) = search {
                     self.editor
                         .sender()
-                        .send(EditorMsg::LoadNote(
-                            note.title.clone(),
-                            note.content.clone(),
-                        ))
+                        .send(EditorMsg::FindSearchTerms(search))
                         .unwrap();
                 }
             }
-            AppMsg::SidebarMsg(SidebarMsg::UpdateNotes(_)) => {}
-            AppMsg::SidebarMsg(SidebarMsg::Search(_)) => {}
-            AppMsg::EditorMsg(EditorMsg::ToolbarMsg(_)) => {}
-            AppMsg::EditorMsg(EditorMsg::UpdateContent(content)) => {
-                if let Some(index) = self.selected_index {
-                    if let Some(note) = self.notes.get_mut(index) {
-                        note.content = content;
-                        note.updated_at = chrono::Utc::now();
-                        // Save on every keystroke for now to prevent data loss.
-                        // Optimization: Implement debounce later if lag occurs.
-                        let _ = self.note_service.update_note(note);
+            AppMsg::EditorMsg(EditorOutput::ContentChanged(id, buffer)) => {
+                // The text and its tags are read when the note is saved;
+                // both take longer the longer the note, too slow for every
+                // keystroke
+                if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                    note.updated_at = chrono::Utc::now();
+                    self.edited.insert(id, buffer);
+                    self.mark_dirty(id, &sender);
+                }
+            }
+            AppMsg::EditorMsg(EditorOutput::TitleChanged(id, title)) => {
+                if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                    note.title = title.clone();
+                    note.updated_at = chrono::Utc::now();
+                    self.mark_dirty(id, &sender);
 
-                        // Update sidebar preview (optional, might be too frequent)
-                        // self.sidebar.sender().send(SidebarMsg::UpdateNotes(self.notes.clone())).unwrap();
+                    // Rebuilding the whole list on every keystroke is too slow
+                    self.sidebar
+                        .sender()
+                        .send(SidebarMsg::RetitleNote(id, title))
+                        .unwrap();
+                }
+            }
+            AppMsg::EditorMsg(EditorOutput::TitleRenamed(id, old, new)) => {
+                self.read_edits();
+                let renamed = match self.note_service.rename_links(id, &old, &new) {
+                    Ok(renamed) => renamed,
+                    Err(e) => {
+                        eprintln!("Failed to update links to {}: {}", new, e);
+                        return;
+                    }
+                };
+                for note in renamed {
+                    let Some(cached) = self.notes.iter_mut().find(|n| n.id == note.id) else {
+                        continue;
+                    };
+                    if self.has_unsaved_changes(note.id) {
+                        // The database copy is behind; rewrite the pending text too
+                        if let Some(content) = links::rename_wiki_links(&cached.content, &old, &new)
+                        {
+                            cached.tags = tags::extract_tags(&content);
+                            cached.content = content;
+                            cached.updated_at = note.updated_at;
+                            self.mark_dirty(note.id, &sender);
+                        }
+                    } else {
+                        cached.tags = tags::extract_tags(&note.content);
+                        cached.content = note.content;
+                        cached.updated_at = note.updated_at;
+                    }
+                    // The editor may have moved on to one of them already
+                    if self.selected_id == Some(note.id) {
+                        if let Some(cached) = self.notes.iter().find(|n| n.id == note.id) {
+                            let load = EditorMsg::LoadNote(
+                                cached.id,
+                                cached.title.clone(),
+                                cached.content.clone(),
+                            );
+                            self.editor.sender().send(load).unwrap();
+                        }
+                    }
+                }
+                self.update_sidebar_notes();
+                self.editor
+                    .sender()
+                    .send(EditorMsg::RefreshBacklinks)
+                    .unwrap();
+            }
+            AppMsg::EditorMsg(EditorOutput::OpenNote(id)) => {
+                self.open_note(id);
+            }
+            AppMsg::EditorMsg(EditorOutput::OpenNoteTitled(title)) => {
+                // Titles edited since the last save are only in the cache
+                let pending = self
+                    .notes
+                    .iter()
+                    .find(|n| {
+                        !n.is_deleted
+                            && self.has_unsaved_changes(n.id)
+                            && n.title.trim().eq_ignore_ascii_case(title.trim())
+                    })
+                    .map(|n| n.id);
+                let found = match pending {
+                    Some(id) => Ok(Some(id)),
+                    None => self
+                        .note_service
+                        .find_note_by_title(&title)
+                        .map(|note| note.map(|n| n.id)),
+                };
+                match found {
+                    Ok(Some(id)) => self.open_note(id),
+                    Ok(None) => match self
+                        .note_service
+                        .create_note(title.trim().to_string(), "".to_string())
+                    {
+                        Ok(note) => {
+                            let id = note.id;
+                            self.notes.insert(0, note);
+                            self.open_note(id);
+                        }
+                        Err(e) => eprintln!("Failed to create note {}: {}", title, e),
+                    },
+                    Err(e) => eprintln!("Failed to find note {}: {}", title, e),
+                }
+            }
+            AppMsg::EditorMsg(EditorOutput::ShowHistory(id)) => {
+                if let Some(note) = self.notes.iter().find(|n| n.id == id) {
+                    self.history
+                        .sender()
+                        .send(HistoryMsg::Show(note.clone()))
+                        .unwrap();
+                }
+            }
+            AppMsg::AutosaveTick(generation) => {
+                // Only the timer from the most recent edit flushes
+                if generation == self.edit_generation {
+                    self.flush_autosave();
+                }
+            }
+            AppMsg::Autosave(output) => {
+                let id = match output {
+                    AutosaveOutput::Saved(id) => {
+                        self.failed.remove(&id);
+                        self.save_failures = 0;
+                        id
+                    }
+                    AutosaveOutput::Failed(id, e) => {
+                        eprintln!("Failed to save note {}: {}", id, e);
+                        self.failed.insert(id, e);
+                        self.save_failures += 1;
+                        self.dirty.insert(id);
+                        let delay = autosave::retry_delay(self.save_failures);
+                        self.schedule_flush(delay, &sender);
+                        id
                     }
+                    AutosaveOutput::Synced => {
+                        if self.closing {
+                            self.finish_closing(sender);
+                        }
+                        return;
+                    }
+                };
+                if let Some(count) = self.saving.get_mut(&id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.saving.remove(&id);
+                    }
+                }
+                self.refresh_save_state();
+            }
+            AppMsg::CloseRequest => {
+                if self.closing {
+                    return;
+                }
+                // Let the worker drain first so it can't overwrite what's
+                // written on closing; meanwhile the header shows the saving
+                self.closing = true;
+                self.flush_autosave();
+                self.autosave.emit(AutosaveMsg::Sync);
+                let sender = sender.clone();
+                glib::timeout_add_local_once(SYNC_TIMEOUT, move || {
+                    sender.input(AppMsg::CloseTimedOut);
+                });
+            }
+            AppMsg::CloseTimedOut => {
+                if self.closing {
+                    eprintln!("Autosave worker did not finish in time");
+                    self.finish_closing(sender);
                 }
             }
-            AppMsg::EditorMsg(EditorMsg::UpdateTitle(title)) => {
-                if let Some(index) = self.selected_index {
-                    if let Some(note) = self.notes.get_mut(index) {
-                        note.title = title;
+            AppMsg::Quit => relm4::main_application().quit(),
+            AppMsg::HistoryOutput(HistoryOutput::Restored(note)) => {
+                self.read_edits();
+                if let Some(existing) = self.notes.iter_mut().find(|n| n.id == note.id) {
+                    *existing = note.clone();
+                }
+                // A queued autosave may still carry the pre-restore text;
+                // queue the restored text behind it so it wins.
+                if self.has_unsaved_changes(note.id) {
+                    self.mark_dirty(note.id, &sender);
+                }
+                if self.selected_id == Some(note.id) {
+                    self.editor
+                        .sender()
+                        .send(EditorMsg::LoadNote(note.id, note.title, note.content))
+                        .unwrap();
+                }
+                self.update_sidebar_notes();
+            }
+            AppMsg::TasksOutput(TasksOutput::Check(id, line)) => {
+                self.read_edits();
+                if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                    // None if the note changed since the list was built
+                    if let Some(content) = tasks::check_task(&note.content, line) {
+                        note.content = content;
                         note.updated_at = chrono::Utc::now();
-                        let _ = self.note_service.update_note(note);
-
-                        // Refresh sidebar to show new title
-                        self.sidebar
-                            .sender()
-                            .send(SidebarMsg::UpdateNotes(self.notes.clone()))
-                            .unwrap();
+                        let load =
+                            EditorMsg::LoadNote(id, note.title.clone(), note.content.clone());
+                        self.mark_dirty(id, &sender);
+                        if self.selected_id == Some(id) {
+                            self.editor.sender().send(load).unwrap();
+                        }
                     }
                 }
+                self.update_sidebar_notes();
+            }
+            AppMsg::TasksOutput(TasksOutput::Open(id, line)) => {
+                self.open_note(id);
+                self.editor
+                    .sender()
+                    .send(EditorMsg::GoToLine(line))
+                    .unwrap();
             }
-            AppMsg::EditorMsg(EditorMsg::LoadNote(_, _)) => {}
-            AppMsg::EditorMsg(EditorMsg::InsertImage(_)) => {}
-            AppMsg::EditorMsg(EditorMsg::Highlight) => {}
-            AppMsg::EditorMsg(EditorMsg::InitTextView(_)) => {}
             AppMsg::NavigationMsg(output) => {
                 match output {
-                    NavigationOutput::FolderSelected(folder_name) => {
-                        self.current_folder = folder_name.clone();
+                    NavigationOutput::CategorySelected(category) => {
+                        self.category = category;
                         self.update_sidebar_notes();
                     }
-                    NavigationOutput::MoveNote(note_id_str, folder_name) => {
-                        if let Ok(note_id) = uuid::Uuid::parse_str(&note_id_str) {
-                            // Find the note
-                            if let Some(mut note) =
-                                self.notes.iter().find(|n| n.id == note_id).cloned()
-                            {
-                                // Update folder
-                                note.folder = Some(folder_name.clone());
-                                note.updated_at = chrono::Utc::now();
-
-                                // Save to DB
-                                if let Err(_e) = self.note_service.update_note(&note) {
-                                } else {
-                                    // Refresh notes
-                                    if let Ok(notes) = self.note_service.get_all_notes() {
-                                        self.notes = notes;
-                                        self.update_sidebar_notes();
-                                    }
+                    NavigationOutput::MoveNote(note_id, folder_id) => {
+                        if let Some(mut note) = self.notes.iter().find(|n| n.id == note_id).cloned()
+                        {
+                            // Dragging a note out of the trash restores it
+                            if note.is_deleted {
+                                if let Err(e) = self.note_service.restore_note(note.id) {
+                                    eprintln!("Failed to restore note: {}", e);
+                                    return;
                                 }
                             }
-                        }
-                    }
-                    NavigationOutput::RenameFolder(old_name, new_name) => {
-                        if let Err(e) = self.note_service.rename_folder(&old_name, &new_name) {
-                            eprintln!("Failed to rename folder: {}", e);
-                        } else {
-                            // Update current folder if it was the one renamed
-                            if self.current_folder == old_name {
-                                self.current_folder = new_name.clone();
-                            }
 
-                            // Refresh notes from DB to reflect folder name changes
-                            if let Ok(notes) = self.note_service.get_all_notes() {
-                                if let Some(_first) = notes.first() {}
-                                self.notes = notes;
-                                self.update_sidebar_notes();
+                            note.folder_id = folder_id;
+                            note.updated_at = chrono::Utc::now();
+
+                            // Save to DB
+                            if let Err(e) = self.note_service.update_note(&note) {
+                                eprintln!("Failed to move note: {}", e);
+                            } else {
+                                // Refresh notes
+                                self.reload_notes();
                             }
                         }
                     }
-                    NavigationOutput::AddFolder(name) => {
-                        if let Err(e) = self.note_service.add_folder(&name) {
-                            eprintln!("Failed to add folder: {}", e);
-                        } else {
-                            // Reload folders in navigation?
-                            // Navigation already added it to its list via push_back?
-                            // Wait, Navigation::update for AddFolder emits this output but DOES NOT push to list.
-                            // So we need to tell Navigation to reload or just push it.
-                            // Actually, Navigation should probably optimistically add it, OR we reload.
-                            // Let's reload the whole app? No.
-                            // Let's just let Navigation handle the UI update, but we need to ensure it's saved.
-                            // Re-reading Navigation::update:
-                            // It emits AddFolder(new_name). It does NOT push to self.folders.
-                            // So we need to save it here, and then somehow tell Navigation to update?
-                            // Or, Navigation should have pushed it.
-                            // Let's change Navigation to push it AND emit AddFolder.
-                            // But wait, if I change Navigation to push it, then I don't need to do anything here except save it.
+                    NavigationOutput::FolderDeleted(_) => {
+                        self.reload_notes();
+                        // Its notes may have gone to the trash
+                        if self.selected_note().is_some_and(|n| n.is_deleted) && !self.in_trash() {
+                            self.select_note(None);
                         }
                     }
+                    NavigationOutput::FoldersChanged(folders) => {
+                        self.tasks
+                            .sender()
+                            .send(TasksMsg::UpdateFolders(folders.clone()))
+                            .unwrap();
+                        self.sidebar
+                            .sender()
+                            .send(SidebarMsg::UpdateFolders(folders))
+                            .unwrap();
+                    }
+                    NavigationOutput::TagFilterChanged(tags, mode) => {
+                        self.tag_filter = tags;
+                        self.tag_match = mode;
+                        self.update_sidebar_notes();
+                    }
                 }
             }
         }
@@ -317,26 +693,328 @@ impl SimpleComponent for App {
 }
 
 impl App {
-    fn update_sidebar_notes(&mut self) {
-        let filtered_notes: Vec<Note> = if self.current_folder == "All Notes" {
-            self.notes.clone()
-        } else if self.current_folder == "Untagged" {
-            self.notes
-                .iter()
-                .filter(|n| n.folder.is_none())
-                .cloned()
-                .collect()
+    fn in_trash(&self) -> bool {
+        self.category == Category::Trash
+    }
+
+    fn selected_note(&self) -> Option<&Note> {
+        let id = self.selected_id?;
+        self.notes.iter().find(|n| n.id == id)
+    }
+
+    /// Notes shown in the sidebar for the current folder, in sidebar order.
+    fn visible_notes(&self) -> Vec<&Note> {
+        self.notes
+            .iter()
+            .filter(|n| match self.category {
+                Category::Trash => n.is_deleted,
+                _ if n.is_deleted => false,
+                Category::AllNotes | Category::Tasks => true,
+                Category::Folder(id) => n.folder_id == Some(id),
+            })
+            .filter(|n| self.matches_tag_filter(n))
+            .collect()
+    }
+
+    fn matches_tag_filter(&self, note: &Note) -> bool {
+        self.tag_filter.is_empty() || self.tag_match.matches(&note.tags, &self.tag_filter)
+    }
+
+    fn visible_position(&self, id: Uuid) -> Option<usize> {
+        self.visible_notes().iter().position(|n| n.id == id)
+    }
+
+    /// Reload the note cache from the database. The selection is by id, so
+    /// it survives as long as the note does.
+    fn reload_notes(&mut self) {
+        self.read_edits();
+        match self.note_service.get_all_notes() {
+            Ok(mut notes) => {
+                notes.extend(self.note_service.get_trashed_notes().unwrap_or_default());
+                // The database may not have caught up with pending edits yet
+                for note in notes.iter_mut() {
+                    if self.has_unsaved_changes(note.id) {
+                        if let Some(cached) = self.notes.iter().find(|n| n.id == note.id) {
+                            note.title = cached.title.clone();
+                            note.content = cached.content.clone();
+                            note.tags = cached.tags.clone();
+                            note.updated_at = cached.updated_at;
+                        }
+                    }
+                }
+                self.notes = notes;
+            }
+            Err(e) => {
+                eprintln!("Failed to reload notes: {}", e);
+                return;
+            }
+        }
+        if self.selected_note().is_none() {
+            self.selected_id = None;
+        }
+        self.update_sidebar_notes();
+    }
+
+    fn has_unsaved_changes(&self, id: Uuid) -> bool {
+        self.dirty.contains(&id) || self.saving.contains_key(&id)
+    }
+
+    /// Drop the editor buffers (and undo history) of notes that no longer exist.
+    fn forget_notes(&self, ids: Vec<Uuid>) {
+        self.editor
+            .sender()
+            .send(EditorMsg::ForgetNotes(ids))
+            .unwrap();
+    }
+
+    /// Record an edit and (re)start the debounce timer.
+    fn mark_dirty(&mut self, id: Uuid, sender: &ComponentSender<Self>) {
+        self.dirty.insert(id);
+        self.schedule_flush(AUTOSAVE_DELAY, sender);
+    }
+
+    /// (Re)start the timer that flushes the dirty notes after `delay`.
+    fn schedule_flush(&mut self, delay: Duration, sender: &ComponentSender<Self>) {
+        self.edit_generation += 1;
+        self.refresh_save_state();
+
+        let generation = self.edit_generation;
+        let sender = sender.clone();
+        glib::timeout_add_local_once(delay, move || {
+            sender.input(AppMsg::AutosaveTick(generation));
+        });
+    }
+
+    /// Hand every dirty note to the background worker, bringing its tags up
+    /// to date with the edits first.
+    fn flush_autosave(&mut self) {
+        self.read_edits();
+        let mut tags_changed = false;
+        for id in std::mem::take(&mut self.dirty) {
+            if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                let note_tags = tags::extract_tags(&note.content);
+                if note.tags != note_tags {
+                    note.tags = note_tags;
+                    tags_changed = true;
+                }
+                *self.saving.entry(id).or_default() += 1;
+                self.autosave.emit(AutosaveMsg::Save(note.clone()));
+            }
+        }
+        self.refresh_save_state();
+
+        // Tag counts and the tag filter depend on the content
+        if tags_changed {
+            self.update_sidebar_notes();
+        }
+    }
+
+    /// Copy the text of the edited buffers into their notes. Anything that
+    /// reads or rewrites a note's content needs to do this first.
+    fn read_edits(&mut self) {
+        for (id, buffer) in std::mem::take(&mut self.edited) {
+            if let Some(note) = self.notes.iter_mut().find(|n| n.id == id) {
+                let (start, end) = buffer.bounds();
+                note.content = buffer.text(&start, &end, true).to_string();
+            }
+        }
+    }
+
+    fn refresh_save_state(&mut self) {
+        self.save_state = if !self.saving.is_empty() {
+            SaveState::Saving
+        } else if let Some(e) = self.failed.values().next() {
+            SaveState::Failed(e.clone())
+        } else if !self.dirty.is_empty() {
+            SaveState::Unsaved
+        } else {
+            SaveState::Saved
+        };
+    }
+
+    /// Quit once everything is written, or ask first if some notes could
+    /// not be.
+    fn finish_closing(&mut self, sender: ComponentSender<Self>) {
+        self.closing = false;
+        if self.save_everything_now() {
+            relm4::main_application().quit();
         } else {
-            self.notes
-                .iter()
-                .filter(|n| n.folder.as_ref() == Some(&self.current_folder))
-                .cloned()
-                .collect()
+            self.confirm(
+                "Some changes could not be saved",
+                "Quitting now will lose them.",
+                "Quit Anyway",
+                sender,
+                AppMsg::Quit,
+            );
+        }
+    }
+
+    /// Synchronously persist everything before quitting. Returns false if
+    /// any note could not be written.
+    fn save_everything_now(&mut self) -> bool {
+        self.read_edits();
+
+        // The worker may not have got to all of its saves, so write every
+        // note that was pending or failed once more from here.
+        let pending: HashSet<Uuid> = self
+            .saving
+            .keys()
+            .chain(self.failed.keys())
+            .chain(self.dirty.iter())
+            .copied()
+            .collect();
+        let mut ok = true;
+        for note in self.notes.iter_mut().filter(|n| pending.contains(&n.id)) {
+            note.tags = tags::extract_tags(&note.content);
+            match self.note_service.save_content(note) {
+                Ok(()) => {
+                    self.failed.remove(&note.id);
+                }
+                Err(e) => {
+                    eprintln!("Failed to save note {}: {}", note.id, e);
+                    self.failed.insert(note.id, e.to_string());
+                    ok = false;
+                }
+            }
+        }
+        self.saving.clear();
+        self.dirty.clear();
+        self.refresh_save_state();
+        ok
+    }
+
+    /// Make `id` the open note, saving the previous one first.
+    fn select_note(&mut self, id: Option<Uuid>) {
+        if id.is_some() && id == self.selected_id {
+            return;
+        }
+        self.flush_autosave();
+
+        self.selected_id = id.filter(|id| self.notes.iter().any(|n| n.id == *id));
+        let load = match self.selected_note() {
+            Some(note) => EditorMsg::LoadNote(note.id, note.title.clone(), note.content.clone()),
+            None => EditorMsg::Clear,
+        };
+        self.editor.sender().send(load).unwrap();
+        self.sidebar
+            .sender()
+            .send(SidebarMsg::SelectNote(self.selected_id))
+            .unwrap();
+    }
+
+    /// Select `id` and show its folder, so the note is in the list. Notes in
+    /// the trash are not opened.
+    fn open_note(&mut self, id: Uuid) {
+        let Some(folder_id) = self
+            .notes
+            .iter()
+            .find(|n| n.id == id && !n.is_deleted)
+            .map(|n| n.folder_id)
+        else {
+            return;
         };
+        self.category = folder_id.map_or(Category::AllNotes, Category::Folder);
+        self.navigation
+            .sender()
+            .send(NavigationMsg::SelectCategory(self.category))
+            .unwrap();
+        self.update_sidebar_notes();
+
+        self.select_note(Some(id));
+    }
+
+    /// After the selected note left the current view, select the note that
+    /// took its place in the list (or clear the editor if the list is empty).
+    fn select_after_removal(&mut self, position: Option<usize>) {
+        self.update_sidebar_notes();
+
+        let next = {
+            let visible = self.visible_notes();
+            let index = position.unwrap_or(0).min(visible.len().saturating_sub(1));
+            visible.get(index).map(|n| n.id)
+        };
+        self.selected_id = None;
+        self.select_note(next);
+    }
+
+    /// Ask before doing something destructive; `on_confirm` is sent only if
+    /// the user picks `action`.
+    fn confirm(
+        &self,
+        message: &str,
+        detail: &str,
+        action: &str,
+        sender: ComponentSender<Self>,
+        on_confirm: AppMsg,
+    ) {
+        let dialog = gtk::AlertDialog::builder()
+            .modal(true)
+            .message(message)
+            .detail(detail)
+            .buttons(["Cancel", action])
+            .cancel_button(0)
+            .default_button(0)
+            .build();
+        dialog.choose(
+            Some(&self.window),
+            gtk::gio::Cancellable::NONE,
+            move |response| {
+                if matches!(response, Ok(1)) {
+                    sender.input(on_confirm);
+                }
+            },
+        );
+    }
+
+    fn update_sidebar_notes(&mut self) {
+        let filtered_notes: Vec<Note> = self.visible_notes().into_iter().cloned().collect();
 
         self.sidebar
             .sender()
             .send(SidebarMsg::UpdateNotes(filtered_notes))
             .unwrap();
+        self.refresh_tasks();
+        self.refresh_tags();
+    }
+
+    /// Send the notes to collect tasks from to the task view, while it is
+    /// shown. It filters by folder itself; the tag filter applies here.
+    fn refresh_tasks(&self) {
+        if self.category != Category::Tasks {
+            return;
+        }
+        let notes = self
+            .notes
+            .iter()
+            .filter(|n| !n.is_deleted && self.matches_tag_filter(n))
+            .cloned()
+            .collect();
+        self.tasks
+            .sender()
+            .send(TasksMsg::UpdateNotes(notes))
+            .unwrap();
+    }
+
+    /// Send tag counts to the navigation pane. Counted from the cache rather
+    /// than the database so unsaved edits show up right away.
+    fn refresh_tags(&self) {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for note in self.notes.iter().filter(|n| !n.is_deleted) {
+            for tag in &note.tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        let counts = counts
+            .into_iter()
+            .map(|(name, count)| TagCount {
+                name: name.to_string(),
+                count,
+            })
+            .collect();
+
+        self.navigation
+            .sender()
+            .send(NavigationMsg::UpdateTags(counts))
+            .unwrap();
     }
 }
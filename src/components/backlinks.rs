@@ -0,0 +1,134 @@
+use crate::models::note::Note;
+use crate::utils::links;
+use gtk::pango;
+use gtk::prelude::*;
+use relm4::prelude::*;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum BacklinksMsg {
+    /// Show `notes`, which link to the note titled `title`
+    Update(Vec<Note>, String),
+    ActivateRow(usize),
+}
+
+#[derive(Debug)]
+pub enum BacklinksOutput {
+    Open(Uuid),
+}
+
+/// The notes that link to the open note with a `[[wiki link]]`.
+#[derive(Debug)]
+pub struct Backlinks {
+    list: gtk::ListBox,
+    note_ids: Vec<Uuid>, // By row
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for Backlinks {
+    type Init = ();
+    type Input = BacklinksMsg;
+    type Output = BacklinksOutput;
+
+    view! {
+        gtk::Expander {
+            #[watch]
+            set_label: Some(&match model.note_ids.len() {
+                1 => "Linked from 1 note".to_string(),
+                n => format!("Linked from {} notes", n),
+            }),
+            #[watch]
+            set_visible: !model.note_ids.is_empty(),
+            set_margin_start: 32,
+            set_margin_end: 32,
+            set_margin_bottom: 8,
+
+            #[local_ref]
+            list -> gtk::ListBox {
+                set_selection_mode: gtk::SelectionMode::None,
+                set_css_classes: &["navigation-sidebar"],
+
+                connect_row_activated[sender] => move |_, row| {
+                    let index = row.index();
+                    if index >= 0 {
+                        sender.input(BacklinksMsg::ActivateRow(index as usize));
+                    }
+                }
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Backlinks {
+            list: gtk::ListBox::new(),
+            note_ids: Vec::new(),
+        };
+        let list = &model.list;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            BacklinksMsg::Update(notes, title) => {
+                while let Some(row) = self.list.first_child() {
+                    self.list.remove(&row);
+                }
+                for note in &notes {
+                    self.list.append(&backlink_row(
+                        &note.title,
+                        &link_context(&note.content, &title),
+                    ));
+                }
+                self.note_ids = notes.iter().map(|note| note.id).collect();
+            }
+            BacklinksMsg::ActivateRow(index) => {
+                if let Some(id) = self.note_ids.get(index) {
+                    let _ = sender.output(BacklinksOutput::Open(*id));
+                }
+            }
+        }
+    }
+}
+
+/// A row with the linking note's title and the line with the link.
+fn backlink_row(title: &str, context: &str) -> gtk::Box {
+    let row = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    row.set_margin_top(6);
+    row.set_margin_bottom(6);
+    row.set_margin_start(6);
+    row.set_margin_end(6);
+    row.set_tooltip_text(Some("Open Note"));
+
+    for (text, classes) in [
+        (title, &["heading"][..]),
+        (context, &["dim-label", "caption"][..]),
+    ] {
+        let label = gtk::Label::new(Some(text));
+        label.set_halign(gtk::Align::Start);
+        label.set_ellipsize(pango::EllipsizeMode::End);
+        label.set_css_classes(classes);
+        row.append(&label);
+    }
+    row
+}
+
+/// The first line of `content` linking to `title`, trimmed.
+fn link_context(content: &str, title: &str) -> String {
+    links::wiki_links(content)
+        .into_iter()
+        .find(|link| link.title.eq_ignore_ascii_case(title.trim()))
+        .map(|link| {
+            let start = content[..link.range.start].rfind('\n').map_or(0, |i| i + 1);
+            let end = content[link.range.end..]
+                .find('\n')
+                .map_or(content.len(), |i| link.range.end + i);
+            content[start..end].trim().to_string()
+        })
+        .unwrap_or_default()
+}
@@ -1,38 +1,208 @@
-use crate::components::toolbar::{Toolbar, ToolbarMsg};
-use gtk::glib;
+use crate::components::backlinks::{Backlinks, BacklinksMsg, BacklinksOutput};
+use crate::components::find_bar::{FindBar, FindBarMsg, FindBarOutput};
+use crate::components::link_popover::{LinkPopover, LinkPopoverMsg, LinkPopoverOutput};
+use crate::components::note_completion::NoteCompletion;
+use crate::components::toolbar::{TableEdit, Toolbar, ToolbarMsg};
+use crate::core::note_service::NoteService;
+use crate::markdown::format::{self, Format};
+use crate::markdown::incremental::{self, DirtyRange, LineSource, Reparse};
+use crate::markdown::list::{self, ListItem, ListKind};
+use crate::markdown::syntax::{self, Token};
+use crate::markdown::table::{self, Table};
+use crate::markdown::{self, Span, SpanKind};
+use crate::models::attachment::{self, Attachment};
+use crate::utils::find::{self, Finder};
+use crate::utils::links;
+use crate::utils::recent::Recent;
 use gtk::prelude::*;
+use gtk::{gdk, gio, glib};
 use relm4::prelude::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use uuid::Uuid;
+
+// Notes whose buffers are kept for switching back to; older ones lose their
+// undo history
+const MAX_BUFFERS: usize = 20;
 
 #[derive(Debug)]
 pub enum EditorMsg {
-    UpdateContent(String),
+    /// The text of this buffer changed
+    Edited(gtk::TextBuffer),
     UpdateTitle(String),
-    LoadNote(String, String), // Title, Content
+    /// Done editing the title; links to the old one follow it
+    CommitTitle,
+    LoadNote(Uuid, String, String), // Id, Title, Content
+    GoToLine(usize),
+    Clear,
+    /// Drop the buffers, with their undo history, of notes that were deleted
+    ForgetNotes(Vec<Uuid>),
     ToolbarMsg(ToolbarMsg),
-    InsertImage(String),
+    /// Copy files into the attachments directory and link them at the cursor
+    AttachFiles(Vec<PathBuf>),
+    /// Attach a pasted image, as PNG data
+    PasteImage(glib::Bytes),
+    /// Replace the range the link popover was opened for with a link
+    InsertLink(String, String), // Text, URL
+    /// Open a link's target in its default app
+    OpenLink(String),
+    /// Open the note a `[[wiki link]]` names
+    OpenWikiLink(String),
+    OpenNote(Uuid),
+    /// Finish the `[[wiki link]]` being typed with a note title
+    CompleteNoteTitle(String),
+    RefreshBacklinks,
+    /// Open the find bar, searching for the selected text
+    ShowFind,
+    Find(FindBarOutput),
+    /// Select the first place the sidebar search `query` matches, and keep
+    /// finding it in the find bar
+    FindSearchTerms(String),
     Highlight,
+    /// The cursor or selection moved
+    SelectionMoved,
+    Undo,
+    Redo,
     InitTextView(gtk::TextView),
+    ShowHistory,
+}
+
+/// Edits are tagged with the note they were made to, so a late signal from
+/// the previous buffer can never land on the newly loaded note.
+#[derive(Debug)]
+pub enum EditorOutput {
+    /// The note's text changed; it's read from the buffer when it's saved,
+    /// as copying it on every keystroke takes longer the longer the note
+    ContentChanged(Uuid, gtk::TextBuffer),
+    TitleChanged(Uuid, String),
+    /// The title was committed after changing from the first to the second
+    TitleRenamed(Uuid, String, String),
+    ShowHistory(Uuid),
+    OpenNote(Uuid),
+    /// Open the note with this title, creating it if there's none
+    OpenNoteTitled(String),
 }
 
 #[derive(Debug)]
 pub struct Editor {
-    pub content: String,
+    pub note_id: Option<Uuid>,
     pub title: String,
+    /// The title as loaded or last committed
+    saved_title: String,
     pub should_reload_buffer: bool,
     pub should_update_title: bool,
     pub buffer: gtk::TextBuffer,
+    /// The buffers of notes opened this session, so each note keeps its own
+    /// undo history while others are open
+    buffers: HashMap<Uuid, NoteBuffer>,
+    /// The order the notes in `buffers` were opened in
+    recent: Recent<Uuid>,
+    /// Edits not yet highlighted, recorded by the buffer's signal handlers
+    dirty: Rc<Cell<DirtyRange>>,
+    /// Set while a note's text is put into its buffer, which isn't an edit
+    loading: Rc<Cell<bool>>,
     pub toolbar: Controller<Toolbar>,
+    link_popover: Controller<LinkPopover>,
+    /// What the open link popover will replace
+    link_range: Option<(gtk::TextMark, gtk::TextMark)>,
+    completion: NoteCompletion,
+    backlinks: Controller<Backlinks>,
+    find_bar: Controller<FindBar>,
+    /// The find bar's search, while it's open
+    find: Option<Find>,
+    /// The formats at the cursor, as last shown by the toolbar
+    formats: Vec<Format>,
+    /// The selection `formats` were read for, as buffer offsets; `None`
+    /// once the text has changed
+    formats_at: Option<(i32, i32)>,
     pub text_view: Option<gtk::TextView>,
+    note_service: Rc<NoteService>,
+}
+
+/// A note's buffer with the attachment previews shown in it, which the
+/// view drops whenever it switches buffers.
+#[derive(Debug)]
+struct NoteBuffer {
+    buffer: gtk::TextBuffer,
+    attachments: Vec<(gtk::TextChildAnchor, gtk::Widget)>,
+}
+
+#[derive(Debug)]
+struct Find {
+    /// `None` if there's nothing to find
+    finder: Option<Finder>,
+    /// The query is a regex that doesn't compile
+    invalid: bool,
+    /// Where `finder` matches, as buffer offsets
+    matches: Vec<Range<i32>>,
 }
 
 impl Editor {
-    fn create_buffer(content: &str) -> gtk::TextBuffer {
+    fn create_buffer(
+        content: &str,
+        dirty: &Rc<Cell<DirtyRange>>,
+        loading: &Rc<Cell<bool>>,
+        sender: &ComponentSender<Self>,
+    ) -> gtk::TextBuffer {
         let buffer = gtk::TextBuffer::builder().text(content).build();
+        let loading = loading.clone();
+        buffer.connect_changed(glib::clone!(@strong sender => move |buffer| {
+            sender.input(EditorMsg::Highlight);
+            if !loading.get() {
+                sender.input(EditorMsg::Edited(buffer.clone()));
+            }
+        }));
+        // The toolbar shows the formats at the cursor
+        buffer.connect_mark_set(glib::clone!(@strong sender => move |buffer, _, mark| {
+            if *mark == buffer.get_insert() || *mark == buffer.selection_bound() {
+                sender.input(EditorMsg::SelectionMoved);
+            }
+        }));
+
+        // Track edited text so highlighting only redoes the blocks around it.
+        // A new buffer starts out fully highlighted or empty.
+        dirty.set(DirtyRange::default());
+        let inserted = dirty.clone();
+        buffer.connect_insert_text(move |_, location, text| {
+            let pos = location.offset() as usize;
+            inserted.set(inserted.get().inserted(pos, text.chars().count()));
+        });
+        // Embedded images take up one char each
+        let anchored = dirty.clone();
+        buffer.connect_insert_child_anchor(move |_, location, _| {
+            anchored.set(anchored.get().inserted(location.offset() as usize, 1));
+        });
+        let deleted = dirty.clone();
+        buffer.connect_delete_range(move |_, start, end| {
+            deleted.set(
+                deleted
+                    .get()
+                    .deleted(start.offset() as usize, end.offset() as usize),
+            );
+        });
+
+        // Heading markers show on the cursor's line only
+        let cursor_line = Cell::new(0);
+        buffer.connect_cursor_position_notify(move |buffer| {
+            let line = buffer.iter_at_offset(buffer.cursor_position()).line();
+            let previous = cursor_line.replace(line);
+            if previous != line {
+                Self::reveal_heading_markers(buffer, previous, false);
+                Self::reveal_heading_markers(buffer, line, true);
+            }
+        });
 
         // Define tags
         buffer.create_tag(Some("bold"), &[("weight", &700)]); // 700 = Bold
         buffer.create_tag(Some("italic"), &[("style", &gtk::pango::Style::Italic)]);
         buffer.create_tag(Some("strikethrough"), &[("strikethrough", &true)]);
+        buffer.create_tag(
+            Some("underline"),
+            &[("underline", &gtk::pango::Underline::Single)],
+        );
         buffer.create_tag(
             Some("code"),
             &[
@@ -64,6 +234,7 @@ impl Editor {
                 ("style", &gtk::pango::Style::Italic),
             ],
         ); // Grey
+        buffer.create_tag(Some("number"), &[("foreground", &"#BD93F9")]); // Purple
 
         buffer.create_tag(
             Some("hidden"),
@@ -79,153 +250,1465 @@ impl Editor {
             ],
         );
 
+        // #tags
+        buffer.create_tag(
+            Some("hashtag"),
+            &[("foreground", &"#2E8B57"), ("weight", &600)],
+        ); // SeaGreen
+
+        // Headings, scaled by level
+        for (level, scale) in [1.6, 1.4, 1.25, 1.1, 1.0, 1.0].into_iter().enumerate() {
+            buffer.create_tag(
+                Some(&format!("h{}", level + 1)),
+                &[
+                    ("scale", &scale),
+                    ("weight", &700),
+                    ("pixels-above-lines", &8),
+                    ("pixels-below-lines", &4),
+                ],
+            );
+        }
+
+        // Marks the `#`s of a heading; they're hidden off the cursor's line
+        buffer.create_tag(Some("heading-marker"), &[]);
+
+        buffer.create_tag(
+            Some("blockquote"),
+            &[
+                ("left-margin", &16),
+                ("paragraph-background", &"#F6F8FA"),
+                ("foreground", &"#6C757D"),
+                ("style", &gtk::pango::Style::Italic),
+            ],
+        );
+
+        // `---` rules
+        buffer.create_tag(
+            Some("rule"),
+            &[
+                ("foreground", &"#C0C4CC"),
+                ("strikethrough", &true),
+                ("pixels-above-lines", &6),
+                ("pixels-below-lines", &6),
+            ],
+        );
+
+        // `inline code`
+        buffer.create_tag(
+            Some("inline-code"),
+            &[
+                ("family", &"monospace"),
+                ("background", &"#EEF1F4"),
+                ("foreground", &"#C7254E"),
+            ],
+        );
+
+        // Task checkboxes, and the text of done tasks
+        buffer.create_tag(
+            Some("task"),
+            &[
+                ("family", &"monospace"),
+                ("foreground", &"#2E8B57"),
+                ("weight", &700),
+            ],
+        );
+        buffer.create_tag(
+            Some("task-done"),
+            &[("strikethrough", &true), ("foreground", &"#8A8F98")],
+        );
+
+        // Tables are kept padded, so a monospace font lines up their columns
+        buffer.create_tag(Some("table"), &[("family", &"monospace")]);
+
+        // Links
+        buffer.create_tag(
+            Some("link"),
+            &[
+                ("foreground", &"#1E6BD6"),
+                ("underline", &gtk::pango::Underline::Single),
+            ],
+        );
+        buffer.create_tag(Some("link-url"), &[("foreground", &"#8A8F98")]);
+
+        // Markdown syntax that stays visible (heading and quote markers, fences...)
+        buffer.create_tag(Some("syntax"), &[("foreground", &"#A0A4AB")]);
+
+        // Find bar matches; last, so they show over any other background
+        buffer.create_tag(Some("find-match"), &[("background", &"#FFE08A")]);
+        buffer.create_tag(Some("find-current"), &[("background", &"#FFA94D")]);
+
         buffer
     }
 
+    /// Anchors for the previews and chips of the attachments linked from
+    /// `content`, which the buffer has just been loaded with, each right
+    /// after its link, with the widget to show there.
+    fn anchor_attachments(&self, content: &str) -> Vec<(gtk::TextChildAnchor, gtk::Widget)> {
+        let mut links = Vec::new();
+        for block in markdown::parse(content) {
+            block.walk(&mut |span, _| {
+                let url = span.children.iter().find(|c| c.kind == SpanKind::LinkUrl);
+                if let (SpanKind::Image | SpanKind::Link, Some(url)) = (&span.kind, url) {
+                    if let Some(id) = attachment::id_from_url(&content[url.range.clone()]) {
+                        links.push((span.range.end, id));
+                    }
+                }
+            });
+        }
+
+        // From the back, so each anchor leaves the offsets before it alone
+        let widgets = links
+            .into_iter()
+            .rev()
+            .filter_map(|(end, id)| {
+                let attachment = match self.note_service.get_attachment(id) {
+                    Ok(attachment) => attachment?,
+                    Err(e) => {
+                        eprintln!("Failed to load attachment {}: {}", id, e);
+                        return None;
+                    }
+                };
+                let offset = content[..end].chars().count() as i32;
+                let anchor = self
+                    .buffer
+                    .create_child_anchor(&mut self.buffer.iter_at_offset(offset));
+                Some((anchor, self.attachment_widget(&attachment)))
+            })
+            .collect();
+        widgets
+    }
+
+    /// A preview for images, a chip to open or save the file otherwise.
+    fn attachment_widget(&self, attachment: &Attachment) -> gtk::Widget {
+        let path = self.note_service.attachment_path(attachment);
+        if attachment.is_image() {
+            image_preview(&path).upcast()
+        } else {
+            attachment_chip(attachment, path).upcast()
+        }
+    }
+
+    /// Links `attachments` at the cursor, replacing the selection, each on a
+    /// line of its own and followed by a preview or chip.
+    fn insert_attachments(&mut self, attachments: &[Attachment]) {
+        if attachments.is_empty() {
+            return;
+        }
+        let buffer = &self.buffer;
+        buffer.begin_user_action();
+        buffer.delete_selection(true, true);
+
+        let mut iter = buffer.iter_at_mark(&buffer.get_insert());
+        if !iter.starts_line() {
+            buffer.insert(&mut iter, "\n");
+        }
+        for (i, attachment) in attachments.iter().enumerate() {
+            if i > 0 {
+                buffer.insert(&mut iter, "\n");
+            }
+            buffer.insert(&mut iter, &attachment.markdown());
+            let anchor = buffer.create_child_anchor(&mut iter);
+            let widget = self.attachment_widget(attachment);
+            if let Some(text_view) = &self.text_view {
+                text_view.add_child_at_anchor(&widget, &anchor);
+            }
+            if let Some(note) = self.note_id.and_then(|id| self.buffers.get_mut(&id)) {
+                note.attachments.push((anchor, widget));
+            }
+        }
+        if !iter.ends_line() {
+            buffer.insert(&mut iter, "\n");
+        }
+        buffer.place_cursor(&iter);
+        buffer.end_user_action();
+    }
+
+    /// Makes the buffer of note `id` the current one, holding `content`,
+    /// and returns the attachment previews to show in it. A note opened
+    /// before gets its old buffer back, undo history and all; if its text
+    /// was changed elsewhere since, say by checking a task in the task list
+    /// or restoring a revision, that change becomes one more step to undo.
+    fn load_buffer(
+        &mut self,
+        id: Uuid,
+        content: &str,
+        sender: &ComponentSender<Self>,
+    ) -> Vec<(gtk::TextChildAnchor, gtk::Widget)> {
+        if let Some(note) = self.buffers.get_mut(&id) {
+            self.buffer = note.buffer.clone();
+            let (start, end) = self.buffer.bounds();
+            if self.buffer.text(&start, &end, true).as_str() == content {
+                note.attachments.retain(|(anchor, _)| !anchor.is_deleted());
+                let attachments = note.attachments.clone();
+                self.dirty.take(); // Still highlighted from last time
+                self.touch_buffer(id);
+                return attachments;
+            }
+
+            self.loading.set(true);
+            self.buffer.begin_user_action();
+            let (mut start, mut end) = self.buffer.bounds();
+            self.buffer.delete(&mut start, &mut end);
+            self.buffer.insert(&mut self.buffer.start_iter(), content);
+            let attachments = self.anchor_attachments(content);
+            self.buffer.end_user_action();
+            self.loading.set(false);
+            self.buffer.place_cursor(&self.buffer.start_iter());
+            self.finish_loading(id, attachments)
+        } else {
+            self.buffer = Self::create_buffer(content, &self.dirty, &self.loading, sender);
+            // Loading a note isn't an edit to undo
+            self.loading.set(true);
+            self.buffer.begin_irreversible_action();
+            let attachments = self.anchor_attachments(content);
+            self.buffer.end_irreversible_action();
+            self.loading.set(false);
+            self.finish_loading(id, attachments)
+        }
+    }
+
+    fn finish_loading(
+        &mut self,
+        id: Uuid,
+        attachments: Vec<(gtk::TextChildAnchor, gtk::Widget)>,
+    ) -> Vec<(gtk::TextChildAnchor, gtk::Widget)> {
+        Self::highlight_buffer(&self.buffer);
+        self.dirty.take(); // The anchors were highlighted with the rest
+        self.buffers.insert(
+            id,
+            NoteBuffer {
+                buffer: self.buffer.clone(),
+                attachments: attachments.clone(),
+            },
+        );
+        self.touch_buffer(id);
+        attachments
+    }
+
+    /// Marks the buffer of note `id` as the most recently opened, dropping
+    /// the least recently opened ones past `MAX_BUFFERS`.
+    fn touch_buffer(&mut self, id: Uuid) {
+        for old in self.recent.touch(id) {
+            self.buffers.remove(&old);
+        }
+    }
+
+    /// Restyles the whole buffer, e.g. after loading a note.
     fn highlight_buffer(buffer: &gtk::TextBuffer) {
-        let (start, end) = buffer.bounds();
-        let text = buffer.text(&start, &end, true);
+        Self::highlight_lines(buffer, 0..buffer.line_count() as usize);
+    }
+
+    /// Restyles the blocks around the edits recorded in `dirty`, returning
+    /// them as parsed, if there were any edits.
+    fn highlight_dirty(buffer: &gtk::TextBuffer, dirty: &Cell<DirtyRange>) -> Option<Reparse> {
+        let chars = dirty.take().range()?;
+        let first = buffer.iter_at_offset(chars.start as i32).line() as usize;
+        let last = buffer.iter_at_offset(chars.end as i32).line() as usize;
+        Self::highlight_lines(buffer, first..last + 1)
+    }
 
-        // Remove existing tags (except code blocks which are handled separately? No, let's clear all for simplicity)
+    fn highlight_lines(buffer: &gtk::TextBuffer, lines: Range<usize>) -> Option<Reparse> {
+        let code = buffer.tag_table().lookup("code")?;
+        let reparse = incremental::reparse(&BufferLines { buffer, code }, lines);
+
+        let start = line_start(buffer, reparse.lines.start);
+        let end = line_start(buffer, reparse.lines.end);
         buffer.remove_all_tags(&start, &end);
 
-        // 1. Bold: **text**
-        if let Ok(re) = regex::Regex::new(r"\*\*([^*]+)\*\*") {
-            for mat in re.find_iter(&text) {
-                let start_offset = mat.start();
-                let end_offset = mat.end();
-                
-                // Convert byte offsets to char offsets
-                let start_char = text[..start_offset].chars().count() as i32;
-                let end_char = text[..end_offset].chars().count() as i32;
-
-                let start_iter = buffer.iter_at_offset(start_char);
-                let end_iter = buffer.iter_at_offset(end_char);
-
-                let mut content_start = start_iter;
-                content_start.forward_chars(2);
-                let mut content_end = end_iter;
-                content_end.backward_chars(2);
-
-                buffer.apply_tag_by_name("bold", &content_start, &content_end);
-                buffer.apply_tag_by_name("hidden", &start_iter, &content_start);
-                buffer.apply_tag_by_name("hidden", &content_end, &end_iter);
-            }
-        }
-
-        // 2. Italic: _text_
-        if let Ok(re) = regex::Regex::new(r"_([^_]+)_") {
-            for mat in re.find_iter(&text) {
-                let start_offset = mat.start();
-                let end_offset = mat.end();
-                
-                let start_char = text[..start_offset].chars().count() as i32;
-                let end_char = text[..end_offset].chars().count() as i32;
-
-                let start_iter = buffer.iter_at_offset(start_char);
-                let end_iter = buffer.iter_at_offset(end_char);
-
-                let mut content_start = start_iter;
-                content_start.forward_chars(1);
-                let mut content_end = end_iter;
-                content_end.backward_chars(1);
-
-                buffer.apply_tag_by_name("italic", &content_start, &content_end);
-                buffer.apply_tag_by_name("hidden", &start_iter, &content_start);
-                buffer.apply_tag_by_name("hidden", &content_end, &end_iter);
-            }
-        }
-
-        // 3. Highlight: ==text==
-        if let Ok(re) = regex::Regex::new(r"==([^=]+)==") {
-            for mat in re.find_iter(&text) {
-                let start_offset = mat.start();
-                let end_offset = mat.end();
-                
-                let start_char = text[..start_offset].chars().count() as i32;
-                let end_char = text[..end_offset].chars().count() as i32;
-
-                let start_iter = buffer.iter_at_offset(start_char);
-                let end_iter = buffer.iter_at_offset(end_char);
-
-                let mut content_start = start_iter;
-                content_start.forward_chars(2);
-                let mut content_end = end_iter;
-                content_end.backward_chars(2);
-
-                buffer.apply_tag_by_name("highlight", &content_start, &content_end);
-                buffer.apply_tag_by_name("hidden", &start_iter, &content_start);
-                buffer.apply_tag_by_name("hidden", &content_end, &end_iter);
-            }
-        }
-
-        // 4. Code Blocks (Simple)
-        // We need to re-apply code block logic here because we cleared tags.
-        // Re-use the logic from previous implementation but simplified
-        if let Some(code_tag) = buffer.tag_table().lookup("code") {
-             // Find ``` ... ```
-             // Regex for multiline code blocks is tricky with simple find_iter on full text.
-             // We can iterate manually or use regex with dot_matches_new_line
-             // But let's stick to the previous manual iteration logic for code blocks as it was robust-ish?
-             // Actually, the previous logic relied on "code" tag being present? No, it was finding it.
-             // Wait, the previous logic was: "If let Some(code_tag)... iterate forward_to_tag_toggle".
-             // This implies the "code" tag was ALREADY applied.
-             // We need to APPLY the "code" tag first.
-             
-             // Regex for code fence
-             if let Ok(re) = regex::Regex::new(r"(?s)```(.*?)```") {
-                 for mat in re.find_iter(&text) {
-                     let start_offset = mat.start();
-                     let end_offset = mat.end();
-                     
-                     let start_char = text[..start_offset].chars().count() as i32;
-                     let end_char = text[..end_offset].chars().count() as i32;
-                     
-                     let start_iter = buffer.iter_at_offset(start_char);
-                     let end_iter = buffer.iter_at_offset(end_char);
-                     
-                     buffer.apply_tag(&code_tag, &start_iter, &end_iter);
-                     
-                     // Now apply syntax highlighting inside
-                     // We can call a helper or just do it here.
-                     // Let's do a simple pass for keywords inside this block
-                     let block_text = &text[start_offset..end_offset];
-                     
-                     // Keywords
-                    let keywords = [
-                        "fn", "struct", "pub", "impl", "let", "mut", "use", "mod", "match", "if",
-                        "else", "return", "val", "var", "def", "class", "trait", "enum", "type",
-                        "const", "static", "async", "await",
-                    ];
-                    
-                    for keyword in keywords {
-                        let pattern = format!(r"\b{}\b", keyword);
-                        if let Ok(kw_re) = regex::Regex::new(&pattern) {
-                            for kw_mat in kw_re.find_iter(block_text) {
-                                let kw_start = start_offset + kw_mat.start();
-                                let kw_end = start_offset + kw_mat.end();
-                                
-                                let ks_char = text[..kw_start].chars().count() as i32;
-                                let ke_char = text[..kw_end].chars().count() as i32;
-                                
-                                let ks_iter = buffer.iter_at_offset(ks_char);
-                                let ke_iter = buffer.iter_at_offset(ke_char);
-                                
-                                buffer.apply_tag_by_name("keyword", &ks_iter, &ke_iter);
+        let offsets = CharOffsets::new(&reparse.text, start.offset());
+        for block in &reparse.blocks {
+            block.walk(&mut |span, parent| {
+                Self::apply_span(buffer, &reparse.text, &offsets, span, parent);
+            });
+        }
+        Some(reparse)
+    }
+
+    /// Applies the tag for one parsed span. Markers of inline formatting are
+    /// hidden, heading markers too unless the cursor is on their line; other
+    /// syntax (quote `>`, fences) is dimmed.
+    fn apply_span(
+        buffer: &gtk::TextBuffer,
+        text: &str,
+        offsets: &CharOffsets,
+        span: &Span,
+        parent: Option<&Span>,
+    ) {
+        let apply = |name: &str, range: Range<usize>| {
+            buffer.apply_tag_by_name(
+                name,
+                &buffer.iter_at_offset(offsets.get(range.start)),
+                &buffer.iter_at_offset(offsets.get(range.end)),
+            );
+        };
+
+        match &span.kind {
+            SpanKind::Heading(level) => apply(&format!("h{}", level), span.range.clone()),
+            SpanKind::BlockQuote => apply("blockquote", span.range.clone()),
+            SpanKind::ThematicBreak => apply("rule", span.range.clone()),
+            SpanKind::TaskMarker { checked } => {
+                apply("task", span.range.clone());
+                if let (true, Some(item)) = (*checked, parent) {
+                    apply("task-done", span.range.end..item.range.end);
+                }
+            }
+            SpanKind::Table => apply("table", span.range.clone()),
+            SpanKind::TableHeader => apply("bold", span.range.clone()),
+            SpanKind::TableDelimiter => apply("syntax", span.range.clone()),
+            SpanKind::CodeBlock { .. } => {
+                apply("code", span.range.clone());
+                Self::highlight_code(buffer, text, offsets, span);
+            }
+            SpanKind::Strong => apply("bold", span.range.clone()),
+            SpanKind::Emphasis => apply("italic", span.range.clone()),
+            SpanKind::Strikethrough => apply("strikethrough", span.range.clone()),
+            SpanKind::Highlight => apply("highlight", span.range.clone()),
+            SpanKind::Underline => apply("underline", span.range.clone()),
+            SpanKind::Code => apply("inline-code", span.range.clone()),
+            SpanKind::Link | SpanKind::Image | SpanKind::Autolink | SpanKind::WikiLink => {
+                // The link text sits between the first two markers (`[` and
+                // `](`, `<` and `>` for autolinks, `[[` and `]]` for wiki
+                // links)
+                let mut markers = span.children.iter().filter(|c| c.kind == SpanKind::Marker);
+                if let (Some(open), Some(close)) = (markers.next(), markers.next()) {
+                    apply("link", open.range.end..close.range.start);
+                }
+            }
+            SpanKind::BareUrl => apply("link", span.range.clone()),
+            SpanKind::LinkUrl => apply("link-url", span.range.clone()),
+            SpanKind::Tag => apply("hashtag", span.range.clone()),
+            SpanKind::Marker if parent.is_some_and(|p| matches!(p.kind, SpanKind::Heading(_))) => {
+                apply("syntax", span.range.clone());
+                apply("heading-marker", span.range.clone());
+                let line = buffer.iter_at_offset(offsets.get(span.range.start)).line();
+                let cursor_line = buffer.iter_at_mark(&buffer.get_insert()).line();
+                if line != cursor_line {
+                    apply("hidden", span.range.clone());
+                }
+            }
+            SpanKind::Marker => {
+                let inline = parent.is_some_and(|p| {
+                    matches!(
+                        p.kind,
+                        SpanKind::Strong
+                            | SpanKind::Emphasis
+                            | SpanKind::Strikethrough
+                            | SpanKind::Highlight
+                            | SpanKind::Underline
+                            | SpanKind::Code
+                    )
+                });
+                apply(if inline { "hidden" } else { "syntax" }, span.range.clone());
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces the heading marker of `line` with one for `level`, or removes
+    /// it for level 0.
+    fn set_heading_level(buffer: &gtk::TextBuffer, line: i32, level: u8) {
+        let Some((mut start, text)) = line_text(buffer, line) else {
+            return;
+        };
+
+        let hashes = text.bytes().take_while(|&b| b == b'#').count();
+        let after = &text[hashes..];
+        let existing = if (1..=6).contains(&hashes) && (after.is_empty() || after.starts_with(' '))
+        {
+            hashes + after.len() - after.trim_start_matches(' ').len()
+        } else {
+            0
+        };
+
+        let mut marker_end = start;
+        marker_end.forward_chars(existing as i32);
+        buffer.delete(&mut start, &mut marker_end);
+        if level > 0 {
+            buffer.insert(&mut start, &format!("{} ", "#".repeat(level as usize)));
+        }
+    }
+
+    /// Enter in a list item continues the list, or ends it when the item is
+    /// empty. Returns false to let the text view insert a plain line break.
+    fn continue_list(buffer: &gtk::TextBuffer) -> bool {
+        if buffer.has_selection() {
+            return false;
+        }
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let Some((line_start, text)) = line_text(buffer, cursor.line()) else {
+            return false;
+        };
+        let Some(item) = ListItem::parse(&text).filter(|_| !in_code_block(&line_start)) else {
+            return false;
+        };
+        // Enter inside the marker itself just breaks the line
+        if buffer.text(&line_start, &cursor, true).len() < item.prefix_len {
+            return false;
+        }
+
+        buffer.begin_user_action();
+        if text[item.prefix_len..].trim().is_empty() {
+            let mut start = line_start;
+            let mut end = line_start;
+            end.forward_to_line_end();
+            buffer.delete(&mut start, &mut end);
+        } else {
+            buffer.insert_at_cursor(&format!("\n{}{}", &text[..item.indent], item.next_prefix()));
+        }
+        buffer.end_user_action();
+        true
+    }
+
+    /// Tab nests the selected list items under the sibling above them and
+    /// Shift-Tab lifts them out a level. Returns false when the first
+    /// selected line isn't a list item.
+    fn indent_list(buffer: &gtk::TextBuffer, outdent: bool) -> bool {
+        let lines = selected_lines(buffer);
+        let Some((line_start, text)) = line_text(buffer, *lines.start()) else {
+            return false;
+        };
+        let Some(item) = ListItem::parse(&text).filter(|_| !in_code_block(&line_start)) else {
+            return false;
+        };
+
+        // The items above, nearest first, up to the first line outside the list
+        let mut above = Vec::new();
+        for n in (0..*lines.start()).rev() {
+            let Some((_, text)) = line_text(buffer, n) else {
+                break;
+            };
+            match ListItem::parse(&text) {
+                Some(item) => above.push(item),
+                None if text.trim().is_empty() => {}
+                None => break,
+            }
+        }
+        let target = if outdent {
+            list::outdented(&above, &item)
+        } else {
+            list::indented(&above, &item)
+        };
+        let delta = target as isize - item.indent as isize;
+        if delta == 0 {
+            return true;
+        }
+
+        buffer.begin_user_action();
+        for n in lines {
+            let Some((mut start, text)) = line_text(buffer, n) else {
+                continue;
+            };
+            let Some(item) = ListItem::parse(&text) else {
+                continue;
+            };
+            let indent = (item.indent as isize + delta).max(0) as usize;
+            let mut end = start;
+            end.forward_chars(item.indent as i32);
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &" ".repeat(indent));
+        }
+        buffer.end_user_action();
+        true
+    }
+
+    /// Toggles the task whose `[ ]` is at `iter`. Only task markers right
+    /// after a list marker count, not brackets elsewhere in the text.
+    fn toggle_task_at(buffer: &gtk::TextBuffer, iter: &gtk::TextIter) -> bool {
+        let Some((line_start, text)) = line_text(buffer, iter.line()) else {
+            return false;
+        };
+        let Some(item) = ListItem::parse(&text).filter(|_| !in_code_block(&line_start)) else {
+            return false;
+        };
+        // The prefix is ASCII, so inside it chars and bytes agree
+        match (item.task, item.task_range()) {
+            (Some(checked), Some(range)) if range.contains(&(iter.line_offset() as usize)) => {
+                Self::set_task(buffer, iter.line(), &item, !checked);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks the tasks on `lines`, or unchecks them if all are checked.
+    /// Returns false when there are no tasks there.
+    fn toggle_tasks(buffer: &gtk::TextBuffer, lines: RangeInclusive<i32>) -> bool {
+        let tasks: Vec<(i32, ListItem)> = lines
+            .filter_map(|n| {
+                let (line_start, text) = line_text(buffer, n)?;
+                let item = ListItem::parse(&text).filter(|_| !in_code_block(&line_start))?;
+                item.task.map(|_| (n, item))
+            })
+            .collect();
+        if tasks.is_empty() {
+            return false;
+        }
+
+        let checked = !tasks.iter().all(|(_, item)| item.task == Some(true));
+        buffer.begin_user_action();
+        for (n, item) in &tasks {
+            Self::set_task(buffer, *n, item, checked);
+        }
+        buffer.end_user_action();
+        true
+    }
+
+    fn set_task(buffer: &gtk::TextBuffer, line: i32, item: &ListItem, checked: bool) {
+        let Some(range) = item.task_range() else {
+            return;
+        };
+        let (Some(mut start), Some(mut end)) = (
+            buffer.iter_at_line_offset(line, range.start as i32 + 1),
+            buffer.iter_at_line_offset(line, range.start as i32 + 2),
+        ) else {
+            return;
+        };
+        buffer.begin_user_action();
+        buffer.delete(&mut start, &mut end);
+        buffer.insert(&mut start, if checked { "x" } else { " " });
+        buffer.end_user_action();
+    }
+
+    /// Toggles a kind of list marker on `lines`, see `list::toggle`.
+    fn toggle_list(buffer: &gtk::TextBuffer, lines: RangeInclusive<i32>, kind: ListKind) {
+        let texts: Vec<String> = lines
+            .clone()
+            .map(|n| line_text(buffer, n).map_or(String::new(), |(_, text)| text))
+            .collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+
+        buffer.begin_user_action();
+        for (n, edit) in lines.zip(list::toggle(&texts, kind)) {
+            let Some((range, prefix)) = edit else {
+                continue;
+            };
+            // Prefixes are ASCII, so their bytes are chars
+            let (Some(mut start), Some(mut end)) = (
+                buffer.iter_at_line_offset(n, range.start as i32),
+                buffer.iter_at_line_offset(n, range.end as i32),
+            ) else {
+                continue;
+            };
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &prefix);
+        }
+        buffer.end_user_action();
+    }
+
+    /// The table the cursor is in, found by the "table" tag highlighting
+    /// left on it.
+    fn table_at_cursor(buffer: &gtk::TextBuffer) -> Option<TableAt> {
+        let tag = buffer.tag_table().lookup("table")?;
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let line_start = buffer.iter_at_line(cursor.line())?;
+        if !line_start.has_tag(&tag) {
+            return None;
+        }
+
+        let mut start = line_start;
+        if !start.starts_tag(Some(&tag)) {
+            start.backward_to_tag_toggle(Some(&tag));
+        }
+        let mut end = start;
+        end.forward_to_tag_toggle(Some(&tag));
+        let table = Table::parse(&buffer.text(&start, &end, true))?;
+
+        let (_, text) = line_text(buffer, cursor.line())?;
+        let pos = buffer.text(&line_start, &cursor, true).len();
+        // The delimiter row has no cells of its own; treat it as the header
+        let line = match (cursor.line() - start.line()) as usize {
+            1 => 0,
+            line => line,
+        };
+        let col = table::column_at(&text, pos).min(table.columns() - 1);
+
+        Some(TableAt {
+            start: start.offset(),
+            end: end.offset(),
+            table,
+            line,
+            col,
+        })
+    }
+
+    /// Writes `table` back over the one at `at` as canonical Markdown and puts
+    /// the cursor at the end of a cell.
+    fn replace_table(
+        buffer: &gtk::TextBuffer,
+        at: &TableAt,
+        table: &Table,
+        line: usize,
+        col: usize,
+    ) {
+        let markdown = table.to_markdown();
+        let mut start = buffer.iter_at_offset(at.start);
+        let mut end = buffer.iter_at_offset(at.end);
+        if buffer.text(&start, &end, true).as_str() != markdown {
+            buffer.begin_user_action();
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &markdown);
+            buffer.end_user_action();
+        }
+        let cell = table.cell_range(line, col);
+        buffer.place_cursor(&buffer.iter_at_offset(at.start + cell.end as i32));
+    }
+
+    /// Moves to the next (or previous) table cell, adding a row after the
+    /// last one. Returns false when the cursor isn't in a table.
+    fn table_tab(buffer: &gtk::TextBuffer, backwards: bool) -> bool {
+        let Some(at) = Self::table_at_cursor(buffer) else {
+            return false;
+        };
+        let mut table = at.table.clone();
+        let columns = table.columns();
+        let (mut line, mut col) = (at.line, at.col);
+
+        if backwards {
+            if col > 0 {
+                col -= 1;
+            } else if line > 0 {
+                line = if line == 2 { 0 } else { line - 1 };
+                col = columns - 1;
+            }
+        } else if col + 1 < columns {
+            col += 1;
+        } else {
+            line = if line == 0 { 2 } else { line + 1 };
+            col = 0;
+            if line - 2 == table.rows.len() {
+                table.insert_row(table.rows.len());
+            }
+        }
+
+        Self::replace_table(buffer, &at, &table, line, col);
+        true
+    }
+
+    fn edit_table(buffer: &gtk::TextBuffer, edit: TableEdit) {
+        let Some(at) = Self::table_at_cursor(buffer) else {
+            if let TableEdit::Insert = edit {
+                Self::insert_table(buffer);
+            }
+            return;
+        };
+        let mut table = at.table.clone();
+        let (mut line, mut col) = (at.line, at.col);
+
+        match edit {
+            // Tables don't nest
+            TableEdit::Insert => return,
+            TableEdit::AddRow => {
+                let row = line.saturating_sub(1);
+                table.insert_row(row);
+                line = row + 2;
+            }
+            TableEdit::RemoveRow => {
+                if line < 2 {
+                    return;
+                }
+                table.remove_row(line - 2);
+                line = line.min(table.rows.len() + 1);
+                if line == 1 {
+                    line = 0;
+                }
+            }
+            TableEdit::AddColumn => {
+                table.insert_column(col + 1);
+                col += 1;
+            }
+            TableEdit::RemoveColumn => {
+                table.remove_column(col);
+                col = col.min(table.columns() - 1);
+            }
+        }
+
+        Self::replace_table(buffer, &at, &table, line, col);
+    }
+
+    /// Inserts a two-column table on its own lines and selects the first
+    /// header cell.
+    fn insert_table(buffer: &gtk::TextBuffer) {
+        let table = Table::new(2);
+        let mut iter = buffer.iter_at_mark(&buffer.get_insert());
+
+        let mut line_end = iter;
+        if !line_end.ends_line() {
+            line_end.forward_to_line_end();
+        }
+        let rest_of_line = buffer.text(&iter, &line_end, true);
+        let prefix = if iter.starts_line() { "" } else { "\n" };
+        // Keep whatever follows from being read as another row
+        let suffix = if !rest_of_line.is_empty() {
+            "\n\n"
+        } else if !iter.is_end() {
+            "\n"
+        } else {
+            ""
+        };
+
+        let start = iter.offset() + prefix.len() as i32;
+        buffer.begin_user_action();
+        buffer.insert(
+            &mut iter,
+            &format!("{}{}{}", prefix, table.to_markdown(), suffix),
+        );
+        buffer.end_user_action();
+
+        let cell = table.cell_range(0, 0);
+        buffer.select_range(
+            &buffer.iter_at_offset(start + cell.end as i32),
+            &buffer.iter_at_offset(start + cell.start as i32),
+        );
+    }
+
+    /// The link at `iter`, with buffer char offsets.
+    fn link_at(buffer: &gtk::TextBuffer, iter: &gtk::TextIter) -> Option<LinkAt> {
+        let (text, pos) = text_and_position(buffer, iter);
+        let link = links::link_at(&text, pos)?;
+        let offsets = CharOffsets::new(&text, 0);
+        Some(LinkAt {
+            start: offsets.get(link.range.start),
+            end: offsets.get(link.range.end),
+            text: text[link.text].to_string(),
+            url: link.url,
+        })
+    }
+
+    /// The title named by the `[[wiki link]]` at `iter`.
+    fn wiki_link_at(buffer: &gtk::TextBuffer, iter: &gtk::TextIter) -> Option<String> {
+        let (text, pos) = text_and_position(buffer, iter);
+        links::wiki_link_at(&text, pos).map(|link| link.title)
+    }
+
+    /// The title typed so far after an unclosed `[[` before the cursor, on
+    /// the cursor's line. There's none once the shown text after a `|` is
+    /// being typed.
+    fn wiki_link_query(buffer: &gtk::TextBuffer) -> Option<String> {
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        if in_code_block(&cursor) {
+            return None;
+        }
+        let mut line_start = cursor;
+        line_start.set_line_offset(0);
+        let before = buffer.text(&line_start, &cursor, true);
+        let query = &before[before.rfind("[[")? + 2..];
+        (!query.contains(['[', ']', '|'])).then(|| query.to_string())
+    }
+
+    /// Replaces the title typed so far of the `[[wiki link]]` at the cursor
+    /// with `title`, closing the link if it isn't yet.
+    fn complete_wiki_link(buffer: &gtk::TextBuffer, title: &str) {
+        let Some(query) = Self::wiki_link_query(buffer) else {
+            return;
+        };
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let start = cursor.offset() - query.chars().count() as i32;
+        let mut after = cursor;
+        after.forward_chars(2);
+        let closed = buffer.text(&cursor, &after, true) == "]]";
+
+        buffer.begin_user_action();
+        buffer.delete(&mut buffer.iter_at_offset(start), &mut cursor.clone());
+        let mut iter = buffer.iter_at_offset(start);
+        if closed {
+            buffer.insert(&mut iter, title);
+            iter.forward_chars(2);
+        } else {
+            buffer.insert(&mut iter, &format!("{}]]", title));
+        }
+        buffer.place_cursor(&iter);
+        buffer.end_user_action();
+    }
+
+    /// Suggests note titles while a `[[wiki link]]` is typed at the cursor.
+    fn update_completion(&self) {
+        let (Some(note_id), Some(text_view)) = (self.note_id, &self.text_view) else {
+            return;
+        };
+        let Some(query) = Self::wiki_link_query(&self.buffer) else {
+            self.completion.hide();
+            return;
+        };
+        match self.note_service.suggest_titles(&query, note_id) {
+            Ok(titles) if !titles.is_empty() => {
+                let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert());
+                self.completion
+                    .show(&titles, iter_rectangle(text_view, &cursor));
+            }
+            Ok(_) => self.completion.hide(),
+            Err(e) => {
+                eprintln!("Failed to suggest note titles: {}", e);
+                self.completion.hide();
+            }
+        }
+    }
+
+    /// Tells the app about a title that changed since it was loaded or last
+    /// committed, so links to the old title can follow it. This waits for
+    /// the title entry to be left; following every keystroke would rewrite
+    /// links to each half-typed title.
+    fn commit_title(&mut self, sender: &ComponentSender<Self>) {
+        let Some(id) = self.note_id else {
+            return;
+        };
+        if self.title.trim() != self.saved_title.trim() {
+            let old = std::mem::replace(&mut self.saved_title, self.title.clone());
+            let _ = sender.output(EditorOutput::TitleRenamed(id, old, self.title.clone()));
+        }
+    }
+
+    /// The blocks around the selection, parsed, with the selection in bytes
+    /// of their text and the buffer offset their text starts at.
+    fn parse_selection(&self) -> Option<(Reparse, Range<usize>, i32)> {
+        let code = self.buffer.tag_table().lookup("code")?;
+        let (start, end) = self.selection();
+        let lines = start.line() as usize..end.line() as usize + 1;
+        let reparse = incremental::reparse(
+            &BufferLines {
+                buffer: &self.buffer,
+                code,
+            },
+            lines,
+        );
+        let (selection, base) = self.selection_in(&reparse);
+        Some((reparse, selection, base))
+    }
+
+    /// The selection's bounds, or the cursor's position twice.
+    fn selection(&self) -> (gtk::TextIter, gtk::TextIter) {
+        self.buffer.selection_bounds().unwrap_or_else(|| {
+            let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert());
+            (cursor, cursor)
+        })
+    }
+
+    /// The selection in bytes of the text of `reparse`, which has to take
+    /// in its lines, and the buffer offset that text starts at.
+    fn selection_in(&self, reparse: &Reparse) -> (Range<usize>, i32) {
+        let (start, end) = self.selection();
+        let base = line_start(&self.buffer, reparse.lines.start).offset();
+        let byte = |iter: &gtk::TextIter| {
+            let chars = (iter.offset() - base).max(0) as usize;
+            reparse
+                .text
+                .char_indices()
+                .nth(chars)
+                .map_or(reparse.text.len(), |(i, _)| i)
+        };
+        (byte(&start)..byte(&end), base)
+    }
+
+    /// Turns `format` on or off for the selection, as one step to undo.
+    fn toggle_format(&mut self, format: Format) {
+        let Some((reparse, selection, base)) = self.parse_selection() else {
+            return;
+        };
+        let toggle = format::toggle(&reparse.text, &reparse.blocks, selection, format);
+
+        let buffer = &self.buffer;
+        let offsets = CharOffsets::new(&reparse.text, base);
+        buffer.begin_user_action();
+        // Back to front, so earlier offsets stay valid
+        for (range, with) in toggle.edits.iter().rev() {
+            let mut from = buffer.iter_at_offset(offsets.get(range.start));
+            let mut to = buffer.iter_at_offset(offsets.get(range.end));
+            buffer.delete(&mut from, &mut to);
+            buffer.insert(&mut from, with);
+        }
+        buffer.end_user_action();
+
+        let edited = toggle.apply(&reparse.text);
+        let offsets = CharOffsets::new(&edited, base);
+        buffer.select_range(
+            &buffer.iter_at_offset(offsets.get(toggle.selection.end)),
+            &buffer.iter_at_offset(offsets.get(toggle.selection.start)),
+        );
+    }
+
+    /// Tells the toolbar about the formats at the cursor, when they change.
+    /// `parsed` is what highlighting just parsed; if it takes in the
+    /// selection, the formats are read from it instead of parsing again.
+    fn update_formats(&mut self, parsed: Option<&Reparse>) {
+        let (start, end) = self.selection();
+        let at = (start.offset(), end.offset());
+        // Moving the cursor sets two marks, and typing may move it too
+        if parsed.is_none() && self.formats_at == Some(at) {
+            return;
+        }
+        self.formats_at = Some(at);
+
+        let lines = start.line() as usize..end.line() as usize + 1;
+        let formats = match parsed {
+            _ if self.note_id.is_none() => Vec::new(),
+            Some(reparse)
+                if reparse.lines.start <= lines.start && lines.end <= reparse.lines.end =>
+            {
+                format::active(&reparse.blocks, self.selection_in(reparse).0)
+            }
+            _ => match self.parse_selection() {
+                Some((reparse, selection, _)) => format::active(&reparse.blocks, selection),
+                None => Vec::new(),
+            },
+        };
+        if formats != self.formats {
+            self.formats = formats.clone();
+            self.toolbar.emit(ToolbarMsg::SetActive(formats));
+        }
+    }
+
+    /// Runs the find bar's search over the buffer again and marks the
+    /// matches, the selected one standing out.
+    fn refresh_find(&mut self) {
+        let (start, end) = self.buffer.bounds();
+        self.buffer.remove_tag_by_name("find-match", &start, &end);
+        let Some(find) = &mut self.find else {
+            self.buffer.remove_tag_by_name("find-current", &start, &end);
+            return;
+        };
+        find.matches = match &find.finder {
+            Some(finder) => {
+                let text = self.buffer.slice(&start, &end, true);
+                let offsets = CharOffsets::new(&text, 0);
+                finder
+                    .matches(&text)
+                    .into_iter()
+                    .map(|m| offsets.get(m.start)..offsets.get(m.end))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        for m in &find.matches {
+            self.buffer.apply_tag_by_name(
+                "find-match",
+                &self.buffer.iter_at_offset(m.start),
+                &self.buffer.iter_at_offset(m.end),
+            );
+        }
+        self.mark_current_match();
+    }
+
+    /// Marks the selected match and tells the find bar where it is.
+    fn mark_current_match(&self) {
+        let (start, end) = self.buffer.bounds();
+        self.buffer.remove_tag_by_name("find-current", &start, &end);
+        let Some(find) = &self.find else {
+            return;
+        };
+        let current = self.current_match();
+        if let Some(m) = current.map(|i| &find.matches[i]) {
+            self.buffer.apply_tag_by_name(
+                "find-current",
+                &self.buffer.iter_at_offset(m.start),
+                &self.buffer.iter_at_offset(m.end),
+            );
+        }
+        self.find_bar.emit(FindBarMsg::Status(
+            current,
+            find.matches.len(),
+            find.invalid,
+        ));
+    }
+
+    /// The match that is exactly the selection.
+    fn current_match(&self) -> Option<usize> {
+        let find = self.find.as_ref()?;
+        let (start, end) = self.selection_offsets();
+        find.matches
+            .iter()
+            .position(|m| m.start == start && m.end == end)
+    }
+
+    /// The selection's bounds, or the cursor twice.
+    fn selection_offsets(&self) -> (i32, i32) {
+        match self.buffer.selection_bounds() {
+            Some((start, end)) => (start.offset(), end.offset()),
+            None => {
+                let cursor = self.buffer.cursor_position();
+                (cursor, cursor)
+            }
+        }
+    }
+
+    fn select_match(&self, index: usize) {
+        let Some(m) = self.find.as_ref().and_then(|find| find.matches.get(index)) else {
+            return;
+        };
+        self.buffer.select_range(
+            &self.buffer.iter_at_offset(m.end),
+            &self.buffer.iter_at_offset(m.start),
+        );
+        self.mark_current_match();
+        self.scroll_to_cursor();
+    }
+
+    /// Selects the match after the selection, or before it, wrapping around.
+    fn find_next(&self, backwards: bool) {
+        let Some(find) = &self.find else {
+            return;
+        };
+        if find.matches.is_empty() {
+            return;
+        }
+        let (start, end) = self.selection_offsets();
+        let index = if backwards {
+            find.matches
+                .iter()
+                .rposition(|m| m.end <= start)
+                .unwrap_or(find.matches.len() - 1)
+        } else {
+            find.matches
+                .iter()
+                .position(|m| m.start >= end)
+                .unwrap_or(0)
+        };
+        self.select_match(index);
+    }
+
+    /// Replaces the selected match, or every match, as one step to undo.
+    /// Returns whether anything was replaced.
+    fn replace_matches(&self, selected_only: bool, replacement: &str) -> bool {
+        let Some(finder) = self.find.as_ref().and_then(|find| find.finder.as_ref()) else {
+            return false;
+        };
+        let buffer = &self.buffer;
+        let (start, end) = buffer.bounds();
+        // Matched afresh, in case the text changed since the last search
+        let text = buffer.slice(&start, &end, true);
+        let offsets = CharOffsets::new(&text, 0);
+        let selection = self.selection_offsets();
+        let matches: Vec<Range<usize>> = finder
+            .matches(&text)
+            .into_iter()
+            .filter(|m| !selected_only || (offsets.get(m.start), offsets.get(m.end)) == selection)
+            .collect();
+        if matches.is_empty() {
+            return false;
+        }
+
+        buffer.begin_user_action();
+        // Back to front, so earlier offsets stay valid
+        for m in matches.into_iter().rev() {
+            let with = finder.replacement(&text, m.clone(), replacement);
+            let mut from = buffer.iter_at_offset(offsets.get(m.start));
+            let mut to = buffer.iter_at_offset(offsets.get(m.end));
+            buffer.delete(&mut from, &mut to);
+            buffer.insert(&mut from, &with);
+        }
+        buffer.end_user_action();
+        true
+    }
+
+    /// Scrolls the cursor into view, once the view shows the current buffer.
+    fn scroll_to_cursor(&self) {
+        if let Some(text_view) = self.text_view.clone() {
+            glib::idle_add_local_once(move || {
+                let cursor = text_view.buffer().get_insert();
+                text_view.scroll_to_mark(&cursor, 0.0, true, 0.0, 0.3);
+            });
+        }
+    }
+
+    fn refresh_backlinks(&self) {
+        let notes = match self.note_id {
+            Some(id) => self
+                .note_service
+                .get_backlinks(id, &self.saved_title)
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to load backlinks: {}", e);
+                    Vec::new()
+                }),
+            None => Vec::new(),
+        };
+        self.backlinks
+            .sender()
+            .send(BacklinksMsg::Update(notes, self.saved_title.clone()))
+            .unwrap();
+    }
+
+    /// Opens the link popover for the link at the cursor, or for a new link
+    /// replacing the selection.
+    fn show_link_popover(&mut self) {
+        let Some(text_view) = &self.text_view else {
+            return;
+        };
+        let buffer = &self.buffer;
+        let (start, end) = buffer.selection_bounds().unwrap_or_else(|| {
+            let cursor = buffer.iter_at_mark(&buffer.get_insert());
+            (cursor, cursor)
+        });
+        let (start, end, text, url) = match Self::link_at(buffer, &start) {
+            Some(link) => (
+                buffer.iter_at_offset(link.start),
+                buffer.iter_at_offset(link.end),
+                link.text,
+                link.url,
+            ),
+            None => (
+                start,
+                end,
+                buffer.text(&start, &end, false).to_string(),
+                String::new(),
+            ),
+        };
+
+        if let Some(marks) = self.link_range.take() {
+            delete_marks(marks);
+        }
+        self.link_range = Some((
+            buffer.create_mark(None, &start, true),
+            buffer.create_mark(None, &end, false),
+        ));
+
+        let target = iter_rectangle(text_view, &start);
+        self.link_popover
+            .sender()
+            .send(LinkPopoverMsg::Show { text, url, target })
+            .unwrap();
+    }
+
+    /// Opens `url` in the desktop's app for it; links to attachments open
+    /// the attached file.
+    fn open_link(&self, url: &str) {
+        let window = self
+            .text_view
+            .as_ref()
+            .and_then(|text_view| text_view.root())
+            .and_downcast::<gtk::Window>();
+        if let Some(id) = attachment::id_from_url(url) {
+            match self.note_service.get_attachment(id) {
+                Ok(Some(attachment)) => {
+                    let file = gio::File::for_path(self.note_service.attachment_path(&attachment));
+                    gtk::FileLauncher::new(Some(&file)).launch(
+                        window.as_ref(),
+                        gio::Cancellable::NONE,
+                        |result| {
+                            if let Err(e) = result {
+                                eprintln!("Failed to open attachment: {}", e);
                             }
-                        }
-                    }
-                 }
-             }
+                        },
+                    );
+                }
+                Ok(None) => eprintln!("Attachment {} no longer exists", id),
+                Err(e) => eprintln!("Failed to load attachment: {}", e),
+            }
+            return;
+        }
+
+        let Some(url) = links::normalize_url(url) else {
+            eprintln!("Can't open link target: {}", url);
+            return;
+        };
+        gtk::UriLauncher::new(&url).launch(window.as_ref(), gio::Cancellable::NONE, |result| {
+            if let Err(e) = result {
+                eprintln!("Failed to open link: {}", e);
+            }
+        });
+    }
+
+    /// Shows or hides the heading markers on `line`.
+    fn reveal_heading_markers(buffer: &gtk::TextBuffer, line: i32, shown: bool) {
+        let tags = buffer.tag_table();
+        let (Some(marker), Some(hidden)) = (tags.lookup("heading-marker"), tags.lookup("hidden"))
+        else {
+            return;
+        };
+        let Some(mut iter) = buffer.iter_at_line(line) else {
+            return;
+        };
+        let mut line_end = iter;
+        if !line_end.ends_line() {
+            line_end.forward_to_line_end();
+        }
+
+        while iter < line_end {
+            if iter.has_tag(&marker) {
+                let mut end = iter;
+                end.forward_to_tag_toggle(Some(&marker));
+                if shown {
+                    buffer.remove_tag(&hidden, &iter, &end);
+                } else {
+                    buffer.apply_tag(&hidden, &iter, &end);
+                }
+                iter = end;
+            } else if !iter.forward_to_tag_toggle(Some(&marker)) {
+                break;
+            }
+        }
+    }
+
+    /// Syntax highlighting between the fences of a code block, in the
+    /// language named after the opening fence.
+    fn highlight_code(buffer: &gtk::TextBuffer, text: &str, offsets: &CharOffsets, block: &Span) {
+        let SpanKind::CodeBlock { info } = &block.kind else {
+            return;
+        };
+        let mut markers = block.children.iter().filter(|c| c.kind == SpanKind::Marker);
+        let content_start = markers.next().map_or(block.range.start, |m| m.range.end);
+        let content_end = markers.next().map_or(block.range.end, |m| m.range.start);
+        let code = &text[content_start..content_end];
+
+        for (range, token) in syntax::highlight(info, code) {
+            let tag = match token {
+                Token::Keyword => "keyword",
+                Token::Type => "type",
+                Token::String => "string",
+                Token::Number => "number",
+                Token::Comment => "comment",
+            };
+            buffer.apply_tag_by_name(
+                tag,
+                &buffer.iter_at_offset(offsets.get(content_start + range.start)),
+                &buffer.iter_at_offset(offsets.get(content_start + range.end)),
+            );
+        }
+    }
+}
+
+/// A table found around the cursor. Offsets are buffer chars; `line` counts
+/// the delimiter row, like `Table::cell_range`.
+struct TableAt {
+    start: i32,
+    end: i32,
+    table: Table,
+    line: usize,
+    col: usize,
+}
+
+/// A link found in the buffer. Offsets are buffer chars.
+struct LinkAt {
+    start: i32,
+    end: i32,
+    text: String,
+    url: String,
+}
+
+/// The buffer's text, anchors included, and the byte position of `iter` in
+/// it.
+fn text_and_position(buffer: &gtk::TextBuffer, iter: &gtk::TextIter) -> (glib::GString, usize) {
+    let (start, end) = buffer.bounds();
+    let pos = buffer.slice(&start, iter, true).len();
+    (buffer.slice(&start, &end, true), pos)
+}
+
+/// Where `iter` is shown, in the text view's widget coordinates; for
+/// pointing popovers at.
+fn iter_rectangle(text_view: &gtk::TextView, iter: &gtk::TextIter) -> gdk::Rectangle {
+    let location = text_view.iter_location(iter);
+    let (x, y) =
+        text_view.buffer_to_window_coords(gtk::TextWindowType::Widget, location.x(), location.y());
+    gdk::Rectangle::new(x, y, 1, location.height())
+}
+
+/// Deletes a pair of marks from whichever buffer they're in.
+fn delete_marks((start, end): (gtk::TextMark, gtk::TextMark)) {
+    if let Some(buffer) = start.buffer() {
+        buffer.delete_mark(&start);
+        buffer.delete_mark(&end);
+    }
+}
+
+/// A picture of an attached image, shown in the text after its Markdown.
+fn image_preview(path: &Path) -> gtk::Picture {
+    let picture = gtk::Picture::for_filename(path);
+    picture.set_content_fit(gtk::ContentFit::ScaleDown);
+    picture.set_height_request(200); // Limit height
+    picture.set_margin_top(10);
+    picture.set_margin_bottom(10);
+    picture
+}
+
+/// A chip for an attached file: its icon, name and size. Clicking it opens
+/// the file with its default app; the button next to it saves a copy.
+fn attachment_chip(attachment: &Attachment, path: PathBuf) -> gtk::Box {
+    let icon = attachment
+        .mime_type
+        .as_deref()
+        .and_then(gio::content_type_from_mime_type)
+        .map(|content_type| gio::content_type_get_symbolic_icon(&content_type))
+        .unwrap_or_else(|| gio::ThemedIcon::new("text-x-generic-symbolic").upcast());
+
+    let label = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    label.append(&gtk::Image::from_gicon(&icon));
+    let name = gtk::Label::new(Some(attachment.file_name.as_str()));
+    name.set_ellipsize(gtk::pango::EllipsizeMode::Middle);
+    name.set_max_width_chars(40);
+    label.append(&name);
+    let size = gtk::Label::new(Some(glib::format_size(attachment.size_bytes).as_str()));
+    size.add_css_class("dim-label");
+    label.append(&size);
+
+    let open = gtk::Button::new();
+    open.set_child(Some(&label));
+    open.add_css_class("flat");
+    open.set_tooltip_text(Some("Open"));
+    let file = gio::File::for_path(&path);
+    open.connect_clicked(move |button| {
+        let window = button.root().and_downcast::<gtk::Window>();
+        gtk::FileLauncher::new(Some(&file)).launch(
+            window.as_ref(),
+            gio::Cancellable::NONE,
+            |result| {
+                if let Err(e) = result {
+                    eprintln!("Failed to open attachment: {}", e);
+                }
+            },
+        );
+    });
+
+    let save = gtk::Button::from_icon_name("document-save-as-symbolic");
+    save.add_css_class("flat");
+    save.set_tooltip_text(Some("Save As…"));
+    let file_name = attachment.file_name.clone();
+    save.connect_clicked(move |button| {
+        let window = button.root().and_downcast::<gtk::Window>();
+        let dialog = gtk::FileDialog::builder()
+            .title("Save Attachment")
+            .initial_name(file_name.as_str())
+            .modal(true)
+            .build();
+        let path = path.clone();
+        dialog.save(window.as_ref(), gio::Cancellable::NONE, move |result| {
+            // An error here usually means the dialog was cancelled
+            let Some(target) = result.ok().and_then(|file| file.path()) else {
+                return;
+            };
+            if let Err(e) = std::fs::copy(&path, &target) {
+                eprintln!("Failed to save attachment to {}: {}", target.display(), e);
+            }
+        });
+    });
+
+    let chip = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    chip.add_css_class("attachment-chip");
+    chip.set_margin_top(4);
+    chip.set_margin_bottom(4);
+    chip.append(&open);
+    chip.append(&save);
+    chip
+}
+
+/// MIME type of a file, going by its name and contents.
+fn mime_type(path: &Path) -> Option<String> {
+    let info = gio::File::for_path(path)
+        .query_info(
+            "standard::content-type",
+            gio::FileQueryInfoFlags::NONE,
+            gio::Cancellable::NONE,
+        )
+        .ok()?;
+    gio::content_type_get_mime_type(&info.content_type()?).map(String::from)
+}
+
+/// The start of line `n` and its text, without the line ending.
+fn line_text(buffer: &gtk::TextBuffer, n: i32) -> Option<(gtk::TextIter, String)> {
+    let start = buffer.iter_at_line(n)?;
+    let mut end = start;
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    Some((start, buffer.text(&start, &end, true).into()))
+}
+
+/// The lines touched by the selection, or the cursor's line. A selection
+/// ending at the start of a line doesn't include that line.
+fn selected_lines(buffer: &gtk::TextBuffer) -> RangeInclusive<i32> {
+    let (start, end) = buffer.selection_bounds().unwrap_or_else(|| {
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        (cursor, cursor)
+    });
+    let last = if end.starts_line() && end.line() > start.line() {
+        end.line() - 1
+    } else {
+        end.line()
+    };
+    start.line()..=last
+}
+
+/// Whether `iter` is inside a fenced code block, going by its highlighting.
+fn in_code_block(iter: &gtk::TextIter) -> bool {
+    iter.buffer()
+        .tag_table()
+        .lookup("code")
+        .is_some_and(|code| iter.has_tag(&code))
+}
+
+/// The start of line `n`, or the end of the buffer past its last line.
+fn line_start(buffer: &gtk::TextBuffer, n: usize) -> gtk::TextIter {
+    buffer
+        .iter_at_line(n as i32)
+        .unwrap_or_else(|| buffer.end_iter())
+}
+
+/// The editor's buffer as the incremental parser sees it. Code blocks are
+/// recognised by the "code" tag the last highlighting pass left behind.
+struct BufferLines<'a> {
+    buffer: &'a gtk::TextBuffer,
+    code: gtk::TextTag,
+}
+
+impl LineSource for BufferLines<'_> {
+    fn line_count(&self) -> usize {
+        self.buffer.line_count() as usize
+    }
+
+    fn line(&self, n: usize) -> String {
+        let start = line_start(self.buffer, n);
+        let mut end = start;
+        if !end.ends_line() {
+            end.forward_to_line_end();
+        }
+        self.buffer.slice(&start, &end, true).into()
+    }
+
+    fn text(&self, lines: Range<usize>) -> String {
+        let start = line_start(self.buffer, lines.start);
+        let end = line_start(self.buffer, lines.end);
+        // Slices keep the placeholder char of embedded images, so offsets
+        // into the text match the buffer's
+        self.buffer.slice(&start, &end, true).into()
+    }
+
+    fn in_code(&self, n: usize) -> bool {
+        // The line break before a line inside a code block carries the tag
+        self.buffer
+            .iter_at_line(n as i32)
+            .is_some_and(|mut iter| iter.backward_char() && iter.has_tag(&self.code))
+    }
+}
+
+/// Converts the parser's byte offsets into the buffer's char offsets. Keeps
+/// the char offset of every line start so a lookup only counts within a line.
+struct CharOffsets<'a> {
+    text: &'a str,
+    lines: Vec<(usize, i32)>,
+}
+
+impl<'a> CharOffsets<'a> {
+    /// `base` is the buffer offset where `text` starts.
+    fn new(text: &'a str, base: i32) -> Self {
+        let mut lines = vec![(0, base)];
+        let mut chars = base;
+        for (byte, c) in text.char_indices() {
+            chars += 1;
+            if c == '\n' {
+                lines.push((byte + 1, chars));
+            }
         }
+        Self { text, lines }
+    }
+
+    fn get(&self, byte: usize) -> i32 {
+        let line = self.lines.partition_point(|&(start, _)| start <= byte) - 1;
+        let (start, chars) = self.lines[line];
+        chars + self.text[start..byte].chars().count() as i32
     }
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for Editor {
-    type Init = ();
+    type Init = Rc<NoteService>;
     type Input = EditorMsg;
-    type Output = EditorMsg; // Forward changes to parent for autosave
+    type Output = EditorOutput; // Forward changes to parent for autosave
 
     view! {
         gtk::Overlay {
@@ -240,11 +1723,59 @@ impl SimpleComponent for Editor {
                     if is_ctrl {
                         match keyval {
                             gtk::gdk::Key::b | gtk::gdk::Key::B => {
-                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Bold));
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Format(
+                                    Format::Bold,
+                                )));
                                 return gtk::glib::Propagation::Stop;
                             }
                             gtk::gdk::Key::i | gtk::gdk::Key::I => {
-                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Italic));
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Format(
+                                    Format::Italic,
+                                )));
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::u | gtk::gdk::Key::U => {
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Format(
+                                    Format::Underline,
+                                )));
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::e | gtk::gdk::Key::E => {
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Format(
+                                    Format::Code,
+                                )));
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::X | gtk::gdk::Key::x
+                                if state.contains(gtk::gdk::ModifierType::SHIFT_MASK) =>
+                            {
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Format(
+                                    Format::Strikethrough,
+                                )));
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::H | gtk::gdk::Key::h
+                                if state.contains(gtk::gdk::ModifierType::SHIFT_MASK) =>
+                            {
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Format(
+                                    Format::Highlight,
+                                )));
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::k | gtk::gdk::Key::K => {
+                                sender.input(EditorMsg::ToolbarMsg(ToolbarMsg::Link));
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::f | gtk::gdk::Key::F => {
+                                sender.input(EditorMsg::ShowFind);
+                                return gtk::glib::Propagation::Stop;
+                            }
+                            gtk::gdk::Key::z | gtk::gdk::Key::Z => {
+                                if state.contains(gtk::gdk::ModifierType::SHIFT_MASK) {
+                                    sender.input(EditorMsg::Redo);
+                                } else {
+                                    sender.input(EditorMsg::Undo);
+                                }
                                 return gtk::glib::Propagation::Stop;
                             }
                             _ => {}
@@ -258,23 +1789,46 @@ impl SimpleComponent for Editor {
             set_child = &gtk::Box {
                 set_orientation: gtk::Orientation::Vertical,
 
-                // Title Input
-                gtk::Entry {
-                    set_placeholder_text: Some("Note Title"),
-                    set_css_classes: &["title-1", "editor-title"],
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
                     set_margin_top: 12,
                     set_margin_start: 32,
                     set_margin_end: 32,
                     set_margin_bottom: 12,
 
-                    #[track(model.should_update_title)]
-                    set_text: &model.title,
-
-                    connect_changed[sender] => move |entry| {
-                        sender.input(EditorMsg::UpdateTitle(entry.text().to_string()));
-                    }
+                    // Title Input
+                    gtk::Entry {
+                        set_placeholder_text: Some("Note Title"),
+                        set_css_classes: &["title-1", "editor-title"],
+                        set_hexpand: true,
+                        #[watch]
+                        set_sensitive: model.note_id.is_some(),
+
+                        #[track(model.should_update_title)]
+                        set_text: &model.title,
+
+                        connect_changed[sender] => move |entry| {
+                            sender.input(EditorMsg::UpdateTitle(entry.text().to_string()));
+                        },
+                        connect_activate => EditorMsg::CommitTitle,
+
+                        add_controller = gtk::EventControllerFocus {
+                            connect_leave => EditorMsg::CommitTitle,
+                        },
+                    },
+
+                    gtk::Button {
+                        set_icon_name: "document-open-recent-symbolic",
+                        set_tooltip_text: Some("Note History"),
+                        set_css_classes: &["flat", "toolbar-btn"],
+                        set_valign: gtk::Align::Center,
+                        connect_clicked => EditorMsg::ShowHistory,
+                    },
                 },
 
+                append: model.backlinks.widget(),
+
                 // Content Area
                 gtk::ScrolledWindow {
                     set_vexpand: true,
@@ -288,6 +1842,8 @@ impl SimpleComponent for Editor {
                         set_left_margin: 32,
                         set_right_margin: 32,
                         set_css_classes: &["editor-content"],
+                        #[watch]
+                        set_editable: model.note_id.is_some(),
 
                         #[track(model.should_reload_buffer)]
                         set_buffer: Some(&model.buffer),
@@ -300,94 +1856,252 @@ impl SimpleComponent for Editor {
                 set_valign: gtk::Align::End,
                 set_margin_bottom: 24,
             },
+
+            add_overlay = model.find_bar.widget() {
+                set_halign: gtk::Align::End,
+                set_valign: gtk::Align::Start,
+                set_margin_top: 12,
+                set_margin_end: 32,
+            },
         }
     }
 
     fn init(
-        _: Self::Init,
+        note_service: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let buffer = Self::create_buffer("");
+        let dirty = Rc::new(Cell::new(DirtyRange::default()));
+        let loading = Rc::new(Cell::new(false));
+        let buffer = Self::create_buffer("", &dirty, &loading, &sender);
 
         let toolbar = Toolbar::builder()
             .launch(())
             .forward(sender.input_sender(), EditorMsg::ToolbarMsg);
 
+        let link_popover =
+            LinkPopover::builder()
+                .launch(())
+                .forward(sender.input_sender(), |msg| match msg {
+                    LinkPopoverOutput::Apply(text, url) => EditorMsg::InsertLink(text, url),
+                });
+
+        let find_bar = FindBar::builder()
+            .launch(())
+            .forward(sender.input_sender(), EditorMsg::Find);
+
+        let backlinks = Backlinks::builder()
+            .launch(())
+            .forward(sender.input_sender(), |msg| match msg {
+                BacklinksOutput::Open(id) => EditorMsg::OpenNote(id),
+            });
+
         let model = Editor {
-            content: String::new(),
+            note_id: None,
             title: String::new(),
+            saved_title: String::new(),
             should_reload_buffer: false,
             should_update_title: false,
             buffer,
+            buffers: HashMap::new(),
+            recent: Recent::new(MAX_BUFFERS),
+            dirty,
+            loading,
             toolbar,
+            link_popover,
+            link_range: None,
+            completion: NoteCompletion::new(),
+            backlinks,
+            find_bar,
+            find: None,
+            formats: Vec::new(),
+            formats_at: None,
             text_view: None,
+            note_service,
         };
 
         let widgets = view_output!();
 
-        // Connect to initial buffer
-        widgets
-            .text_view
-            .buffer()
-            .connect_changed(glib::clone!(@strong sender => move |buffer| {
-                let (start, end) = buffer.bounds();
-                let text = buffer.text(&start, &end, true).to_string();
-                sender.input(EditorMsg::UpdateContent(text));
-            }));
-
-        // Connect on buffer replacement
-        widgets.text_view.connect_buffer_notify(glib::clone!(@strong sender => move |text_view| {
-            text_view.buffer().connect_changed(glib::clone!(@strong sender => move |_| {
-                sender.input(EditorMsg::Highlight);
-            }));
-
-            text_view.buffer().connect_changed(glib::clone!(@strong sender, @strong text_view => move |_| {
-                let buffer = text_view.buffer();
-                let (start, end) = buffer.bounds();
-                let text = buffer.text(&start, &end, true);
-                sender.input(EditorMsg::UpdateContent(text.to_string()));
-            }));
+        // Tab moves between table cells and nests list items, Enter continues
+        // lists and Ctrl+Enter checks tasks; anywhere else they're plain
+        // keys. Capture phase, so this runs before the text view handles them.
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let text_view = widgets.text_view.clone();
+        let completion = model.completion.clone();
+        keys.connect_key_pressed(move |_controller, keyval, _keycode, state| {
+            let buffer = text_view.buffer();
+            // While note titles are suggested, the arrows pick one and Enter
+            // or Tab takes it
+            if completion.is_visible() {
+                match keyval {
+                    gtk::gdk::Key::Up => completion.move_selection(-1),
+                    gtk::gdk::Key::Down => completion.move_selection(1),
+                    gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter | gtk::gdk::Key::Tab => {
+                        if let Some(title) = completion.selected() {
+                            Self::complete_wiki_link(&buffer, &title);
+                        }
+                        completion.hide();
+                    }
+                    gtk::gdk::Key::Escape => completion.hide(),
+                    _ => return gtk::glib::Propagation::Proceed,
+                }
+                return gtk::glib::Propagation::Stop;
+            }
+            let handled = match keyval {
+                gtk::gdk::Key::Tab | gtk::gdk::Key::ISO_Left_Tab => {
+                    let backwards = keyval == gtk::gdk::Key::ISO_Left_Tab
+                        || state.contains(gtk::gdk::ModifierType::SHIFT_MASK);
+                    Self::table_tab(&buffer, backwards) || Self::indent_list(&buffer, backwards)
+                }
+                gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter
+                    if state.contains(gtk::gdk::ModifierType::CONTROL_MASK) =>
+                {
+                    Self::toggle_tasks(&buffer, selected_lines(&buffer))
+                }
+                gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter
+                    if !state.intersects(
+                        gtk::gdk::ModifierType::SHIFT_MASK
+                            | gtk::gdk::ModifierType::CONTROL_MASK
+                            | gtk::gdk::ModifierType::ALT_MASK,
+                    ) =>
+                {
+                    Self::continue_list(&buffer)
+                }
+                _ => false,
+            };
+            if handled {
+                text_view.scroll_mark_onscreen(&buffer.get_insert());
+                gtk::glib::Propagation::Stop
+            } else {
+                gtk::glib::Propagation::Proceed
+            }
+        });
+        widgets.text_view.add_controller(keys);
+
+        // Dropped files are attached where they land
+        let drop = gtk::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+        drop.set_propagation_phase(gtk::PropagationPhase::Capture);
+        drop.connect_drop(glib::clone!(@strong sender => move |drop, value, x, y| {
+            let Ok(files) = value.get::<gdk::FileList>() else {
+                return false;
+            };
+            let paths: Vec<PathBuf> = files.files().iter().filter_map(|f| f.path()).collect();
+            if paths.is_empty() {
+                return false;
+            }
+            if let Some(text_view) = drop.widget().and_downcast::<gtk::TextView>() {
+                let (x, y) =
+                    text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+                if let Some(iter) = text_view.iter_at_location(x, y) {
+                    text_view.buffer().place_cursor(&iter);
+                }
+            }
+            sender.input(EditorMsg::AttachFiles(paths));
+            true
+        }));
+        widgets.text_view.add_controller(drop);
+
+        // Pasting an image or copied files attaches them instead of pasting text
+        widgets.text_view.connect_paste_clipboard(glib::clone!(@strong sender => move |text_view| {
+            let clipboard = text_view.clipboard();
+            let formats = clipboard.formats();
+            if formats.contain_gtype(gdk::Texture::static_type()) {
+                text_view.stop_signal_emission_by_name("paste-clipboard");
+                clipboard.read_texture_async(gio::Cancellable::NONE, glib::clone!(@strong sender => move |result| {
+                    match result {
+                        Ok(Some(texture)) => sender.input(EditorMsg::PasteImage(texture.save_to_png_bytes())),
+                        Ok(None) => {}
+                        Err(e) => eprintln!("Failed to paste image: {}", e),
+                    }
+                }));
+            } else if formats.contain_gtype(gdk::FileList::static_type()) {
+                text_view.stop_signal_emission_by_name("paste-clipboard");
+                clipboard.read_value_async(
+                    gdk::FileList::static_type(),
+                    glib::Priority::DEFAULT,
+                    gio::Cancellable::NONE,
+                    glib::clone!(@strong sender => move |result| {
+                        let files = result.ok().and_then(|value| value.get::<gdk::FileList>().ok());
+                        let paths: Vec<PathBuf> = files
+                            .map(|files| files.files().iter().filter_map(|f| f.path()).collect())
+                            .unwrap_or_default();
+                        if !paths.is_empty() {
+                            sender.input(EditorMsg::AttachFiles(paths));
+                        }
+                    }),
+                );
+            }
         }));
 
         // Send the text_view widget to the model for later use (e.g., image insertion)
 
         sender.input(EditorMsg::InitTextView(widgets.text_view.clone()));
 
-        // Add Click Handler for Checkboxes manually
+        // Clicking a task item's `[ ]` toggles it; Ctrl+clicking a link opens it
         let gesture = gtk::GestureClick::new();
+        let completion = model.completion.clone();
         gesture.connect_pressed(glib::clone!(@strong sender => move |gesture, _n_press, x, y| {
-            let text_view = gesture.widget().unwrap().downcast::<gtk::TextView>().unwrap();
+            completion.hide();
+            let text_view = gesture
+                .widget()
+                .unwrap()
+                .downcast::<gtk::TextView>()
+                .unwrap();
+            let (x_buffer, y_buffer) =
+                text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+            let Some(iter) = text_view.iter_at_location(x_buffer, y_buffer) else {
+                return;
+            };
             let buffer = text_view.buffer();
-
-            let (x_buffer, y_buffer) = text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
-            if let Some(iter) = text_view.iter_at_location(x_buffer, y_buffer) {
-                let mut start = iter;
-                start.backward_chars(3);
-                let mut end = iter;
-                end.forward_chars(3);
-
-                let text = buffer.text(&start, &end, false);
-
-                if let Some(pos) = text.find("[ ]") {
-                    let mut match_start = start;
-                    match_start.forward_chars(pos as i32);
-                    let mut match_end = match_start;
-                    match_end.forward_chars(3);
-                    buffer.delete(&mut match_start, &mut match_end);
-                    buffer.insert(&mut match_start, "[x]");
-                } else if let Some(pos) = text.find("[x]") {
-                    let mut match_start = start;
-                    match_start.forward_chars(pos as i32);
-                    let mut match_end = match_start;
-                    match_end.forward_chars(3);
-                    buffer.delete(&mut match_start, &mut match_end);
-                    buffer.insert(&mut match_start, "[ ]");
-                }
+            let open = if gesture
+                .current_event_state()
+                .contains(gdk::ModifierType::CONTROL_MASK)
+            {
+                Self::wiki_link_at(&buffer, &iter)
+                    .map(EditorMsg::OpenWikiLink)
+                    .or_else(|| {
+                        Self::link_at(&buffer, &iter).map(|link| EditorMsg::OpenLink(link.url))
+                    })
+            } else {
+                None
+            };
+            if let Some(open) = open {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                sender.input(open);
+            } else if Self::toggle_task_at(&buffer, &iter) {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
             }
         }));
         widgets.text_view.add_controller(gesture);
 
+        // Links get a pointer cursor, a hint that they can be Ctrl+clicked
+        let motion = gtk::EventControllerMotion::new();
+        motion.connect_motion(|motion, x, y| {
+            let Some(text_view) = motion.widget().and_downcast::<gtk::TextView>() else {
+                return;
+            };
+            let (x, y) =
+                text_view.window_to_buffer_coords(gtk::TextWindowType::Widget, x as i32, y as i32);
+            let buffer = text_view.buffer();
+            let on_link = text_view.iter_at_location(x, y).is_some_and(|iter| {
+                buffer
+                    .tag_table()
+                    .lookup("link")
+                    .is_some_and(|tag| iter.has_tag(&tag))
+            });
+            text_view.set_cursor_from_name(Some(if on_link { "pointer" } else { "text" }));
+        });
+        widgets.text_view.add_controller(motion);
+
+        model.link_popover.widget().set_parent(&widgets.text_view);
+        model.completion.set_parent(&widgets.text_view);
+        model
+            .completion
+            .connect_picked(glib::clone!(@strong sender => move |title| {
+                sender.input(EditorMsg::CompleteNoteTitle(title));
+            }));
+
         ComponentParts { model, widgets }
     }
 
@@ -395,234 +2109,280 @@ impl SimpleComponent for Editor {
         self.should_reload_buffer = false;
         self.should_update_title = false;
         match msg {
-            EditorMsg::UpdateContent(text) => {
-                // Prevent infinite loop if update comes from buffer
-                if self.content != text {
-                    self.content = text.clone();
-                    // Do NOT update tracker here. We don't want to reset the buffer while typing.
-                    let _ = sender.output(EditorMsg::UpdateContent(text));
+            EditorMsg::Edited(buffer) => {
+                // The signal may come from the buffer of the note just left
+                let Some(id) = self
+                    .buffers
+                    .iter()
+                    .find(|(_, note)| note.buffer == buffer)
+                    .map(|(id, _)| *id)
+                else {
+                    return;
+                };
+                if buffer == self.buffer {
+                    self.update_completion();
                 }
+                // Do NOT update tracker here. We don't want to reset the buffer while typing.
+                let _ = sender.output(EditorOutput::ContentChanged(id, buffer));
             }
             EditorMsg::UpdateTitle(text) => {
                 if self.title != text {
                     self.title = text.clone();
-                    let _ = sender.output(EditorMsg::UpdateTitle(text));
+                    if let Some(id) = self.note_id {
+                        let _ = sender.output(EditorOutput::TitleChanged(id, text));
+                    }
                 }
             }
             EditorMsg::Highlight => {
-                Self::highlight_buffer(&self.buffer);
+                let parsed = Self::highlight_dirty(&self.buffer, &self.dirty);
+                // Restyling took the match marks off the edited lines
+                if self.find.is_some() {
+                    self.refresh_find();
+                }
+                self.update_formats(parsed.as_ref());
+            }
+            EditorMsg::SelectionMoved => self.update_formats(None),
+            EditorMsg::ShowFind => {
+                // A selection within a line is what to look for
+                let selected = self
+                    .buffer
+                    .selection_bounds()
+                    .map(|(start, end)| self.buffer.text(&start, &end, false).to_string())
+                    .filter(|text| !text.contains('\n'));
+                self.find_bar.emit(FindBarMsg::Show(selected));
+            }
+            EditorMsg::Find(FindBarOutput::Search(query, options)) => {
+                let (finder, invalid) = match Finder::new(&query, options) {
+                    Ok(finder) => (finder, false),
+                    Err(_) => (None, true),
+                };
+                self.find = Some(Find {
+                    finder,
+                    invalid,
+                    matches: Vec::new(),
+                });
+                self.refresh_find();
+                // Typing moves on to the nearest match
+                if self.current_match().is_none() {
+                    let (start, _) = self.selection_offsets();
+                    let matches = self.find.as_ref().map_or(&[][..], |find| &find.matches[..]);
+                    if !matches.is_empty() {
+                        let next = matches.iter().position(|m| m.start >= start).unwrap_or(0);
+                        self.select_match(next);
+                    }
+                }
+            }
+            EditorMsg::Find(FindBarOutput::Next) => self.find_next(false),
+            EditorMsg::Find(FindBarOutput::Previous) => self.find_next(true),
+            EditorMsg::Find(FindBarOutput::Replace(replacement)) => {
+                if self.replace_matches(true, &replacement) {
+                    self.refresh_find();
+                }
+                self.find_next(false);
+            }
+            EditorMsg::Find(FindBarOutput::ReplaceAll(replacement)) => {
+                if self.replace_matches(false, &replacement) {
+                    self.refresh_find();
+                }
+            }
+            EditorMsg::Find(FindBarOutput::Closed) => {
+                self.find = None;
+                self.refresh_find();
+                if let Some(text_view) = &self.text_view {
+                    text_view.grab_focus();
+                }
+            }
+            EditorMsg::FindSearchTerms(query) => {
+                let (start, end) = self.buffer.bounds();
+                let text = self.buffer.slice(&start, &end, true);
+                if let Some((term, range)) = find::first_term(&text, &find::search_terms(&query)) {
+                    let offsets = CharOffsets::new(&text, 0);
+                    self.buffer.select_range(
+                        &self.buffer.iter_at_offset(offsets.get(range.end)),
+                        &self.buffer.iter_at_offset(offsets.get(range.start)),
+                    );
+                    self.scroll_to_cursor();
+                    self.find_bar.emit(FindBarMsg::Show(Some(term.to_string())));
+                }
+            }
+            EditorMsg::ForgetNotes(ids) => {
+                for id in ids {
+                    self.buffers.remove(&id);
+                    self.recent.remove(&id);
+                }
+            }
+            // The text view handles these keys itself while it has the
+            // focus; these come from the rest of the editor
+            EditorMsg::Undo => {
+                if self.buffer.can_undo() {
+                    self.buffer.undo();
+                }
+            }
+            EditorMsg::Redo => {
+                if self.buffer.can_redo() {
+                    self.buffer.redo();
+                }
+            }
+            EditorMsg::CommitTitle => self.commit_title(&sender),
+            EditorMsg::RefreshBacklinks => self.refresh_backlinks(),
+            EditorMsg::OpenWikiLink(title) => {
+                let _ = sender.output(EditorOutput::OpenNoteTitled(title));
+            }
+            EditorMsg::OpenNote(id) => {
+                let _ = sender.output(EditorOutput::OpenNote(id));
+            }
+            EditorMsg::CompleteNoteTitle(title) => {
+                Self::complete_wiki_link(&self.buffer, &title);
+                self.completion.hide();
+                if let Some(text_view) = &self.text_view {
+                    text_view.grab_focus();
+                }
             }
             EditorMsg::InitTextView(view) => {
                 self.text_view = Some(view);
             }
-            EditorMsg::LoadNote(title, content) => {
+            EditorMsg::ShowHistory => {
+                if let Some(id) = self.note_id {
+                    let _ = sender.output(EditorOutput::ShowHistory(id));
+                }
+            }
+            EditorMsg::Clear => {
+                self.commit_title(&sender);
+                self.completion.hide();
+                self.note_id = None;
+                self.title.clear();
+                self.saved_title.clear();
+                self.buffer = Self::create_buffer("", &self.dirty, &self.loading, &sender);
+                self.should_reload_buffer = true;
+                self.should_update_title = true;
+                self.refresh_backlinks();
+                self.refresh_find();
+                self.formats_at = None;
+                self.update_formats(None);
+            }
+            EditorMsg::LoadNote(id, title, content) => {
+                self.commit_title(&sender);
+                self.completion.hide();
+                self.note_id = Some(id);
+                self.saved_title = title.clone();
                 self.title = title;
-                self.content = content.clone();
 
-                // Create new buffer with tags
-                self.buffer = Self::create_buffer(&content);
-                Self::highlight_buffer(&self.buffer); // Syntax highlighting enabled
+                let attachments = self.load_buffer(id, &content, &sender);
+
+                // The view only switches to the new buffer once it updates
+                if let Some(text_view) = self.text_view.clone() {
+                    let buffer = self.buffer.clone();
+                    glib::idle_add_local_once(move || {
+                        if text_view.buffer() == buffer {
+                            for (anchor, widget) in attachments {
+                                // Reloading the open note keeps its previews
+                                if !anchor.is_deleted() && widget.parent().is_none() {
+                                    text_view.add_child_at_anchor(&widget, &anchor);
+                                }
+                            }
+                        }
+                    });
+                }
                 self.should_reload_buffer = true; // Only update buffer on load
                 self.should_update_title = true;
+                self.refresh_backlinks();
+                self.refresh_find();
+                self.formats_at = None;
+                self.update_formats(None);
             }
-            EditorMsg::InsertImage(path) => {
-                let buffer = &self.buffer;
-                let (mut start, mut end) = buffer.selection_bounds().unwrap_or_else(|| {
-                    let offset = buffer.cursor_position();
-                    let iter = buffer.iter_at_offset(offset);
-                    (iter, iter)
-                });
-
-                let start_mark = buffer.create_mark(None, &start, true);
-                buffer.delete(&mut start, &mut end);
-
-                let mut iter = buffer.iter_at_mark(&start_mark);
-                let text = format!("![Image]({})", path);
-                buffer.insert(&mut iter, &text);
-
-                // Insert Image Preview
-                if let Some(text_view) = &self.text_view {
-                    let mut anchor_iter = buffer.iter_at_mark(&start_mark);
-                    // Move after the inserted text to show preview below/after
-                    anchor_iter.forward_chars(text.chars().count() as i32);
-
-                    buffer.insert(&mut anchor_iter, "\n"); // Newline for image
-                    let anchor = buffer.create_child_anchor(&mut anchor_iter);
-
-                    let picture = gtk::Picture::for_filename(&path);
-                    picture.set_content_fit(gtk::ContentFit::ScaleDown);
-                    picture.set_height_request(200); // Limit height
-                    picture.set_margin_top(10);
-                    picture.set_margin_bottom(10);
-
-                    text_view.add_child_at_anchor(&picture, &anchor);
+            EditorMsg::GoToLine(line) => {
+                if let Some(iter) = self.buffer.iter_at_line(line as i32) {
+                    self.buffer.place_cursor(&iter);
+                }
+                // A freshly loaded buffer is only shown once the view updates
+                if let Some(text_view) = self.text_view.clone() {
+                    glib::idle_add_local_once(move || {
+                        text_view.grab_focus();
+                        let cursor = text_view.buffer().get_insert();
+                        text_view.scroll_to_mark(&cursor, 0.0, true, 0.0, 0.3);
+                    });
                 }
-
-                let start_iter = buffer.iter_at_mark(&start_mark);
-                let mut cursor = start_iter;
-                cursor.forward_chars(text.chars().count() as i32 + 1); // +1 for newline
-                buffer.place_cursor(&cursor);
-                buffer.delete_mark(&start_mark);
             }
+            EditorMsg::AttachFiles(files) => {
+                let Some(note_id) = self.note_id else {
+                    return;
+                };
+                let attachments: Vec<Attachment> = files
+                    .iter()
+                    .filter_map(|path| {
+                        self.note_service
+                            .attach_file(note_id, path, mime_type(path).as_deref())
+                            .map_err(|e| eprintln!("Failed to attach {}: {}", path.display(), e))
+                            .ok()
+                    })
+                    .collect();
+                self.insert_attachments(&attachments);
+            }
+            EditorMsg::PasteImage(png) => {
+                let Some(note_id) = self.note_id else {
+                    return;
+                };
+                match self.note_service.attach_bytes(
+                    note_id,
+                    "Pasted Image.png",
+                    Some("image/png"),
+                    &png,
+                ) {
+                    Ok(attachment) => self.insert_attachments(&[attachment]),
+                    Err(e) => eprintln!("Failed to attach pasted image: {}", e),
+                }
+            }
+            EditorMsg::InsertLink(text, url) => {
+                let Some((start_mark, end_mark)) = self.link_range.take() else {
+                    return;
+                };
+                // The note may have been switched while the popover was open
+                if start_mark.buffer().as_ref() == Some(&self.buffer) {
+                    let buffer = &self.buffer;
+                    buffer.begin_user_action();
+                    buffer.delete(
+                        &mut buffer.iter_at_mark(&start_mark),
+                        &mut buffer.iter_at_mark(&end_mark),
+                    );
+                    buffer.insert(
+                        &mut buffer.iter_at_mark(&start_mark),
+                        &links::markdown_link(&text, &url),
+                    );
+                    buffer.end_user_action();
+                    buffer.place_cursor(&buffer.iter_at_mark(&end_mark));
+                }
+                delete_marks((start_mark, end_mark));
+            }
+            EditorMsg::OpenLink(url) => self.open_link(&url),
+            // Links are edited in a popover rather than by inserting syntax
+            EditorMsg::ToolbarMsg(ToolbarMsg::Link) => self.show_link_popover(),
+            EditorMsg::ToolbarMsg(ToolbarMsg::Format(format)) => self.toggle_format(format),
             EditorMsg::ToolbarMsg(msg) => {
                 let buffer = &self.buffer;
-                let (start, end) = buffer.selection_bounds().unwrap_or_else(|| {
-                    let offset = buffer.cursor_position();
-                    let iter = buffer.iter_at_offset(offset);
-                    (iter, iter)
-                });
-
-                let has_selection = start.offset() != end.offset();
-
-                // Create marks to preserve positions across mutations
-                let start_mark = buffer.create_mark(None, &start, true);
-                let end_mark = buffer.create_mark(None, &end, false); // Left gravity for end mark so it stays after insertion at start? 
-                // Actually:
-                // If we insert at start, we want start_mark to stay before it? No, usually we wrap.
-                // Let's assume we wrap selection: **|text|**
-                // Insert "**" at start. Start mark should stay at original start?
-                // If we use left gravity (true), it stays to the left of inserted text.
-                // If we use right gravity (false), it moves to the right.
-                
-                // We want to wrap: insert at start, insert at end.
-                // It's safer to resolve iterators from marks every time.
-
+                // Each command is a single step to undo
+                buffer.begin_user_action();
                 match msg {
                     ToolbarMsg::Checkbox => {
-                        let mut iter = buffer.iter_at_mark(&start_mark);
-                        buffer.insert(&mut iter, "- [ ] ");
+                        Self::toggle_list(buffer, selected_lines(buffer), ListKind::Task);
                     }
-
-                    ToolbarMsg::Highlight => {
-                        if has_selection {
-                            // Insert at end first (to avoid affecting start offset if we used offsets, but marks handle this)
-                            // But inserting at start shifts end if we don't use marks.
-                            
-                            let mut end_iter = buffer.iter_at_mark(&end_mark);
-                            buffer.insert(&mut end_iter, "==");
-                            
-                            let mut start_iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut start_iter, "==");
-
-                            // Re-calculate ranges for tags
-                            let start_iter = buffer.iter_at_mark(&start_mark);
-                            let end_iter = buffer.iter_at_mark(&end_mark);
-                            
-                            // Apply tags
-                            // start_iter points to first "=". 
-                            // end_iter points after last "=".
-                            
-                            // We want to hide the "==" markers and highlight the text inside.
-                            let mut content_start = start_iter;
-                            content_start.forward_chars(2);
-                            
-                            let mut content_end = end_iter;
-                            content_end.backward_chars(2);
-                            
-                            buffer.apply_tag_by_name("highlight", &content_start, &content_end);
-                            buffer.apply_tag_by_name("hidden", &start_iter, &content_start);
-                            buffer.apply_tag_by_name("hidden", &content_end, &end_iter);
-                        } else {
-                            let mut iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut iter, "====");
-                            let mut cursor = buffer.iter_at_mark(&start_mark);
-                            cursor.forward_chars(2);
-                            buffer.place_cursor(&cursor);
+                    ToolbarMsg::Heading(level) => {
+                        for line in selected_lines(buffer) {
+                            Self::set_heading_level(buffer, line, level);
                         }
                     }
-                    ToolbarMsg::Bold => {
-                        if has_selection {
-                            let mut end_iter = buffer.iter_at_mark(&end_mark);
-                            buffer.insert(&mut end_iter, "**");
-                            
-                            let mut start_iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut start_iter, "**");
-
-                            let start_iter = buffer.iter_at_mark(&start_mark);
-                            let end_iter = buffer.iter_at_mark(&end_mark);
-                            
-                            let mut content_start = start_iter;
-                            content_start.forward_chars(2);
-                            
-                            let mut content_end = end_iter;
-                            content_end.backward_chars(2);
-                            
-                            buffer.apply_tag_by_name("bold", &content_start, &content_end);
-                            buffer.apply_tag_by_name("hidden", &start_iter, &content_start);
-                            buffer.apply_tag_by_name("hidden", &content_end, &end_iter);
-                        } else {
-                            let mut iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut iter, "****");
-                            let mut cursor = buffer.iter_at_mark(&start_mark);
-                            cursor.forward_chars(2);
-                            buffer.place_cursor(&cursor);
-                        }
-                    }
-                    ToolbarMsg::Italic => {
-                        if has_selection {
-                            let mut end_iter = buffer.iter_at_mark(&end_mark);
-                            buffer.insert(&mut end_iter, "_");
-                            
-                            let mut start_iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut start_iter, "_");
-
-                            let start_iter = buffer.iter_at_mark(&start_mark);
-                            let end_iter = buffer.iter_at_mark(&end_mark);
-                            
-                            let mut content_start = start_iter;
-                            content_start.forward_chars(1);
-                            
-                            let mut content_end = end_iter;
-                            content_end.backward_chars(1);
-                            
-                            buffer.apply_tag_by_name("italic", &content_start, &content_end);
-                            buffer.apply_tag_by_name("hidden", &start_iter, &content_start);
-                            buffer.apply_tag_by_name("hidden", &content_end, &end_iter);
-                        } else {
-                            let mut iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut iter, "__");
-                            let mut cursor = buffer.iter_at_mark(&start_mark);
-                            cursor.forward_chars(1);
-                            buffer.place_cursor(&cursor);
-                        }
+                    ToolbarMsg::Table(edit) => {
+                        Self::edit_table(buffer, edit);
                     }
                     ToolbarMsg::BulletList => {
-                        let mut iter = buffer.iter_at_mark(&start_mark);
-                        buffer.insert(&mut iter, "- ");
+                        Self::toggle_list(buffer, selected_lines(buffer), ListKind::Bullet);
                     }
                     ToolbarMsg::NumberedList => {
-                        let mut iter = buffer.iter_at_mark(&start_mark);
-                        buffer.insert(&mut iter, "1. ");
-                    }
-                    ToolbarMsg::Link => {
-                        if has_selection {
-                            let start_iter = buffer.iter_at_mark(&start_mark);
-                            let end_iter = buffer.iter_at_mark(&end_mark);
-                            let text = buffer.text(&start_iter, &end_iter, false);
-                            
-                            // Delete selection
-                            buffer.delete(&mut buffer.iter_at_mark(&start_mark), &mut buffer.iter_at_mark(&end_mark));
-                            
-                            let new_text = format!("[{}]()", text);
-                            let mut iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut iter, &new_text);
-
-                            let mut cursor = buffer.iter_at_mark(&start_mark);
-                            cursor.forward_chars(new_text.chars().count() as i32 - 1);
-                            buffer.place_cursor(&cursor);
-                        } else {
-                            let mut iter = buffer.iter_at_mark(&start_mark);
-                            buffer.insert(&mut iter, "[]()");
-                            let mut cursor = buffer.iter_at_mark(&start_mark);
-                            cursor.forward_chars(1);
-                            buffer.place_cursor(&cursor);
-                        }
+                        Self::toggle_list(buffer, selected_lines(buffer), ListKind::Numbered);
                     }
+                    // Handled above, or only ever sent to the toolbar
+                    ToolbarMsg::Link | ToolbarMsg::Format(_) | ToolbarMsg::SetActive(_) => {}
                 }
-                
-                // Clean up marks
-                buffer.delete_mark(&start_mark);
-                buffer.delete_mark(&end_mark);
+                buffer.end_user_action();
             }
         }
     }
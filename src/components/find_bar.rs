@@ -0,0 +1,292 @@
+use crate::utils::find::FindOptions;
+use gtk::glib;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+#[derive(Debug)]
+pub enum FindBarMsg {
+    /// Open the bar, searching for the given text if any
+    Show(Option<String>),
+    Hide,
+    SetQuery(String),
+    SetReplacement(String),
+    SetCaseSensitive(bool),
+    SetRegex(bool),
+    SetReplacing(bool),
+    Next,
+    Previous,
+    Replace,
+    ReplaceAll,
+    /// What the editor found: the current match, if one is selected, the
+    /// number of matches, and whether the query is a broken regex
+    Status(Option<usize>, usize, bool),
+}
+
+#[derive(Debug)]
+pub enum FindBarOutput {
+    Search(String, FindOptions),
+    Next,
+    Previous,
+    Replace(String),
+    ReplaceAll(String),
+    Closed,
+}
+
+/// Find and replace within the open note. The editor does the searching;
+/// this only holds the query and shows how it went.
+#[derive(Debug)]
+pub struct FindBar {
+    visible: bool,
+    replacing: bool,
+    query: String,
+    replacement: String,
+    options: FindOptions,
+    current: Option<usize>,
+    count: usize,
+    error: bool,
+    entry: gtk::SearchEntry,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for FindBar {
+    type Init = ();
+    type Input = FindBarMsg;
+    type Output = FindBarOutput;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 6,
+            set_css_classes: &["find-bar"],
+            #[watch]
+            set_visible: model.visible,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 2,
+
+                #[local_ref]
+                entry -> gtk::SearchEntry {
+                    set_placeholder_text: Some("Find in note"),
+                    set_width_chars: 24,
+                    #[watch]
+                    set_css_classes: if model.error { &["error"] } else { &[] },
+                    connect_search_changed[sender] => move |entry| {
+                        sender.input(FindBarMsg::SetQuery(entry.text().to_string()));
+                    },
+                    connect_activate => FindBarMsg::Next,
+                    connect_next_match => FindBarMsg::Next,
+                    connect_previous_match => FindBarMsg::Previous,
+                    connect_stop_search => FindBarMsg::Hide,
+                    add_controller = gtk::EventControllerKey {
+                        // Shift+Enter goes back, like Shift+Ctrl+G. Capture
+                        // phase, as the entry's text takes Enter for itself
+                        set_propagation_phase: gtk::PropagationPhase::Capture,
+                        connect_key_pressed[sender] => move |_, keyval, _, state| {
+                            if matches!(keyval, gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter)
+                                && state.contains(gtk::gdk::ModifierType::SHIFT_MASK)
+                            {
+                                sender.input(FindBarMsg::Previous);
+                                return glib::Propagation::Stop;
+                            }
+                            glib::Propagation::Proceed
+                        }
+                    },
+                },
+
+                gtk::Label {
+                    set_width_chars: 9,
+                    set_css_classes: &["dim-label", "caption"],
+                    #[watch]
+                    set_label: &model.status(),
+                },
+
+                gtk::Button {
+                    set_icon_name: "go-up-symbolic",
+                    set_tooltip_text: Some("Previous Match (Shift+Enter)"),
+                    set_css_classes: &["flat", "toolbar-btn"],
+                    #[watch]
+                    set_sensitive: model.count > 0,
+                    connect_clicked => FindBarMsg::Previous,
+                },
+                gtk::Button {
+                    set_icon_name: "go-down-symbolic",
+                    set_tooltip_text: Some("Next Match (Enter)"),
+                    set_css_classes: &["flat", "toolbar-btn"],
+                    #[watch]
+                    set_sensitive: model.count > 0,
+                    connect_clicked => FindBarMsg::Next,
+                },
+                gtk::ToggleButton {
+                    set_label: "Aa",
+                    set_tooltip_text: Some("Match Case"),
+                    set_css_classes: &["flat", "toolbar-btn"],
+                    connect_toggled[sender] => move |button| {
+                        sender.input(FindBarMsg::SetCaseSensitive(button.is_active()));
+                    },
+                },
+                gtk::ToggleButton {
+                    set_label: ".*",
+                    set_tooltip_text: Some("Regular Expression"),
+                    set_css_classes: &["flat", "toolbar-btn"],
+                    connect_toggled[sender] => move |button| {
+                        sender.input(FindBarMsg::SetRegex(button.is_active()));
+                    },
+                },
+                gtk::ToggleButton {
+                    set_icon_name: "edit-find-replace-symbolic",
+                    set_tooltip_text: Some("Replace"),
+                    set_css_classes: &["flat", "toolbar-btn"],
+                    #[watch]
+                    set_active: model.replacing,
+                    connect_toggled[sender] => move |button| {
+                        sender.input(FindBarMsg::SetReplacing(button.is_active()));
+                    },
+                },
+                gtk::Button {
+                    set_icon_name: "window-close-symbolic",
+                    set_tooltip_text: Some("Close (Escape)"),
+                    set_css_classes: &["flat", "toolbar-btn"],
+                    connect_clicked => FindBarMsg::Hide,
+                },
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 4,
+                #[watch]
+                set_visible: model.replacing,
+
+                gtk::Entry {
+                    set_placeholder_text: Some("Replace with"),
+                    set_hexpand: true,
+                    connect_changed[sender] => move |entry| {
+                        sender.input(FindBarMsg::SetReplacement(entry.text().to_string()));
+                    },
+                    connect_activate => FindBarMsg::Replace,
+                },
+                gtk::Button {
+                    set_label: "Replace",
+                    #[watch]
+                    set_sensitive: model.count > 0,
+                    connect_clicked => FindBarMsg::Replace,
+                },
+                gtk::Button {
+                    set_label: "All",
+                    set_tooltip_text: Some("Replace All"),
+                    #[watch]
+                    set_sensitive: model.count > 0,
+                    connect_clicked => FindBarMsg::ReplaceAll,
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = FindBar {
+            visible: false,
+            replacing: false,
+            query: String::new(),
+            replacement: String::new(),
+            options: FindOptions::default(),
+            current: None,
+            count: 0,
+            error: false,
+            entry: gtk::SearchEntry::new(),
+        };
+        let entry = &model.entry;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            FindBarMsg::Show(query) => {
+                self.visible = true;
+                if let Some(query) = query {
+                    // Taken first, so the SetQuery the entry sends back for
+                    // it changes nothing
+                    self.query = query.clone();
+                    self.entry.set_text(&query);
+                }
+                // It can only take the focus once it's shown
+                let entry = self.entry.clone();
+                glib::idle_add_local_once(move || {
+                    entry.grab_focus();
+                    entry.select_region(0, -1);
+                });
+                self.search(&sender);
+            }
+            FindBarMsg::Hide => {
+                if self.visible {
+                    self.visible = false;
+                    let _ = sender.output(FindBarOutput::Closed);
+                }
+            }
+            FindBarMsg::SetQuery(query) => {
+                if query != self.query {
+                    self.query = query;
+                    self.search(&sender);
+                }
+            }
+            FindBarMsg::SetReplacement(replacement) => {
+                self.replacement = replacement;
+            }
+            FindBarMsg::SetCaseSensitive(case_sensitive) => {
+                self.options.case_sensitive = case_sensitive;
+                self.search(&sender);
+            }
+            FindBarMsg::SetRegex(regex) => {
+                self.options.regex = regex;
+                self.search(&sender);
+            }
+            FindBarMsg::SetReplacing(replacing) => {
+                self.replacing = replacing;
+            }
+            FindBarMsg::Next => {
+                let _ = sender.output(FindBarOutput::Next);
+            }
+            FindBarMsg::Previous => {
+                let _ = sender.output(FindBarOutput::Previous);
+            }
+            FindBarMsg::Replace => {
+                let _ = sender.output(FindBarOutput::Replace(self.replacement.clone()));
+            }
+            FindBarMsg::ReplaceAll => {
+                let _ = sender.output(FindBarOutput::ReplaceAll(self.replacement.clone()));
+            }
+            FindBarMsg::Status(current, count, error) => {
+                self.current = current;
+                self.count = count;
+                self.error = error;
+            }
+        }
+    }
+}
+
+impl FindBar {
+    fn search(&self, sender: &ComponentSender<Self>) {
+        let _ = sender.output(FindBarOutput::Search(self.query.clone(), self.options));
+    }
+
+    fn status(&self) -> String {
+        if self.query.is_empty() {
+            String::new()
+        } else if self.error {
+            "Invalid".to_string()
+        } else if self.count == 0 {
+            "No matches".to_string()
+        } else if let Some(current) = self.current {
+            format!("{} of {}", current + 1, self.count)
+        } else if self.count == 1 {
+            "1 match".to_string()
+        } else {
+            format!("{} matches", self.count)
+        }
+    }
+}
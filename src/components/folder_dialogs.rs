@@ -0,0 +1,376 @@
+use crate::models::folder::{self, Folder, FolderNameError, NoteDisposal};
+use gtk::prelude::*;
+use relm4::prelude::*;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum NewFolderMsg {
+    /// Open the dialog. `locations` are the possible parents with their
+    /// display paths; `parent_id` is preselected.
+    Show {
+        folders: Vec<Folder>,
+        locations: Vec<(Option<Uuid>, String)>,
+        parent_id: Option<Uuid>,
+    },
+    Hide,
+    SetName(String),
+    SetLocation(u32),
+    Create,
+}
+
+#[derive(Debug)]
+pub enum NewFolderOutput {
+    Create(String, Option<Uuid>), // Name, Parent ID
+}
+
+pub struct NewFolderDialog {
+    visible: bool,
+    // Set by Show so the widgets pick up the new name and location
+    reset: bool,
+    folders: Vec<Folder>,
+    locations: Vec<Option<Uuid>>,
+    location_names: gtk::StringList,
+    location: u32,
+    name: String,
+    error: Option<FolderNameError>,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for NewFolderDialog {
+    type Init = ();
+    type Input = NewFolderMsg;
+    type Output = NewFolderOutput;
+
+    view! {
+        gtk::Window {
+            set_title: Some("New Folder"),
+            set_default_width: 380,
+            set_modal: true,
+            set_resizable: false,
+            set_hide_on_close: true,
+            #[watch]
+            set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(NewFolderMsg::Hide);
+                gtk::glib::Propagation::Proceed
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 8,
+                set_margin_all: 16,
+
+                gtk::Label {
+                    set_label: "Name",
+                    set_halign: gtk::Align::Start,
+                    set_css_classes: &["heading"],
+                },
+                gtk::Entry {
+                    #[track(model.reset)]
+                    set_text: &model.name,
+                    #[watch]
+                    set_css_classes: if model.error.is_some() { &["error"] } else { &[] },
+                    connect_changed[sender] => move |entry| {
+                        sender.input(NewFolderMsg::SetName(entry.text().to_string()));
+                    },
+                    connect_activate => NewFolderMsg::Create,
+                },
+                gtk::Label {
+                    #[watch]
+                    set_label: &model.error.as_ref().map(|e| e.to_string()).unwrap_or_default(),
+                    #[watch]
+                    set_visible: model.error.is_some(),
+                    set_halign: gtk::Align::Start,
+                    set_css_classes: &["error", "caption"],
+                },
+
+                gtk::Label {
+                    set_label: "Location",
+                    set_halign: gtk::Align::Start,
+                    set_margin_top: 8,
+                    set_css_classes: &["heading"],
+                },
+                gtk::DropDown {
+                    set_model: Some(&model.location_names),
+                    #[track(model.reset)]
+                    set_selected: model.location,
+                    connect_selected_notify[sender] => move |dropdown| {
+                        sender.input(NewFolderMsg::SetLocation(dropdown.selected()));
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    set_halign: gtk::Align::End,
+                    set_margin_top: 12,
+
+                    gtk::Button {
+                        set_label: "Cancel",
+                        connect_clicked => NewFolderMsg::Hide,
+                    },
+                    gtk::Button {
+                        set_label: "Create",
+                        set_css_classes: &["suggested-action"],
+                        #[watch]
+                        set_sensitive: model.error.is_none(),
+                        connect_clicked => NewFolderMsg::Create,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = NewFolderDialog {
+            visible: false,
+            reset: false,
+            folders: Vec::new(),
+            locations: Vec::new(),
+            location_names: gtk::StringList::new(&[]),
+            location: 0,
+            name: String::new(),
+            error: None,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        self.reset = false;
+        match msg {
+            NewFolderMsg::Show {
+                folders,
+                locations,
+                parent_id,
+            } => {
+                let names: Vec<&str> = locations.iter().map(|(_, name)| name.as_str()).collect();
+                self.location_names
+                    .splice(0, self.location_names.n_items(), &names);
+                self.locations = locations.into_iter().map(|(id, _)| id).collect();
+                self.location = self
+                    .locations
+                    .iter()
+                    .position(|id| *id == parent_id)
+                    .unwrap_or(0) as u32;
+                self.name = folder::unique_name("New Folder", parent_id, &folders);
+                self.folders = folders;
+                self.validate();
+                self.reset = true;
+                self.visible = true;
+            }
+            NewFolderMsg::Hide => {
+                self.visible = false;
+            }
+            NewFolderMsg::SetName(name) => {
+                self.name = name;
+                self.validate();
+            }
+            NewFolderMsg::SetLocation(index) => {
+                self.location = index;
+                self.validate();
+            }
+            NewFolderMsg::Create => {
+                if let Ok(name) =
+                    folder::validate_name(&self.name, self.parent_id(), None, &self.folders)
+                {
+                    self.visible = false;
+                    let _ = sender.output(NewFolderOutput::Create(name, self.parent_id()));
+                }
+            }
+        }
+    }
+}
+
+impl NewFolderDialog {
+    fn parent_id(&self) -> Option<Uuid> {
+        self.locations
+            .get(self.location as usize)
+            .copied()
+            .flatten()
+    }
+
+    fn validate(&mut self) {
+        self.error = folder::validate_name(&self.name, self.parent_id(), None, &self.folders).err();
+    }
+}
+
+#[derive(Debug)]
+pub enum DeleteFolderMsg {
+    /// Open the dialog for `folder`. `targets` are the places its notes can
+    /// go, with labels; the first one is preselected.
+    Show {
+        folder: Folder,
+        note_count: usize,
+        targets: Vec<(NoteDisposal, String)>,
+    },
+    Hide,
+    SetTarget(u32),
+    Delete,
+}
+
+#[derive(Debug)]
+pub enum DeleteFolderOutput {
+    Delete(Uuid, NoteDisposal),
+}
+
+pub struct DeleteFolderDialog {
+    visible: bool,
+    reset: bool,
+    folder: Option<Folder>,
+    note_count: usize,
+    targets: Vec<NoteDisposal>,
+    target_names: gtk::StringList,
+    target: u32,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for DeleteFolderDialog {
+    type Init = ();
+    type Input = DeleteFolderMsg;
+    type Output = DeleteFolderOutput;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Delete Folder"),
+            set_default_width: 380,
+            set_modal: true,
+            set_resizable: false,
+            set_hide_on_close: true,
+            #[watch]
+            set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(DeleteFolderMsg::Hide);
+                gtk::glib::Propagation::Proceed
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 8,
+                set_margin_all: 16,
+
+                gtk::Label {
+                    #[watch]
+                    set_label: &format!(
+                        "Delete “{}”?",
+                        model.folder.as_ref().map(|f| f.name.as_str()).unwrap_or("")
+                    ),
+                    set_halign: gtk::Align::Start,
+                    set_wrap: true,
+                    set_css_classes: &["title-4"],
+                },
+                gtk::Label {
+                    #[watch]
+                    set_label: &match model.note_count {
+                        0 => "The folder and its subfolders are empty.".to_string(),
+                        1 => "The folder and its subfolders contain 1 note.".to_string(),
+                        n => format!("The folder and its subfolders contain {} notes.", n),
+                    },
+                    set_halign: gtk::Align::Start,
+                    set_wrap: true,
+                },
+
+                gtk::Label {
+                    set_label: "Move notes to",
+                    set_halign: gtk::Align::Start,
+                    set_margin_top: 8,
+                    set_css_classes: &["heading"],
+                    #[watch]
+                    set_visible: model.note_count > 0,
+                },
+                gtk::DropDown {
+                    set_model: Some(&model.target_names),
+                    #[track(model.reset)]
+                    set_selected: model.target,
+                    #[watch]
+                    set_visible: model.note_count > 0,
+                    connect_selected_notify[sender] => move |dropdown| {
+                        sender.input(DeleteFolderMsg::SetTarget(dropdown.selected()));
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 8,
+                    set_halign: gtk::Align::End,
+                    set_margin_top: 12,
+
+                    gtk::Button {
+                        set_label: "Cancel",
+                        connect_clicked => DeleteFolderMsg::Hide,
+                    },
+                    gtk::Button {
+                        set_label: "Delete",
+                        set_css_classes: &["destructive-action"],
+                        connect_clicked => DeleteFolderMsg::Delete,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = DeleteFolderDialog {
+            visible: false,
+            reset: false,
+            folder: None,
+            note_count: 0,
+            targets: Vec::new(),
+            target_names: gtk::StringList::new(&[]),
+            target: 0,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        self.reset = false;
+        match msg {
+            DeleteFolderMsg::Show {
+                folder,
+                note_count,
+                targets,
+            } => {
+                let names: Vec<&str> = targets.iter().map(|(_, name)| name.as_str()).collect();
+                self.target_names
+                    .splice(0, self.target_names.n_items(), &names);
+                self.targets = targets.into_iter().map(|(target, _)| target).collect();
+                self.target = 0;
+                self.folder = Some(folder);
+                self.note_count = note_count;
+                self.reset = true;
+                self.visible = true;
+            }
+            DeleteFolderMsg::Hide => {
+                self.visible = false;
+            }
+            DeleteFolderMsg::SetTarget(index) => {
+                self.target = index;
+            }
+            DeleteFolderMsg::Delete => {
+                let Some(folder) = self.folder.take() else {
+                    return;
+                };
+                let target = self
+                    .targets
+                    .get(self.target as usize)
+                    .copied()
+                    .unwrap_or(NoteDisposal::Trash);
+                self.visible = false;
+                let _ = sender.output(DeleteFolderOutput::Delete(folder.id, target));
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::core::note_service::NoteService;
+use crate::models::note::Note;
+use crate::models::revision::Revision;
+use crate::utils::diff::{self, DiffKind};
+use gtk::pango;
+use gtk::prelude::*;
+use relm4::factory::FactoryVecDeque;
+use relm4::prelude::*;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub struct RevisionRow {
+    pub revision: Revision,
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for RevisionRow {
+    type Init = Revision;
+    type Input = ();
+    type Output = ();
+    type CommandOutput = ();
+    type ParentWidget = gtk::ListBox;
+
+    view! {
+        gtk::ListBoxRow {
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 4,
+                set_margin_all: 8,
+
+                gtk::Label {
+                    set_label: &revision_time(&self.revision),
+                    set_halign: gtk::Align::Start,
+                    set_css_classes: &["sidebar-title"],
+                },
+                gtk::Label {
+                    set_label: &self.revision.title,
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: pango::EllipsizeMode::End,
+                    set_css_classes: &["sidebar-preview"],
+                },
+            }
+        }
+    }
+
+    fn init_model(
+        revision: Self::Init,
+        _index: &DynamicIndex,
+        _sender: FactorySender<Self>,
+    ) -> Self {
+        Self { revision }
+    }
+}
+
+#[derive(Debug)]
+pub enum HistoryMsg {
+    Show(Note),
+    Hide,
+    SelectRevision(usize),
+    /// Pick what the selected revision is compared with, by its position in
+    /// the "Compare with" menu
+    CompareWith(u32),
+    Restore,
+}
+
+#[derive(Debug)]
+pub enum HistoryOutput {
+    Restored(Note),
+}
+
+/// What the selected revision is diffed against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Base {
+    /// The revision before it
+    Previous,
+    /// The note as it is now
+    Current,
+    /// The revision at this index
+    Revision(usize),
+}
+
+// The "Compare with" menu lists these two, then every revision
+const BASE_PREVIOUS: u32 = 0;
+const BASE_CURRENT: u32 = 1;
+const BASE_REVISIONS: u32 = 2;
+
+pub struct History {
+    note_service: Rc<NoteService>,
+    note: Option<Note>,
+    revisions: FactoryVecDeque<RevisionRow>,
+    selected: Option<usize>,
+    base: Base,
+    base_menu: gtk::DropDown,
+    diff_buffer: gtk::TextBuffer,
+    visible: bool,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for History {
+    type Init = Rc<NoteService>;
+    type Input = HistoryMsg;
+    type Output = HistoryOutput;
+
+    view! {
+        gtk::Window {
+            set_title: Some("Note History"),
+            set_default_size: (860, 560),
+            set_modal: true,
+            set_hide_on_close: true,
+            #[watch]
+            set_visible: model.visible,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(HistoryMsg::Hide);
+                gtk::glib::Propagation::Proceed
+            },
+
+            #[wrap(Some)]
+            set_titlebar = &gtk::HeaderBar {
+                pack_start = &gtk::Box {
+                    set_spacing: 6,
+
+                    gtk::Label {
+                        set_label: "Compare with",
+                    },
+                    #[local_ref]
+                    base_menu -> gtk::DropDown {
+                        connect_selected_notify[sender] => move |menu| {
+                            sender.input(HistoryMsg::CompareWith(menu.selected()));
+                        },
+                    },
+                },
+                pack_end = &gtk::Button {
+                    set_label: "Restore",
+                    set_css_classes: &["suggested-action"],
+                    #[watch]
+                    set_sensitive: model.selected.is_some(),
+                    connect_clicked => HistoryMsg::Restore,
+                },
+            },
+
+            gtk::Paned {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_position: 240,
+                set_shrink_start_child: false,
+
+                #[wrap(Some)]
+                set_start_child = &gtk::ScrolledWindow {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+
+                    #[local_ref]
+                    revision_list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::Single,
+                        set_css_classes: &["navigation-sidebar"],
+
+                        connect_row_activated[sender] => move |_, row| {
+                            if row.index() >= 0 {
+                                sender.input(HistoryMsg::SelectRevision(row.index() as usize));
+                            }
+                        }
+                    },
+                },
+
+                #[wrap(Some)]
+                set_end_child = &gtk::ScrolledWindow {
+                    set_hexpand: true,
+
+                    gtk::TextView {
+                        set_editable: false,
+                        set_cursor_visible: false,
+                        set_monospace: true,
+                        set_top_margin: 12,
+                        set_left_margin: 12,
+                        set_right_margin: 12,
+                        set_buffer: Some(&model.diff_buffer),
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        note_service: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let revisions = FactoryVecDeque::builder()
+            .launch(gtk::ListBox::default())
+            .detach();
+
+        let diff_buffer = gtk::TextBuffer::new(None);
+        diff_buffer.create_tag(
+            Some("added"),
+            &[
+                ("paragraph-background", &"#E6FFED"),
+                ("foreground", &"#22863A"),
+            ],
+        );
+        diff_buffer.create_tag(
+            Some("removed"),
+            &[
+                ("paragraph-background", &"#FFEEF0"),
+                ("foreground", &"#B31D28"),
+            ],
+        );
+
+        let model = History {
+            note_service,
+            note: None,
+            revisions,
+            selected: None,
+            base: Base::Previous,
+            base_menu: gtk::DropDown::from_strings(&["Previous revision", "Current note"]),
+            diff_buffer,
+            visible: false,
+        };
+
+        let revision_list = model.revisions.widget();
+        let base_menu = &model.base_menu;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            HistoryMsg::Show(note) => {
+                let revisions = self
+                    .note_service
+                    .get_revisions(note.id)
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to load revisions: {}", e);
+                        Vec::new()
+                    });
+
+                let mut bases = vec!["Previous revision".to_string(), "Current note".to_string()];
+                bases.extend(revisions.iter().map(revision_time));
+                let bases: Vec<&str> = bases.iter().map(String::as_str).collect();
+
+                {
+                    let mut guard = self.revisions.guard();
+                    guard.clear();
+                    for revision in revisions {
+                        guard.push_back(revision);
+                    }
+                }
+
+                self.base = Base::Previous;
+                self.base_menu
+                    .set_model(Some(&gtk::StringList::new(&bases)));
+                self.base_menu.set_selected(BASE_PREVIOUS);
+                self.note = Some(note);
+                self.visible = true;
+                self.select(0);
+            }
+            HistoryMsg::Hide => {
+                self.visible = false;
+                self.note = None;
+            }
+            HistoryMsg::SelectRevision(index) => self.select(index),
+            HistoryMsg::CompareWith(position) => {
+                let base = match position {
+                    BASE_PREVIOUS => Base::Previous,
+                    BASE_CURRENT => Base::Current,
+                    n => Base::Revision((n - BASE_REVISIONS) as usize),
+                };
+                if base != self.base {
+                    self.base = base;
+                    if let Some(index) = self.selected {
+                        self.select(index);
+                    }
+                }
+            }
+            HistoryMsg::Restore => {
+                let Some(row) = self.selected.and_then(|i| self.revisions.get(i)) else {
+                    return;
+                };
+                match self.note_service.restore_revision(row.revision.id) {
+                    Ok(note) => {
+                        self.visible = false;
+                        self.note = None;
+                        let _ = sender.output(HistoryOutput::Restored(note));
+                    }
+                    Err(e) => eprintln!("Failed to restore revision: {}", e),
+                }
+            }
+        }
+    }
+}
+
+impl History {
+    /// Select a revision and show what changed: against the revision before
+    /// it, the note as it is now, or the revision picked to compare with.
+    fn select(&mut self, index: usize) {
+        self.diff_buffer.set_text("");
+        let Some(revision) = self.revisions.get(index).map(|row| &row.revision) else {
+            self.selected = None;
+            return;
+        };
+        self.selected = Some(index);
+
+        if let Some(row) = self.revisions.widget().row_at_index(index as i32) {
+            self.revisions.widget().select_row(Some(&row));
+        }
+
+        let lines = match self.base {
+            Base::Current => {
+                let current = self.note.as_ref().map(|n| n.content.as_str()).unwrap_or("");
+                Ok(diff::diff_lines(&revision.content, current))
+            }
+            // Revisions are newest first, so the older neighbour is at index + 1
+            Base::Previous => match self.revisions.get(index + 1) {
+                Some(previous) => self
+                    .note_service
+                    .diff_revisions(previous.revision.id, revision.id),
+                None => Ok(diff::diff_lines("", &revision.content)),
+            },
+            Base::Revision(base) => match self.revisions.get(base) {
+                Some(base) => self
+                    .note_service
+                    .diff_revisions(base.revision.id, revision.id),
+                None => Ok(Vec::new()),
+            },
+        };
+        let lines = lines.unwrap_or_else(|e| {
+            eprintln!("Failed to diff revisions: {}", e);
+            Vec::new()
+        });
+
+        let buffer = &self.diff_buffer;
+        for line in lines {
+            let (prefix, tag) = match line.kind {
+                DiffKind::Unchanged => ("  ", None),
+                DiffKind::Added => ("+ ", Some("added")),
+                DiffKind::Removed => ("- ", Some("removed")),
+            };
+            let text = format!("{}{}\n", prefix, line.text);
+            let mut end = buffer.end_iter();
+            match tag {
+                Some(tag) => buffer.insert_with_tags_by_name(&mut end, &text, &[tag]),
+                None => buffer.insert(&mut end, &text),
+            }
+        }
+    }
+}
+
+/// When a revision was last edited, as the list and the menu show it.
+fn revision_time(revision: &Revision) -> String {
+    revision
+        .updated_at
+        .with_timezone(&chrono::Local)
+        .format("%b %e, %Y  %H:%M")
+        .to_string()
+}
@@ -0,0 +1,165 @@
+use crate::utils::links;
+use gtk::gdk;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+#[derive(Debug)]
+pub enum LinkPopoverMsg {
+    /// Open pointing at `target`, in the parent's coordinates. `url` is
+    /// empty for a new link.
+    Show {
+        text: String,
+        url: String,
+        target: gdk::Rectangle,
+    },
+    Hide,
+    SetText(String),
+    SetUrl(String),
+    Apply,
+}
+
+#[derive(Debug)]
+pub enum LinkPopoverOutput {
+    Apply(String, String), // Text, URL
+}
+
+/// Asks for the target and text of a link being inserted or edited.
+#[derive(Debug)]
+pub struct LinkPopover {
+    visible: bool,
+    // Set by Show so the entries pick up the new text and url
+    reset: bool,
+    editing: bool,
+    text: String,
+    url: String,
+    target: gdk::Rectangle,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for LinkPopover {
+    type Init = ();
+    type Input = LinkPopoverMsg;
+    type Output = LinkPopoverOutput;
+
+    view! {
+        gtk::Popover {
+            set_position: gtk::PositionType::Bottom,
+            #[watch]
+            set_pointing_to: Some(&model.target),
+            #[watch]
+            set_visible: model.visible,
+
+            connect_closed => LinkPopoverMsg::Hide,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 8,
+                set_margin_all: 8,
+                set_width_request: 320,
+
+                gtk::Label {
+                    set_label: "URL",
+                    set_halign: gtk::Align::Start,
+                    set_css_classes: &["heading"],
+                },
+                gtk::Entry {
+                    set_placeholder_text: Some("https://"),
+                    #[track(model.reset)]
+                    set_text: &model.url,
+                    #[watch]
+                    set_css_classes: if model.show_error() { &["error"] } else { &[] },
+                    connect_changed[sender] => move |entry| {
+                        sender.input(LinkPopoverMsg::SetUrl(entry.text().to_string()));
+                    },
+                    connect_activate => LinkPopoverMsg::Apply,
+                },
+                gtk::Label {
+                    set_label: "Enter a full address, like https://example.com",
+                    #[watch]
+                    set_visible: model.show_error(),
+                    set_halign: gtk::Align::Start,
+                    set_css_classes: &["error", "caption"],
+                },
+
+                gtk::Label {
+                    set_label: "Text",
+                    set_halign: gtk::Align::Start,
+                    set_css_classes: &["heading"],
+                },
+                gtk::Entry {
+                    set_placeholder_text: Some("Same as the URL"),
+                    #[track(model.reset)]
+                    set_text: &model.text,
+                    connect_changed[sender] => move |entry| {
+                        sender.input(LinkPopoverMsg::SetText(entry.text().to_string()));
+                    },
+                    connect_activate => LinkPopoverMsg::Apply,
+                },
+
+                gtk::Button {
+                    #[watch]
+                    set_label: if model.editing { "Update Link" } else { "Insert Link" },
+                    set_halign: gtk::Align::End,
+                    set_css_classes: &["suggested-action"],
+                    #[watch]
+                    set_sensitive: links::normalize_url(&model.url).is_some(),
+                    connect_clicked => LinkPopoverMsg::Apply,
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = LinkPopover {
+            visible: false,
+            reset: false,
+            editing: false,
+            text: String::new(),
+            url: String::new(),
+            target: gdk::Rectangle::new(0, 0, 1, 1),
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        self.reset = false;
+        match msg {
+            LinkPopoverMsg::Show { text, url, target } => {
+                self.editing = !url.is_empty();
+                self.text = text;
+                self.url = url;
+                self.target = target;
+                self.reset = true;
+                self.visible = true;
+            }
+            LinkPopoverMsg::Hide => {
+                self.visible = false;
+            }
+            LinkPopoverMsg::SetText(text) => {
+                self.text = text;
+            }
+            LinkPopoverMsg::SetUrl(url) => {
+                self.url = url;
+            }
+            LinkPopoverMsg::Apply => {
+                if let Some(url) = links::normalize_url(&self.url) {
+                    self.visible = false;
+                    let _ = sender.output(LinkPopoverOutput::Apply(self.text.clone(), url));
+                }
+            }
+        }
+    }
+}
+
+impl LinkPopover {
+    /// A url is only flagged once something has been typed.
+    fn show_error(&self) -> bool {
+        !self.url.trim().is_empty() && links::normalize_url(&self.url).is_none()
+    }
+}
@@ -1,4 +1,11 @@
+pub mod backlinks;
 pub mod editor;
+pub mod find_bar;
+pub mod folder_dialogs;
+pub mod history;
+pub mod link_popover;
 pub mod navigation;
+pub mod note_completion;
 pub mod sidebar;
+pub mod tasks;
 pub mod toolbar;
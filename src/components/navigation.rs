@@ -1,14 +1,60 @@
+use crate::components::folder_dialogs::{
+    DeleteFolderDialog, DeleteFolderMsg, DeleteFolderOutput, NewFolderDialog, NewFolderMsg,
+    NewFolderOutput,
+};
 use crate::core::note_service::NoteService;
+use crate::models::folder::{self, Folder, NoteDisposal};
+use crate::models::tag::{TagCount, TagMatch};
 use gtk::gdk;
 use gtk::glib;
 use gtk::prelude::*;
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
+use std::collections::HashSet;
 use std::rc::Rc;
+use uuid::Uuid;
+
+// Drag payloads are strings: a bare note id, or this prefix plus a folder id.
+const FOLDER_DRAG_PREFIX: &str = "folder:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    AllNotes,
+    /// Open tasks across notes instead of a note list
+    Tasks,
+    Folder(Uuid),
+    Trash,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DropItem {
+    Note(Uuid),
+    Folder(Uuid),
+}
+
+fn parse_drop(payload: &str) -> Option<DropItem> {
+    match payload.strip_prefix(FOLDER_DRAG_PREFIX) {
+        Some(id) => Uuid::parse_str(id).ok().map(DropItem::Folder),
+        None => Uuid::parse_str(payload).ok().map(DropItem::Note),
+    }
+}
+
+/// One visible line of the folder tree.
+#[derive(Debug)]
+pub struct FolderNode {
+    pub folder: Folder,
+    pub depth: usize,
+    pub has_children: bool,
+    pub is_expanded: bool,
+    pub is_selected: bool,
+}
 
 #[derive(Debug)]
 pub struct FolderRow {
-    pub name: String,
+    pub folder: Folder,
+    pub depth: usize,
+    pub has_children: bool,
+    pub is_expanded: bool,
     pub is_selected: bool,
     pub is_renaming: bool,
 }
@@ -16,16 +62,54 @@ pub struct FolderRow {
 #[derive(Debug, Clone)]
 pub enum FolderRowMsg {
     Select,
-    DropNote(String), // Note ID
+    ToggleExpanded,
+    Drop(String), // Drag payload
     StartRename,
     CommitRename(String),
     CancelRename,
-    UpdateSelection(String), // Currently selected folder name
+    NewSubfolder,
+    Delete,
+    UpdateSelection(Option<Uuid>), // Currently selected folder
+}
+
+/// Right-click menu of a folder row, shown at `x`, `y` within `widget`.
+fn show_folder_menu(widget: &gtk::Widget, x: f64, y: f64, sender: &FactorySender<FolderRow>) {
+    let popover = gtk::Popover::new();
+    popover.set_has_arrow(false);
+    popover.set_halign(gtk::Align::Start);
+    popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+
+    let items = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    for (label, msg) in [
+        ("New Subfolder…", FolderRowMsg::NewSubfolder),
+        ("Rename", FolderRowMsg::StartRename),
+        ("Delete…", FolderRowMsg::Delete),
+    ] {
+        let button = gtk::Button::new();
+        button.set_css_classes(&["flat"]);
+        let label = gtk::Label::new(Some(label));
+        label.set_halign(gtk::Align::Start);
+        button.set_child(Some(&label));
+        button.connect_clicked(glib::clone!(@strong sender, @weak popover => move |_| {
+            popover.popdown();
+            sender.input(msg.clone());
+        }));
+        items.append(&button);
+    }
+    popover.set_child(Some(&items));
+
+    popover.set_parent(widget);
+    popover.connect_closed(|popover| {
+        // Unparent once the click that closed it has been handled
+        let popover = popover.clone();
+        glib::idle_add_local_once(move || popover.unparent());
+    });
+    popover.popup();
 }
 
 #[relm4::factory(pub)]
 impl FactoryComponent for FolderRow {
-    type Init = String;
+    type Init = FolderNode;
     type Input = FolderRowMsg;
     type Output = FolderRowOutput;
     type CommandOutput = ();
@@ -53,12 +137,22 @@ impl FactoryComponent for FolderRow {
                     }
                 },
 
+                add_controller = gtk::GestureClick {
+                    set_button: gtk::gdk::BUTTON_SECONDARY,
+                    connect_pressed[sender] => move |gesture, _, x, y| {
+                        gesture.set_state(gtk::EventSequenceState::Claimed);
+                        if let Some(widget) = gesture.widget() {
+                            show_folder_menu(&widget, x, y, &sender);
+                        }
+                    }
+                },
+
                 add_controller = gtk::DropTarget {
                     set_actions: gdk::DragAction::MOVE,
                     set_types: &[glib::Type::STRING],
                     connect_drop[sender] => move |_, value, _, _| {
-                        if let Ok(note_id) = value.get::<String>() {
-                            sender.input(FolderRowMsg::DropNote(note_id));
+                        if let Ok(payload) = value.get::<String>() {
+                            sender.input(FolderRowMsg::Drop(payload));
                             true
                         } else {
                             false
@@ -66,18 +160,36 @@ impl FactoryComponent for FolderRow {
                     },
                 },
 
+                add_controller = gtk::DragSource {
+                    set_actions: gdk::DragAction::MOVE,
+                    connect_prepare[folder_id = self.folder.id] => move |_, _, _| {
+                        let payload = format!("{}{}", FOLDER_DRAG_PREFIX, folder_id);
+                        Some(gdk::ContentProvider::for_value(&payload.to_value()))
+                    },
+                },
+
                 gtk::Box {
                     set_orientation: gtk::Orientation::Horizontal,
-                    set_spacing: 8,
-
+                    set_spacing: 4,
+                    set_margin_start: self.depth as i32 * 16,
+
+                    gtk::Button {
+                        set_icon_name: if self.is_expanded { "pan-down-symbolic" } else { "pan-end-symbolic" },
+                        set_css_classes: &["flat", "folder-expander"],
+                        // Keep the space so sibling icons line up
+                        set_opacity: if self.has_children { 1.0 } else { 0.0 },
+                        set_sensitive: self.has_children,
+                        connect_clicked => FolderRowMsg::ToggleExpanded,
+                    },
                     gtk::Image {
                         set_icon_name: Some("folder-symbolic"),
                     },
                     gtk::Label {
                         #[watch]
-                        set_label: &self.name,
+                        set_label: &self.folder.name,
                         set_hexpand: true,
                         set_halign: gtk::Align::Start,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
                         set_css_classes: &["nav-item-label"],
                     },
                 }
@@ -86,7 +198,7 @@ impl FactoryComponent for FolderRow {
             },
 
             add_child = &gtk::Entry {
-                set_text: &self.name,
+                set_text: &self.folder.name,
                 set_halign: gtk::Align::Fill,
                 set_hexpand: true,
                 connect_map => move |entry| {
@@ -122,10 +234,13 @@ impl FactoryComponent for FolderRow {
         }
     }
 
-    fn init_model(name: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+    fn init_model(node: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
         Self {
-            name,
-            is_selected: false,
+            folder: node.folder,
+            depth: node.depth,
+            has_children: node.has_children,
+            is_expanded: node.is_expanded,
+            is_selected: node.is_selected,
             is_renaming: false,
         }
     }
@@ -133,11 +248,21 @@ impl FactoryComponent for FolderRow {
     fn update(&mut self, msg: Self::Input, sender: FactorySender<Self>) {
         match msg {
             FolderRowMsg::Select => {
-                let _ = sender.output(FolderRowOutput::Selected(self.name.clone()));
+                let _ = sender.output(FolderRowOutput::Selected(self.folder.id));
             }
-            FolderRowMsg::DropNote(note_id) => {
-                let _ = sender.output(FolderRowOutput::NoteDropped(self.name.clone(), note_id));
+            FolderRowMsg::ToggleExpanded => {
+                let _ = sender.output(FolderRowOutput::ToggleExpanded(self.folder.id));
             }
+            FolderRowMsg::Drop(payload) => match parse_drop(&payload) {
+                Some(DropItem::Note(note_id)) => {
+                    let _ = sender.output(FolderRowOutput::NoteDropped(self.folder.id, note_id));
+                }
+                Some(DropItem::Folder(folder_id)) => {
+                    let _ =
+                        sender.output(FolderRowOutput::FolderDropped(self.folder.id, folder_id));
+                }
+                None => {}
+            },
             FolderRowMsg::StartRename => {
                 self.is_renaming = true;
             }
@@ -145,29 +270,29 @@ impl FactoryComponent for FolderRow {
                 if !self.is_renaming {
                     return;
                 }
-                if !new_name.trim().is_empty() && new_name != self.name {
-                    let old_name = self.name.clone();
-                    self.name = new_name.clone();
-                    self.is_renaming = false;
-
-                    let _ = sender.output(FolderRowOutput::Renamed(old_name, new_name));
-                } else {
-                    self.is_renaming = false;
+                let new_name = new_name.trim().to_string();
+                self.is_renaming = false;
+                if !new_name.is_empty() && new_name != self.folder.name {
+                    self.folder.name = new_name.clone();
+                    let _ = sender.output(FolderRowOutput::Renamed(self.folder.id, new_name));
                 }
             }
             FolderRowMsg::CancelRename => {
                 self.is_renaming = false;
             }
-            FolderRowMsg::UpdateSelection(selected_name) => {
-                let was_selected = self.is_selected;
-                self.is_selected = self.name == selected_name;
+            FolderRowMsg::NewSubfolder => {
+                let _ = sender.output(FolderRowOutput::NewSubfolder(self.folder.id));
+            }
+            FolderRowMsg::Delete => {
+                let _ = sender.output(FolderRowOutput::Delete(self.folder.id));
+            }
+            FolderRowMsg::UpdateSelection(selected_id) => {
+                self.is_selected = selected_id == Some(self.folder.id);
 
                 // If we are not the selected folder, ensure we are not renaming
                 if !self.is_selected {
                     self.is_renaming = false;
                 }
-
-                if was_selected != self.is_selected {}
             }
         }
     }
@@ -175,21 +300,114 @@ impl FactoryComponent for FolderRow {
 
 #[derive(Debug)]
 pub enum FolderRowOutput {
-    Selected(String),
-    NoteDropped(String, String), // Folder Name, Note ID
-    Renamed(String, String),     // Old Name, New Name
+    Selected(Uuid),
+    ToggleExpanded(Uuid),
+    NoteDropped(Uuid, Uuid),   // Folder ID, Note ID
+    FolderDropped(Uuid, Uuid), // Target Folder ID, Dropped Folder ID
+    Renamed(Uuid, String),     // Folder ID, New Name
+    NewSubfolder(Uuid),        // Parent Folder ID
+    Delete(Uuid),
+}
+
+#[derive(Debug)]
+pub struct TagRow {
+    pub tag: TagCount,
+    pub is_selected: bool,
+}
+
+#[derive(Debug)]
+pub enum TagRowMsg {
+    Toggle,
+    Deselect,
+}
+
+#[derive(Debug)]
+pub enum TagRowOutput {
+    Toggled(String, bool), // Tag name, now selected
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for TagRow {
+    type Init = (TagCount, bool);
+    type Input = TagRowMsg;
+    type Output = TagRowOutput;
+    type CommandOutput = ();
+    type ParentWidget = gtk::Box;
+
+    view! {
+        gtk::Button {
+            #[watch]
+            set_css_classes: if self.is_selected { &["nav-item", "selected"] } else { &["nav-item"] },
+            set_halign: gtk::Align::Fill,
+            connect_clicked => TagRowMsg::Toggle,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 8,
+
+                gtk::Label {
+                    set_label: &format!("#{}", self.tag.name),
+                    set_hexpand: true,
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    set_css_classes: &["nav-item-label"],
+                },
+                gtk::Label {
+                    set_label: &self.tag.count.to_string(),
+                    set_css_classes: &["nav-count"],
+                },
+            }
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        let (tag, is_selected) = init;
+        Self { tag, is_selected }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: FactorySender<Self>) {
+        match msg {
+            TagRowMsg::Toggle => {
+                self.is_selected = !self.is_selected;
+                let _ = sender.output(TagRowOutput::Toggled(
+                    self.tag.name.clone(),
+                    self.is_selected,
+                ));
+            }
+            TagRowMsg::Deselect => {
+                self.is_selected = false;
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum NavigationMsg {
-    SelectCategory(String),
+    SelectCategory(Category),
     AddFolder,
     FolderOutput(FolderRowOutput),
+    DropOnAllNotes(String), // Drag payload
+    NewFolderOutput(NewFolderOutput),
+    DeleteFolderOutput(DeleteFolderOutput),
+    UpdateTags(Vec<TagCount>),
+    TagOutput(TagRowOutput),
+    SetTagMatch(TagMatch),
+    ClearTags,
 }
 
 pub struct Navigation {
-    selected_category: String,
-    folders: FactoryVecDeque<FolderRow>,
+    note_service: Rc<NoteService>,
+    selected_category: Category,
+    // All folders sorted by name; the tree is derived from parent ids
+    folders: Vec<Folder>,
+    expanded: HashSet<Uuid>,
+    folder_rows: FactoryVecDeque<FolderRow>,
+    new_folder_dialog: Controller<NewFolderDialog>,
+    delete_folder_dialog: Controller<DeleteFolderDialog>,
+    tags: FactoryVecDeque<TagRow>,
+    // Tag filter, applied on top of the selected category
+    selected_tags: Vec<String>,
+    tag_match: TagMatch,
 }
 
 #[relm4::component(pub)]
@@ -236,10 +454,25 @@ impl SimpleComponent for Navigation {
                     #[watch]
                     set_css_classes: {
 
-                        if model.selected_category == "All Notes" { &["nav-item", "selected"] } else { &["nav-item"] }
+                        if model.selected_category == Category::AllNotes { &["nav-item", "selected"] } else { &["nav-item"] }
                     },
                     set_halign: gtk::Align::Fill,
-                    connect_clicked => NavigationMsg::SelectCategory("All Notes".to_string()),
+                    connect_clicked => NavigationMsg::SelectCategory(Category::AllNotes),
+
+                    // Dropping here takes a note out of its folder, or moves a
+                    // folder back to the top level
+                    add_controller = gtk::DropTarget {
+                        set_actions: gdk::DragAction::MOVE,
+                        set_types: &[glib::Type::STRING],
+                        connect_drop[sender] => move |_, value, _, _| {
+                            if let Ok(payload) = value.get::<String>() {
+                                sender.input(NavigationMsg::DropOnAllNotes(payload));
+                                true
+                            } else {
+                                false
+                            }
+                        },
+                    },
 
                     gtk::Box {
                         set_orientation: gtk::Orientation::Horizontal,
@@ -256,6 +489,30 @@ impl SimpleComponent for Navigation {
                         }
                     },
                 },
+
+                gtk::Button {
+                    #[watch]
+                    set_css_classes: {
+                        if model.selected_category == Category::Tasks { &["nav-item", "selected"] } else { &["nav-item"] }
+                    },
+                    set_halign: gtk::Align::Fill,
+                    connect_clicked => NavigationMsg::SelectCategory(Category::Tasks),
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 8,
+
+                        gtk::Image {
+                            set_icon_name: Some("object-select-symbolic"),
+                        },
+                        gtk::Label {
+                            set_label: "Tasks",
+                            set_hexpand: true,
+                            set_halign: gtk::Align::Start,
+                            set_css_classes: &["nav-item-label"],
+                        }
+                    },
+                },
             },
 
             // Folders (Dynamic)
@@ -268,6 +525,97 @@ impl SimpleComponent for Navigation {
                 set_margin_start: 10,
                 set_margin_end: 10,
             },
+
+            // Tags
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 10,
+                set_margin_all: 10,
+                set_margin_top: 16,
+                #[watch]
+                set_visible: !model.tags.is_empty(),
+
+                gtk::Label {
+                    set_text: "TAGS",
+                    set_css_classes: &["nav-header"],
+                    set_halign: gtk::Align::Start,
+                    set_hexpand: true,
+                },
+
+                gtk::ToggleButton {
+                    set_label: "All",
+                    set_tooltip_text: Some("Only show notes with every selected tag"),
+                    set_css_classes: &["flat", "nav-icon"],
+                    #[watch]
+                    set_active: model.tag_match == TagMatch::All,
+                    connect_toggled[sender] => move |button| {
+                        let mode = if button.is_active() { TagMatch::All } else { TagMatch::Any };
+                        sender.input(NavigationMsg::SetTagMatch(mode));
+                    },
+                },
+
+                gtk::Button {
+                    set_icon_name: "edit-clear-symbolic",
+                    set_tooltip_text: Some("Clear Tag Filter"),
+                    set_css_classes: &["flat", "nav-icon"],
+                    #[watch]
+                    set_visible: !model.selected_tags.is_empty(),
+                    connect_clicked => NavigationMsg::ClearTags,
+                },
+            },
+
+            gtk::ScrolledWindow {
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+                set_propagate_natural_height: true,
+                set_max_content_height: 240,
+                #[watch]
+                set_visible: !model.tags.is_empty(),
+
+                #[local_ref]
+                tag_list -> gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 2,
+                    set_margin_start: 10,
+                    set_margin_end: 10,
+                },
+            },
+
+            // Spacer pushes system categories to the bottom
+            gtk::Box {
+                set_vexpand: true,
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 2,
+                set_margin_start: 10,
+                set_margin_end: 10,
+                set_margin_bottom: 10,
+
+                gtk::Button {
+                    #[watch]
+                    set_css_classes: {
+                        if model.selected_category == Category::Trash { &["nav-item", "selected"] } else { &["nav-item"] }
+                    },
+                    set_halign: gtk::Align::Fill,
+                    connect_clicked => NavigationMsg::SelectCategory(Category::Trash),
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 8,
+
+                        gtk::Image {
+                            set_icon_name: Some("user-trash-symbolic"),
+                        },
+                        gtk::Label {
+                            set_label: "Trash",
+                            set_hexpand: true,
+                            set_halign: gtk::Align::Start,
+                            set_css_classes: &["nav-item-label"],
+                        }
+                    },
+                },
+            },
         }
     }
 
@@ -276,23 +624,45 @@ impl SimpleComponent for Navigation {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let mut folders = FactoryVecDeque::builder()
+        let folder_rows = FactoryVecDeque::builder()
             .launch(gtk::Box::default())
             .forward(sender.input_sender(), NavigationMsg::FolderOutput);
 
-        // Load folders from DB
-        if let Ok(db_folders) = note_service.get_folders() {
-            for folder in db_folders {
-                folders.guard().push_back(folder);
+        let mut tags = FactoryVecDeque::builder()
+            .launch(gtk::Box::default())
+            .forward(sender.input_sender(), NavigationMsg::TagOutput);
+
+        match note_service.get_tag_counts() {
+            Ok(counts) => {
+                for tag in counts {
+                    tags.guard().push_back((tag, false));
+                }
             }
+            Err(e) => eprintln!("Failed to load tags: {}", e),
         }
 
-        let model = Navigation {
-            selected_category: "All Notes".to_string(),
-            folders,
+        let mut model = Navigation {
+            note_service,
+            selected_category: Category::AllNotes,
+            folders: Vec::new(),
+            expanded: HashSet::new(),
+            folder_rows,
+            new_folder_dialog: NewFolderDialog::builder()
+                .transient_for(&root)
+                .launch(())
+                .forward(sender.input_sender(), NavigationMsg::NewFolderOutput),
+            delete_folder_dialog: DeleteFolderDialog::builder()
+                .transient_for(&root)
+                .launch(())
+                .forward(sender.input_sender(), NavigationMsg::DeleteFolderOutput),
+            tags,
+            selected_tags: Vec::new(),
+            tag_match: TagMatch::Any,
         };
+        model.reload_folders();
 
-        let folder_list = model.folders.widget();
+        let folder_list = model.folder_rows.widget();
+        let tag_list = model.tags.widget();
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -300,46 +670,301 @@ impl SimpleComponent for Navigation {
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
-            NavigationMsg::SelectCategory(cat) => {
-                self.selected_category = cat.clone();
-                self.folders
-                    .broadcast(FolderRowMsg::UpdateSelection(cat.clone()));
-                let _ = sender.output(NavigationOutput::FolderSelected(cat));
+            NavigationMsg::SelectCategory(category) => {
+                self.select_category(category, &sender);
+            }
+            NavigationMsg::FolderOutput(output) => match output {
+                FolderRowOutput::Selected(id) => {
+                    self.select_category(Category::Folder(id), &sender);
+                }
+                FolderRowOutput::ToggleExpanded(id) => {
+                    if !self.expanded.remove(&id) {
+                        self.expanded.insert(id);
+                    }
+                    self.rebuild_tree();
+                }
+                FolderRowOutput::NoteDropped(folder_id, note_id) => {
+                    let _ = sender.output(NavigationOutput::MoveNote(note_id, Some(folder_id)));
+                }
+                FolderRowOutput::FolderDropped(target_id, folder_id) => {
+                    if target_id != folder_id {
+                        self.move_folder(folder_id, Some(target_id), &sender);
+                    }
+                }
+                FolderRowOutput::Renamed(id, name) => {
+                    let parent_id = self.folder(id).and_then(|f| f.parent_id);
+                    match folder::validate_name(&name, parent_id, Some(id), &self.folders) {
+                        Ok(name) => {
+                            if let Err(e) = self.note_service.rename_folder(id, &name) {
+                                self.show_error("Couldn’t rename folder", &e.to_string());
+                            }
+                        }
+                        Err(e) => self.show_error("Couldn’t rename folder", &e.to_string()),
+                    }
+                    // Re-sort, or put the old name back if the rename failed
+                    self.folders_changed(&sender);
+                }
+                FolderRowOutput::NewSubfolder(parent_id) => {
+                    self.show_new_folder_dialog(Some(parent_id));
+                }
+                FolderRowOutput::Delete(id) => self.show_delete_folder_dialog(id),
+            },
+            NavigationMsg::DropOnAllNotes(payload) => match parse_drop(&payload) {
+                Some(DropItem::Note(note_id)) => {
+                    let _ = sender.output(NavigationOutput::MoveNote(note_id, None));
+                }
+                Some(DropItem::Folder(folder_id)) => self.move_folder(folder_id, None, &sender),
+                None => {}
+            },
+            NavigationMsg::UpdateTags(counts) => {
+                // Called on every edit; only rebuild when something changed
+                if self.tags.iter().map(|row| &row.tag).eq(counts.iter()) {
+                    return;
+                }
+
+                let before = self.selected_tags.len();
+                self.selected_tags
+                    .retain(|name| counts.iter().any(|tag| &tag.name == name));
+
+                {
+                    let mut guard = self.tags.guard();
+                    guard.clear();
+                    for tag in counts {
+                        let selected = self.selected_tags.contains(&tag.name);
+                        guard.push_back((tag, selected));
+                    }
+                }
+
+                // A selected tag disappeared; stop filtering by it
+                if self.selected_tags.len() != before {
+                    self.emit_tag_filter(&sender);
+                }
+            }
+            NavigationMsg::TagOutput(TagRowOutput::Toggled(name, selected)) => {
+                if selected {
+                    self.selected_tags.push(name);
+                } else {
+                    self.selected_tags.retain(|n| n != &name);
+                }
+                self.emit_tag_filter(&sender);
             }
-            NavigationMsg::FolderOutput(output) => {
-                match output {
-                    FolderRowOutput::Selected(name) => {
-                        self.selected_category = name.clone();
-                        self.folders
-                            .broadcast(FolderRowMsg::UpdateSelection(name.clone()));
-                        let _ = sender.output(NavigationOutput::FolderSelected(name));
+            NavigationMsg::SetTagMatch(mode) => {
+                if self.tag_match != mode {
+                    self.tag_match = mode;
+                    if !self.selected_tags.is_empty() {
+                        self.emit_tag_filter(&sender);
                     }
-                    FolderRowOutput::NoteDropped(folder, note_id) => {
-                        let _ = sender.output(NavigationOutput::MoveNote(note_id, folder));
+                }
+            }
+            NavigationMsg::ClearTags => {
+                self.selected_tags.clear();
+                self.tags.broadcast(TagRowMsg::Deselect);
+                self.emit_tag_filter(&sender);
+            }
+            NavigationMsg::AddFolder => self.show_new_folder_dialog(None),
+            NavigationMsg::NewFolderOutput(NewFolderOutput::Create(name, parent_id)) => {
+                match self.note_service.add_folder(&name, parent_id) {
+                    Ok(folder) => {
+                        if let Some(parent_id) = parent_id {
+                            self.expanded.insert(parent_id);
+                        }
+                        self.folders_changed(&sender);
+                        self.select_category(Category::Folder(folder.id), &sender);
                     }
-                    FolderRowOutput::Renamed(old_name, new_name) => {
-                        // Update selected category if it was the renamed folder
-                        if self.selected_category == old_name {
-                            self.selected_category = new_name.clone();
+                    Err(e) => self.show_error("Couldn’t create folder", &e.to_string()),
+                }
+            }
+            NavigationMsg::DeleteFolderOutput(DeleteFolderOutput::Delete(id, notes)) => {
+                let subtree = self.note_service.folder_subtree(id).unwrap_or_default();
+                match self.note_service.delete_folder(id, notes) {
+                    Ok(_) => {
+                        for id in &subtree {
+                            self.expanded.remove(id);
+                        }
+                        if matches!(self.selected_category, Category::Folder(selected) if subtree.contains(&selected))
+                        {
+                            self.select_category(Category::AllNotes, &sender);
                         }
-                        let _ = sender.output(NavigationOutput::RenameFolder(old_name, new_name));
+                        self.folders_changed(&sender);
+                        let _ = sender.output(NavigationOutput::FolderDeleted(id));
                     }
+                    Err(e) => self.show_error("Couldn’t delete folder", &e.to_string()),
                 }
             }
-            NavigationMsg::AddFolder => {
-                // In a real app, this would show a dialog
-                let new_name = format!("New Folder {}", self.folders.len() + 1);
-                self.folders.guard().push_back(new_name.clone());
-                let _ = sender.output(NavigationOutput::AddFolder(new_name));
+        }
+    }
+}
+
+impl Navigation {
+    fn select_category(&mut self, category: Category, sender: &ComponentSender<Self>) {
+        self.selected_category = category;
+        let folder_id = match category {
+            Category::Folder(id) => Some(id),
+            _ => None,
+        };
+        self.folder_rows
+            .broadcast(FolderRowMsg::UpdateSelection(folder_id));
+        let _ = sender.output(NavigationOutput::CategorySelected(category));
+    }
+
+    fn reload_folders(&mut self) {
+        match self.note_service.get_folders() {
+            Ok(folders) => self.folders = folders,
+            Err(e) => eprintln!("Failed to load folders: {}", e),
+        }
+        self.rebuild_tree();
+    }
+
+    /// Reload after a folder was added, renamed or moved, and let the rest of
+    /// the app know.
+    fn folders_changed(&mut self, sender: &ComponentSender<Self>) {
+        self.reload_folders();
+        let _ = sender.output(NavigationOutput::FoldersChanged(self.folders.clone()));
+    }
+
+    fn move_folder(&mut self, id: Uuid, parent_id: Option<Uuid>, sender: &ComponentSender<Self>) {
+        let Some(name) = self.folder(id).map(|f| f.name.clone()) else {
+            return;
+        };
+        if let Err(e) = folder::validate_name(&name, parent_id, Some(id), &self.folders) {
+            self.show_error("Couldn’t move folder", &e.to_string());
+            return;
+        }
+        match self.note_service.move_folder(id, parent_id) {
+            Ok(()) => {
+                // Show the folder where it landed
+                if let Some(parent_id) = parent_id {
+                    self.expanded.insert(parent_id);
+                }
+                self.folders_changed(sender);
+            }
+            Err(e) => self.show_error("Couldn’t move folder", &e.to_string()),
+        }
+    }
+
+    fn folder(&self, id: Uuid) -> Option<&Folder> {
+        self.folders.iter().find(|f| f.id == id)
+    }
+
+    /// Every folder in tree order with its full path, e.g. "Work / Meetings".
+    fn folder_paths(&self) -> Vec<(Uuid, String)> {
+        folder::paths(&self.folders)
+    }
+
+    fn show_new_folder_dialog(&self, parent_id: Option<Uuid>) {
+        let mut locations = vec![(None, "Top Level".to_string())];
+        locations.extend(
+            self.folder_paths()
+                .into_iter()
+                .map(|(id, path)| (Some(id), path)),
+        );
+        self.new_folder_dialog.emit(NewFolderMsg::Show {
+            folders: self.folders.clone(),
+            locations,
+            parent_id,
+        });
+    }
+
+    fn show_delete_folder_dialog(&self, id: Uuid) {
+        let Some(folder) = self.folder(id).cloned() else {
+            return;
+        };
+        let subtree = self
+            .note_service
+            .folder_subtree(id)
+            .unwrap_or_else(|_| vec![id]);
+        let note_count = self
+            .note_service
+            .count_notes_in_folder(id)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to count notes: {}", e);
+                0
+            });
+
+        let mut targets = vec![
+            (NoteDisposal::Trash, "Move to Trash".to_string()),
+            (NoteDisposal::MoveTo(None), "No Folder".to_string()),
+        ];
+        targets.extend(
+            self.folder_paths()
+                .into_iter()
+                .filter(|(id, _)| !subtree.contains(id))
+                .map(|(id, path)| (NoteDisposal::MoveTo(Some(id)), path)),
+        );
+        self.delete_folder_dialog.emit(DeleteFolderMsg::Show {
+            folder,
+            note_count,
+            targets,
+        });
+    }
+
+    fn show_error(&self, message: &str, detail: &str) {
+        let window = self
+            .folder_rows
+            .widget()
+            .root()
+            .and_then(|root| root.downcast::<gtk::Window>().ok());
+        gtk::AlertDialog::builder()
+            .modal(true)
+            .message(message)
+            .detail(detail)
+            .build()
+            .show(window.as_ref());
+    }
+
+    /// Flatten the expanded part of the folder tree into rows.
+    fn rebuild_tree(&mut self) {
+        let mut nodes = Vec::new();
+        self.collect_nodes(None, 0, &mut nodes);
+
+        let mut guard = self.folder_rows.guard();
+        guard.clear();
+        for node in nodes {
+            guard.push_back(node);
+        }
+    }
+
+    fn collect_nodes(&self, parent_id: Option<Uuid>, depth: usize, nodes: &mut Vec<FolderNode>) {
+        for folder in self
+            .folders
+            .iter()
+            .filter(|f| self.parent_of(f) == parent_id)
+        {
+            let has_children = self.folders.iter().any(|f| f.parent_id == Some(folder.id));
+            let is_expanded = self.expanded.contains(&folder.id);
+            nodes.push(FolderNode {
+                folder: folder.clone(),
+                depth,
+                has_children,
+                is_expanded,
+                is_selected: self.selected_category == Category::Folder(folder.id),
+            });
+            if has_children && is_expanded {
+                self.collect_nodes(Some(folder.id), depth + 1, nodes);
             }
         }
     }
+
+    /// Parent of `folder` in the tree; a dangling parent id counts as top level.
+    fn parent_of(&self, folder: &Folder) -> Option<Uuid> {
+        folder
+            .parent_id
+            .filter(|id| self.folders.iter().any(|f| f.id == *id))
+    }
+
+    fn emit_tag_filter(&self, sender: &ComponentSender<Self>) {
+        let _ = sender.output(NavigationOutput::TagFilterChanged(
+            self.selected_tags.clone(),
+            self.tag_match,
+        ));
+    }
 }
 
 #[derive(Debug)]
 pub enum NavigationOutput {
-    FolderSelected(String),
-    MoveNote(String, String),     // Note ID, Target Folder
-    RenameFolder(String, String), // Old Name, New Name
-    AddFolder(String),            // New Folder Name
+    CategorySelected(Category),
+    MoveNote(Uuid, Option<Uuid>), // Note ID, Target Folder ID
+    FoldersChanged(Vec<Folder>),
+    FolderDeleted(Uuid), // Its notes were moved or trashed
+    TagFilterChanged(Vec<String>, TagMatch),
 }
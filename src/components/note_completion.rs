@@ -0,0 +1,96 @@
+use gtk::gdk;
+use gtk::prelude::*;
+
+/// The list of note titles offered while a `[[wiki link]]` is typed. It
+/// never takes the focus; the editor's key handler drives it instead, so
+/// typing carries on in the text.
+#[derive(Debug, Clone)]
+pub struct NoteCompletion {
+    popover: gtk::Popover,
+    list: gtk::ListBox,
+}
+
+impl NoteCompletion {
+    pub fn new() -> Self {
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Browse);
+        list.set_can_focus(false);
+
+        let popover = gtk::Popover::new();
+        popover.set_autohide(false);
+        popover.set_has_arrow(false);
+        popover.set_position(gtk::PositionType::Bottom);
+        popover.set_can_focus(false);
+        popover.set_child(Some(&list));
+
+        Self { popover, list }
+    }
+
+    pub fn set_parent(&self, parent: &impl IsA<gtk::Widget>) {
+        self.popover.set_parent(parent);
+    }
+
+    /// Called with the title when a suggestion is clicked.
+    pub fn connect_picked(&self, f: impl Fn(String) + 'static) {
+        self.list.connect_row_activated(move |_, row| {
+            if let Some(title) = row_title(row) {
+                f(title);
+            }
+        });
+    }
+
+    /// Offers `titles` below `target`, in the parent's coordinates, with the
+    /// first one selected.
+    pub fn show(&self, titles: &[String], target: gdk::Rectangle) {
+        while let Some(row) = self.list.first_child() {
+            self.list.remove(&row);
+        }
+        for title in titles {
+            let label = gtk::Label::new(Some(title.as_str()));
+            label.set_halign(gtk::Align::Start);
+            label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            label.set_max_width_chars(40);
+            self.list.append(&label);
+        }
+        self.list.select_row(self.list.row_at_index(0).as_ref());
+        self.popover.set_pointing_to(Some(&target));
+        self.popover.popup();
+    }
+
+    pub fn hide(&self) {
+        if self.popover.is_visible() {
+            self.popover.popdown();
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.popover.is_visible()
+    }
+
+    /// Moves the selection `step` rows, wrapping around.
+    pub fn move_selection(&self, step: i32) {
+        let count = self.list.observe_children().n_items() as i32;
+        if count == 0 {
+            return;
+        }
+        let current = self.list.selected_row().map_or(0, |row| row.index());
+        let next = (current + step).rem_euclid(count);
+        self.list.select_row(self.list.row_at_index(next).as_ref());
+    }
+
+    pub fn selected(&self) -> Option<String> {
+        self.list.selected_row().as_ref().and_then(row_title)
+    }
+}
+
+impl Default for NoteCompletion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn row_title(row: &gtk::ListBoxRow) -> Option<String> {
+    row.child()
+        .and_downcast::<gtk::Label>()
+        .map(|label| label.text().to_string())
+}
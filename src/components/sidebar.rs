@@ -1,14 +1,22 @@
+use crate::core::note_service::NoteService;
+use crate::models::folder::Folder;
 use crate::models::note::Note;
+use crate::models::search::SearchHit;
 use gtk::gdk;
+use gtk::glib;
 use gtk::pango;
 use gtk::prelude::*;
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+use uuid::Uuid;
 
 #[derive(Debug)]
 pub struct SidebarRow {
     pub note: Note,
-    pub index: usize,
+    pub preview: String, // Pango markup
+    pub folder_name: String,
 }
 
 #[derive(Debug)]
@@ -18,7 +26,7 @@ pub enum SidebarRowMsg {
 
 #[relm4::factory(pub)]
 impl FactoryComponent for SidebarRow {
-    type Init = (usize, Note);
+    type Init = (Note, Option<SearchHit>, String); // Note, search hit, folder name
     type Input = SidebarRowMsg;
     type Output = SidebarMsg;
     type CommandOutput = ();
@@ -56,6 +64,7 @@ impl FactoryComponent for SidebarRow {
 
                 // Title
                 gtk::Label {
+                    #[watch]
                     set_label: &self.note.title,
                     set_halign: gtk::Align::Start,
                     set_css_classes: &["sidebar-title"],
@@ -64,7 +73,7 @@ impl FactoryComponent for SidebarRow {
 
                 // Preview
                 gtk::Label {
-                    set_label: &self.note.content.lines().next().unwrap_or("No content"),
+                    set_markup: &self.preview,
                     set_halign: gtk::Align::Start,
                     set_ellipsize: pango::EllipsizeMode::End,
                     set_css_classes: &["sidebar-preview"],
@@ -73,7 +82,7 @@ impl FactoryComponent for SidebarRow {
 
                 // Folder Name (Instead of Date)
                 gtk::Label {
-                    set_label: &self.note.folder.clone().unwrap_or("All Notes".to_string()),
+                    set_label: &self.folder_name,
                     set_halign: gtk::Align::Start,
                     set_css_classes: &["sidebar-date"], // Reusing date style for folder
                 },
@@ -82,35 +91,68 @@ impl FactoryComponent for SidebarRow {
     }
 
     fn init_model(init: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        let (note, hit, folder_name) = init;
+        let preview = match &hit {
+            Some(hit) => snippet_markup(hit),
+            None => glib::markup_escape_text(note.content.lines().next().unwrap_or("No content"))
+                .to_string(),
+        };
         Self {
-            index: init.0,
-            note: init.1,
+            note,
+            preview,
+            folder_name,
         }
     }
 
     fn update(&mut self, _msg: Self::Input, _sender: FactorySender<Self>) {}
 }
 
+/// Render a search snippet with the matched terms in bold.
+fn snippet_markup(hit: &SearchHit) -> String {
+    let mut markup = String::new();
+    let mut last = 0;
+    for &(start, end) in &hit.matches {
+        markup.push_str(&glib::markup_escape_text(&hit.snippet[last..start]));
+        markup.push_str("<b>");
+        markup.push_str(&glib::markup_escape_text(&hit.snippet[start..end]));
+        markup.push_str("</b>");
+        last = end;
+    }
+    markup.push_str(&glib::markup_escape_text(&hit.snippet[last..]));
+    markup.replace('\n', " ")
+}
+
 #[derive(Debug)]
 pub enum SidebarMsg {
-    SelectNote(usize),
+    ActivateRow(usize),
+    SelectNote(Option<Uuid>), // Highlight a note chosen elsewhere
     UpdateNotes(Vec<Note>),
+    RetitleNote(Uuid, String), // Only the note's row changes, not the list
+    UpdateFolders(Vec<Folder>),
     Search(String),
 }
 
 #[derive(Debug)]
+pub enum SidebarOutput {
+    NoteSelected(Uuid, Option<String>), // Id, the search that found it
+}
+
 pub struct Sidebar {
     pub notes_factory: FactoryVecDeque<SidebarRow>,
-    pub selected_index: Option<usize>,
+    pub selected_id: Option<Uuid>,
     pub all_notes: Vec<Note>,
+    pub folder_names: HashMap<Uuid, String>,
     pub search_text: String,
+    // Ranked FTS results for `search_text`, None when not searching
+    pub search_hits: Option<Vec<SearchHit>>,
+    note_service: Rc<NoteService>,
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for Sidebar {
-    type Init = Vec<Note>;
+    type Init = (Vec<Note>, Rc<NoteService>);
     type Input = SidebarMsg;
-    type Output = SidebarMsg; // Forward selection to parent
+    type Output = SidebarOutput;
 
     view! {
         gtk::Box {
@@ -139,7 +181,7 @@ impl SimpleComponent for Sidebar {
                     connect_row_activated[sender] => move |_, row| {
                         let index = row.index();
                         if index >= 0 {
-                            sender.input(SidebarMsg::SelectNote(index as usize));
+                            sender.input(SidebarMsg::ActivateRow(index as usize));
                         }
                     }
                 }
@@ -148,117 +190,78 @@ impl SimpleComponent for Sidebar {
     }
 
     fn init(
-        notes: Self::Init,
+        (notes, note_service): Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let widgets = view_output!();
 
-        let mut notes_factory = FactoryVecDeque::builder()
+        let notes_factory = FactoryVecDeque::builder()
             .launch(widgets.notes_list.clone())
             .forward(sender.input_sender(), |msg| msg);
 
-        // Populate initial notes
-        for (i, note) in notes.iter().enumerate() {
-            notes_factory.guard().push_back((i, note.clone()));
-        }
+        let folders = note_service.get_folders().unwrap_or_else(|e| {
+            eprintln!("Failed to load folders: {}", e);
+            Vec::new()
+        });
 
-        let model = Sidebar {
+        let mut model = Sidebar {
             notes_factory,
-            selected_index: None,
+            selected_id: None,
             all_notes: notes,
+            folder_names: HashMap::new(),
             search_text: String::new(),
+            search_hits: None,
+            note_service,
         };
+        model.set_folders(folders);
+
+        // Populate initial notes
+        model.update_filtered_list();
 
         ComponentParts { model, widgets }
     }
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
-            SidebarMsg::SelectNote(index) => {
-                // Map the filtered index back to the original index if searching
-                // But wait, the index from ListBoxRow corresponds to the filtered list (notes_factory)
-                // We need to find the actual note in all_notes to send the correct index/ID to App?
-                // Actually, App expects an index into ITS self.notes.
-                // If we filter, the indices won't match App's indices.
-                // PROBLEM: App uses index to find note. If Sidebar filters, index 0 might be note 5.
-                // SOLUTION: We should probably pass the Note ID or the Note itself back to App,
-                // OR App needs to know about the filtered state.
-                // EASIER FIX for now: App receives SelectNote(index).
-                // If we are filtering, we need to map this index to the real index in all_notes.
-
-                // Let's find the note in the filtered list
+            SidebarMsg::ActivateRow(index) => {
+                // Rows are rebuilt on every filter change, so the position is
+                // only meaningful right now; resolve it to the note id.
                 if let Some(row) = self.notes_factory.get(index) {
-                    // We need to find this note's index in self.all_notes
-                    if let Some(real_index) =
-                        self.all_notes.iter().position(|n| n.id == row.note.id)
-                    {
-                        self.selected_index = Some(real_index);
-
-                        // Programmatically select the row
-                        if let Some(widget_row) =
-                            self.notes_factory.widget().row_at_index(index as i32)
-                        {
-                            self.notes_factory.widget().select_row(Some(&widget_row));
-                        }
-
-                        let _ = sender.output(SidebarMsg::SelectNote(real_index));
-                        return;
-                    }
+                    let id = row.note.id;
+                    self.selected_id = Some(id);
+                    let search = self.search_hits.is_some().then(|| self.search_text.clone());
+                    let _ = sender.output(SidebarOutput::NoteSelected(id, search));
                 }
-
-                // Fallback (shouldn't happen if logic is correct)
-                self.selected_index = Some(index);
-                let _ = sender.output(SidebarMsg::SelectNote(index));
+            }
+            SidebarMsg::SelectNote(id) => {
+                self.selected_id = id;
+                self.sync_selection();
             }
             SidebarMsg::UpdateNotes(notes) => {
                 self.all_notes = notes;
+                // Content may have changed, so the ranking may have too
+                self.run_search();
                 self.update_filtered_list();
-
-                // Restore selection logic is tricky with search.
-                // If selected note is still visible, select it.
-                if let Some(selected_idx) = self.selected_index {
-                    if let Some(_selected_note) = self.all_notes.get(selected_idx) {
-                        // Find this note in the filtered factory
-                        // We need to iterate the factory... accessing the inner deque is hard from here without guard
-                        // But we just rebuilt it in update_filtered_list
-                        // Let's do a simple check:
-                        // We can't easily iterate the factory items to find the index.
-                        // But we can iterate the filtered notes we just pushed.
-
-                        // Re-running filter logic to find index
-                        let filtered_notes: Vec<(usize, &Note)> = self
-                            .all_notes
-                            .iter()
-                            .enumerate()
-                            .filter(|(_, n)| {
-                                self.search_text.is_empty()
-                                    || n.title
-                                        .to_lowercase()
-                                        .contains(&self.search_text.to_lowercase())
-                                    || n.content
-                                        .to_lowercase()
-                                        .contains(&self.search_text.to_lowercase())
-                            })
-                            .collect();
-
-                        if let Some(filtered_idx) = filtered_notes
-                            .iter()
-                            .position(|(orig_idx, _)| *orig_idx == selected_idx)
-                        {
-                            if let Some(row) = self
-                                .notes_factory
-                                .widget()
-                                .row_at_index(filtered_idx as i32)
-                            {
-                                self.notes_factory.widget().select_row(Some(&row));
-                            }
-                        }
+            }
+            SidebarMsg::RetitleNote(id, title) => {
+                if let Some(note) = self.all_notes.iter_mut().find(|n| n.id == id) {
+                    note.title = title.clone();
+                }
+                let mut guard = self.notes_factory.guard();
+                if let Some(i) = guard.iter().position(|row| row.note.id == id) {
+                    if let Some(row) = guard.get_mut(i) {
+                        row.note.title = title;
                     }
                 }
             }
+            SidebarMsg::UpdateFolders(folders) => {
+                self.set_folders(folders);
+                self.update_filtered_list();
+            }
             SidebarMsg::Search(text) => {
                 self.search_text = text;
+                self.run_search();
                 self.update_filtered_list();
             }
         }
@@ -266,40 +269,71 @@ impl SimpleComponent for Sidebar {
 }
 
 impl Sidebar {
+    fn run_search(&mut self) {
+        self.search_hits = if self.search_text.trim().is_empty() {
+            None
+        } else {
+            match self.note_service.search(&self.search_text) {
+                Ok(hits) => Some(hits),
+                Err(e) => {
+                    eprintln!("Search failed: {}", e);
+                    Some(Vec::new())
+                }
+            }
+        };
+    }
+
+    /// Notes to show with their search hit. While searching this is the
+    /// matching subset in rank order.
+    fn visible_notes(&self) -> Vec<(&Note, Option<&SearchHit>)> {
+        match &self.search_hits {
+            None => self.all_notes.iter().map(|note| (note, None)).collect(),
+            Some(hits) => {
+                let notes: HashMap<Uuid, &Note> =
+                    self.all_notes.iter().map(|note| (note.id, note)).collect();
+                hits.iter()
+                    .filter_map(|hit| Some((*notes.get(&hit.note_id)?, Some(hit))))
+                    .collect()
+            }
+        }
+    }
+
+    fn set_folders(&mut self, folders: Vec<Folder>) {
+        self.folder_names = folders.into_iter().map(|f| (f.id, f.name)).collect();
+    }
+
     fn update_filtered_list(&mut self) {
-        self.notes_factory.guard().clear();
-
-        let search_lower = self.search_text.to_lowercase();
-
-        for (i, note) in self.all_notes.iter().enumerate() {
-            if self.search_text.is_empty()
-                || note.title.to_lowercase().contains(&search_lower)
-                || note.content.to_lowercase().contains(&search_lower)
-            {
-                // We pass 'i' (original index) to SidebarRow so it knows its real identity?
-                // Actually SidebarRow just displays data. The 'index' in SidebarRow init was used for selection.
-                // If we pass 'i' here, SidebarRowMsg::Select will send 'i'.
-                // Then SidebarMsg::SelectNote(i) will be called.
-                // 'i' is the REAL index in all_notes.
-                // So we don't need the complex mapping in SelectNote handler above!
-                // Wait, connect_row_activated sends row.index() which is the VISUAL index (0, 1, 2...).
-                // So we DO need mapping if we rely on row.index().
-
-                // BETTER APPROACH:
-                // Let's use the SidebarRow's internal index (which we set to 'i' - the real index).
-                // But we are using connect_row_activated on the ListBox, which gives us the visual index.
-                // We can't easily access the SidebarRow data from the ListBox row activation without casting.
-
-                // ALTERNATIVE:
-                // Go back to using connect_activate on SidebarRow? No, that was buggy.
-
-                // HYBRID:
-                // We have the filtered list.
-                // row.index() is the index in the filtered list.
-                // We can reconstruct the filtered list to find the real index.
-
-                self.notes_factory.guard().push_back((i, note.clone()));
+        let rows: Vec<(Note, Option<SearchHit>, String)> = self
+            .visible_notes()
+            .into_iter()
+            .map(|(note, hit)| {
+                let folder_name = note
+                    .folder_id
+                    .and_then(|id| self.folder_names.get(&id).cloned())
+                    .unwrap_or_else(|| "All Notes".to_string());
+                (note.clone(), hit.cloned(), folder_name)
+            })
+            .collect();
+
+        {
+            let mut guard = self.notes_factory.guard();
+            guard.clear();
+            for row in rows {
+                guard.push_back(row);
             }
         }
+        self.sync_selection();
+    }
+
+    /// Highlight the row of the selected note if it is in the current list.
+    fn sync_selection(&self) {
+        let list = self.notes_factory.widget();
+        let position = self
+            .selected_id
+            .and_then(|id| self.notes_factory.iter().position(|row| row.note.id == id));
+        match position.and_then(|i| list.row_at_index(i as i32)) {
+            Some(row) => list.select_row(Some(&row)),
+            None => list.unselect_all(),
+        }
     }
 }
@@ -0,0 +1,324 @@
+use crate::models::folder::{self, Folder};
+use crate::models::note::Note;
+use crate::utils::tasks::{self, OpenTask};
+use gtk::pango;
+use gtk::prelude::*;
+use relm4::factory::FactoryVecDeque;
+use relm4::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct TaskRow {
+    pub note_id: Uuid,
+    pub task: OpenTask,
+    pub source: String, // Note title, folder and line
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for TaskRow {
+    type Init = (Uuid, OpenTask, String); // Note ID, task, source
+    type Input = ();
+    type Output = TasksMsg;
+    type CommandOutput = ();
+    type ParentWidget = gtk::ListBox;
+
+    view! {
+        gtk::ListBoxRow {
+            set_activatable: true,
+            set_css_classes: &["sidebar-row"],
+            set_margin_bottom: 2,
+            set_margin_top: 2,
+            set_margin_start: 8,
+            set_margin_end: 8,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 10,
+                set_margin_all: 12,
+                set_css_classes: &["sidebar-card"],
+
+                gtk::CheckButton {
+                    set_valign: gtk::Align::Start,
+                    set_tooltip_text: Some("Check Off"),
+                    connect_toggled[sender, note_id = self.note_id, line = self.task.line] => move |button| {
+                        if button.is_active() {
+                            let _ = sender.output(TasksMsg::Check(note_id, line));
+                        }
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+                    set_hexpand: true,
+
+                    gtk::Label {
+                        set_label: &self.task.text,
+                        set_halign: gtk::Align::Start,
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                        set_wrap_mode: pango::WrapMode::WordChar,
+                        set_css_classes: &["task-text"],
+                    },
+
+                    gtk::Label {
+                        set_label: &self.source,
+                        set_halign: gtk::Align::Start,
+                        set_ellipsize: pango::EllipsizeMode::End,
+                        set_css_classes: &["sidebar-date"],
+                    },
+                },
+            }
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        let (note_id, task, source) = init;
+        Self {
+            note_id,
+            task,
+            source,
+        }
+    }
+}
+
+/// Which notes the task list takes its tasks from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FolderFilter {
+    Any,
+    NoFolder,
+    /// The folder and its subfolders
+    Folder(Uuid),
+}
+
+#[derive(Debug)]
+pub enum TasksMsg {
+    UpdateNotes(Vec<Note>),
+    UpdateFolders(Vec<Folder>),
+    SetFolder(u32), // Index into the folder choices
+    ActivateRow(usize),
+    Check(Uuid, usize), // Note ID, line
+}
+
+#[derive(Debug)]
+pub enum TasksOutput {
+    /// Check off the task on a line of a note
+    Check(Uuid, usize),
+    /// Open a note at a line
+    Open(Uuid, usize),
+}
+
+/// Every unchecked task of the notes it is given, grouped by note.
+pub struct Tasks {
+    rows: FactoryVecDeque<TaskRow>,
+    notes: Vec<Note>,
+    folders: Vec<Folder>,
+    folder_choices: Vec<FolderFilter>,
+    folder_names: gtk::StringList,
+    folder_filter: FolderFilter,
+    // Set when the folder choices were rebuilt so the dropdown picks the
+    // current filter again
+    reset: bool,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for Tasks {
+    type Init = Vec<Folder>;
+    type Input = TasksMsg;
+    type Output = TasksOutput;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 0,
+            set_hexpand: true,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 8,
+                set_margin_all: 12,
+
+                gtk::Label {
+                    #[watch]
+                    set_label: &match model.rows.len() {
+                        1 => "1 open task".to_string(),
+                        n => format!("{} open tasks", n),
+                    },
+                    set_halign: gtk::Align::Start,
+                    set_hexpand: true,
+                    set_css_classes: &["sidebar-title"],
+                },
+
+                gtk::DropDown {
+                    set_model: Some(&model.folder_names),
+                    set_tooltip_text: Some("Only show tasks from this folder"),
+                    #[track(model.reset)]
+                    set_selected: model.folder_index(),
+                    connect_selected_notify[sender] => move |dropdown| {
+                        sender.input(TasksMsg::SetFolder(dropdown.selected()));
+                    },
+                },
+            },
+
+            gtk::Label {
+                set_label: "No open tasks",
+                set_vexpand: true,
+                set_css_classes: &["dim-label"],
+                #[watch]
+                set_visible: model.rows.is_empty(),
+            },
+
+            gtk::ScrolledWindow {
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+                set_vexpand: true,
+                #[watch]
+                set_visible: !model.rows.is_empty(),
+
+                #[local_ref]
+                task_list -> gtk::ListBox {
+                    set_selection_mode: gtk::SelectionMode::None,
+                    set_activate_on_single_click: false,
+                    set_css_classes: &["navigation-sidebar"],
+
+                    connect_row_activated[sender] => move |_, row| {
+                        let index = row.index();
+                        if index >= 0 {
+                            sender.input(TasksMsg::ActivateRow(index as usize));
+                        }
+                    }
+                },
+            },
+        }
+    }
+
+    fn init(
+        folders: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let rows = FactoryVecDeque::builder()
+            .launch(gtk::ListBox::default())
+            .forward(sender.input_sender(), |msg| msg);
+
+        let mut model = Tasks {
+            rows,
+            notes: Vec::new(),
+            folders: Vec::new(),
+            folder_choices: Vec::new(),
+            folder_names: gtk::StringList::new(&[]),
+            folder_filter: FolderFilter::Any,
+            reset: false,
+        };
+        model.set_folders(folders);
+
+        let task_list = model.rows.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        self.reset = false;
+        match msg {
+            TasksMsg::UpdateNotes(notes) => {
+                self.notes = notes;
+                self.rebuild();
+            }
+            TasksMsg::UpdateFolders(folders) => {
+                if folders != self.folders {
+                    self.set_folders(folders);
+                    self.rebuild();
+                }
+            }
+            TasksMsg::SetFolder(index) => {
+                let filter = self
+                    .folder_choices
+                    .get(index as usize)
+                    .copied()
+                    .unwrap_or(FolderFilter::Any);
+                if filter != self.folder_filter {
+                    self.folder_filter = filter;
+                    self.rebuild();
+                }
+            }
+            TasksMsg::ActivateRow(index) => {
+                if let Some(row) = self.rows.get(index) {
+                    let _ = sender.output(TasksOutput::Open(row.note_id, row.task.line));
+                }
+            }
+            TasksMsg::Check(note_id, line) => {
+                let _ = sender.output(TasksOutput::Check(note_id, line));
+            }
+        }
+    }
+}
+
+impl Tasks {
+    fn set_folders(&mut self, folders: Vec<Folder>) {
+        let paths = folder::paths(&folders);
+        // A deleted folder can't filter anything any more
+        if let FolderFilter::Folder(id) = self.folder_filter {
+            if !paths.iter().any(|(folder_id, _)| *folder_id == id) {
+                self.folder_filter = FolderFilter::Any;
+            }
+        }
+
+        let mut names = vec!["All Folders", "No Folder"];
+        names.extend(paths.iter().map(|(_, path)| path.as_str()));
+        self.folder_names
+            .splice(0, self.folder_names.n_items(), &names);
+        self.folder_choices = [FolderFilter::Any, FolderFilter::NoFolder]
+            .into_iter()
+            .chain(paths.iter().map(|(id, _)| FolderFilter::Folder(*id)))
+            .collect();
+        self.folders = folders;
+        self.reset = true;
+    }
+
+    fn folder_index(&self) -> u32 {
+        self.folder_choices
+            .iter()
+            .position(|f| *f == self.folder_filter)
+            .unwrap_or(0) as u32
+    }
+
+    fn in_filter(&self, note: &Note) -> bool {
+        match (self.folder_filter, note.folder_id) {
+            (FolderFilter::Any, _) => true,
+            (FolderFilter::NoFolder, folder_id) => folder_id.is_none(),
+            (FolderFilter::Folder(id), Some(folder_id)) => {
+                folder::is_within(folder_id, id, &self.folders)
+            }
+            (FolderFilter::Folder(_), None) => false,
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let paths: HashMap<Uuid, String> = folder::paths(&self.folders).into_iter().collect();
+        let rows: Vec<(Uuid, OpenTask, String)> = self
+            .notes
+            .iter()
+            .filter(|note| self.in_filter(note))
+            .flat_map(|note| {
+                let folder_name = note
+                    .folder_id
+                    .and_then(|id| paths.get(&id).cloned())
+                    .unwrap_or_else(|| "No Folder".to_string());
+                tasks::open_tasks(&note.content)
+                    .into_iter()
+                    .map(move |task| {
+                        let source =
+                            format!("{} · {} · line {}", note.title, folder_name, task.line + 1);
+                        (note.id, task, source)
+                    })
+            })
+            .collect();
+
+        let mut guard = self.rows.guard();
+        guard.clear();
+        for row in rows {
+            guard.push_back(row);
+        }
+    }
+}
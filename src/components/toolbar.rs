@@ -1,20 +1,37 @@
+use crate::markdown::format::Format;
 use gtk::prelude::*;
 use relm4::prelude::*;
 
+#[derive(Debug, Clone, Copy)]
+pub enum TableEdit {
+    Insert,
+    AddRow,
+    RemoveRow,
+    AddColumn,
+    RemoveColumn,
+}
+
 #[derive(Debug)]
 pub enum ToolbarMsg {
     Checkbox,
     BulletList,
     NumberedList,
-    Bold,
-    Italic,
-    Highlight,
+    /// Turns the current lines into headings of this level; 0 is normal text
+    Heading(u8),
+    Table(TableEdit),
+    /// Turns an inline format on or off for the selection
+    Format(Format),
     Link,
+    /// From the editor: the formats at the cursor, shown as pressed buttons
+    SetActive(Vec<Format>),
 }
 
 #[derive(Debug)]
 pub struct Toolbar {
     list_popover: gtk::Popover,
+    heading_popover: gtk::Popover,
+    table_popover: gtk::Popover,
+    active: Vec<Format>,
 }
 
 #[relm4::component(pub)]
@@ -32,7 +49,7 @@ impl SimpleComponent for Toolbar {
             // Checkbox
             gtk::Button {
                 set_icon_name: "checkbox-checked-symbolic", // Better task icon
-                set_tooltip_text: Some("Task List"),
+                set_tooltip_text: Some("Task List (Ctrl+Enter checks)"),
                 set_css_classes: &["flat", "toolbar-btn"],
                 connect_clicked => ToolbarMsg::Checkbox,
             },
@@ -45,34 +62,77 @@ impl SimpleComponent for Toolbar {
                 set_popover: Some(&model.list_popover),
             },
 
+            // Heading Dropdown
+            gtk::MenuButton {
+                set_label: "H",
+                set_tooltip_text: Some("Headings"),
+                set_css_classes: &["flat", "toolbar-btn"],
+                set_popover: Some(&model.heading_popover),
+            },
+
+            // Table Dropdown
+            gtk::MenuButton {
+                set_icon_name: "x-office-spreadsheet-symbolic",
+                set_tooltip_text: Some("Table"),
+                set_css_classes: &["flat", "toolbar-btn"],
+                set_popover: Some(&model.table_popover),
+            },
+
             gtk::Separator {
                 set_orientation: gtk::Orientation::Vertical,
                 set_margin_start: 4,
                 set_margin_end: 4,
             },
 
-            // Bold
-            gtk::Button {
+            // Inline formats; pressed while the cursor is in them. Clicks
+            // only send the command, the editor reports the new state back.
+            gtk::ToggleButton {
                 set_icon_name: "format-text-bold-symbolic",
                 set_tooltip_text: Some("Bold (Ctrl+B)"),
                 set_css_classes: &["flat", "toolbar-btn"],
-                connect_clicked => ToolbarMsg::Bold,
+                #[watch]
+                set_active: model.active.contains(&Format::Bold),
+                connect_clicked => ToolbarMsg::Format(Format::Bold),
             },
-
-            // Italic
-            gtk::Button {
+            gtk::ToggleButton {
                 set_icon_name: "format-text-italic-symbolic",
                 set_tooltip_text: Some("Italic (Ctrl+I)"),
                 set_css_classes: &["flat", "toolbar-btn"],
-                connect_clicked => ToolbarMsg::Italic,
+                #[watch]
+                set_active: model.active.contains(&Format::Italic),
+                connect_clicked => ToolbarMsg::Format(Format::Italic),
             },
-
-            // Highlight
-            gtk::Button {
+            gtk::ToggleButton {
+                set_icon_name: "format-text-underline-symbolic",
+                set_tooltip_text: Some("Underline (Ctrl+U)"),
+                set_css_classes: &["flat", "toolbar-btn"],
+                #[watch]
+                set_active: model.active.contains(&Format::Underline),
+                connect_clicked => ToolbarMsg::Format(Format::Underline),
+            },
+            gtk::ToggleButton {
+                set_icon_name: "format-text-strikethrough-symbolic",
+                set_tooltip_text: Some("Strikethrough (Ctrl+Shift+X)"),
+                set_css_classes: &["flat", "toolbar-btn"],
+                #[watch]
+                set_active: model.active.contains(&Format::Strikethrough),
+                connect_clicked => ToolbarMsg::Format(Format::Strikethrough),
+            },
+            gtk::ToggleButton {
+                set_label: "</>",
+                set_tooltip_text: Some("Inline Code (Ctrl+E)"),
+                set_css_classes: &["flat", "toolbar-btn"],
+                #[watch]
+                set_active: model.active.contains(&Format::Code),
+                connect_clicked => ToolbarMsg::Format(Format::Code),
+            },
+            gtk::ToggleButton {
                 set_icon_name: "user-bookmarks-symbolic", // Looks like a marker/tag
-                set_tooltip_text: Some("Highlight"),
+                set_tooltip_text: Some("Highlight (Ctrl+Shift+H)"),
                 set_css_classes: &["flat", "toolbar-btn"],
-                connect_clicked => ToolbarMsg::Highlight,
+                #[watch]
+                set_active: model.active.contains(&Format::Highlight),
+                connect_clicked => ToolbarMsg::Format(Format::Highlight),
             },
 
             gtk::Separator {
@@ -84,7 +144,7 @@ impl SimpleComponent for Toolbar {
             // Link
             gtk::Button {
                 set_icon_name: "insert-link-symbolic",
-                set_tooltip_text: Some("Link"),
+                set_tooltip_text: Some("Link (Ctrl+K)"),
                 set_css_classes: &["flat", "toolbar-btn"],
                 connect_clicked => ToolbarMsg::Link,
             },
@@ -125,7 +185,60 @@ impl SimpleComponent for Toolbar {
         l_box.append(&num_btn);
         list_popover.set_child(Some(&l_box));
 
-        let model = Toolbar { list_popover };
+        let heading_popover = gtk::Popover::builder().build();
+        let h_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(2)
+            .build();
+
+        for level in 0..=6u8 {
+            let label = match level {
+                0 => "Normal Text".to_string(),
+                _ => format!("Heading {}", level),
+            };
+            let heading_btn = gtk::Button::builder()
+                .label(label)
+                .css_classes(["flat"])
+                .build();
+            let sender_clone = sender.clone();
+            heading_btn.connect_clicked(move |_| {
+                sender_clone.input(ToolbarMsg::Heading(level));
+            });
+            h_box.append(&heading_btn);
+        }
+        heading_popover.set_child(Some(&h_box));
+
+        let table_popover = gtk::Popover::builder().build();
+        let t_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(2)
+            .build();
+
+        for (label, edit) in [
+            ("Insert Table", TableEdit::Insert),
+            ("Add Row", TableEdit::AddRow),
+            ("Remove Row", TableEdit::RemoveRow),
+            ("Add Column", TableEdit::AddColumn),
+            ("Remove Column", TableEdit::RemoveColumn),
+        ] {
+            let table_btn = gtk::Button::builder()
+                .label(label)
+                .css_classes(["flat"])
+                .build();
+            let sender_clone = sender.clone();
+            table_btn.connect_clicked(move |_| {
+                sender_clone.input(ToolbarMsg::Table(edit));
+            });
+            t_box.append(&table_btn);
+        }
+        table_popover.set_child(Some(&t_box));
+
+        let model = Toolbar {
+            list_popover,
+            heading_popover,
+            table_popover,
+            active: Vec::new(),
+        };
         let widgets = view_output!();
         ComponentParts { model, widgets }
     }
@@ -133,7 +246,13 @@ impl SimpleComponent for Toolbar {
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         // Close popovers on selection
         match msg {
+            ToolbarMsg::SetActive(formats) => {
+                self.active = formats;
+                return;
+            }
             ToolbarMsg::BulletList | ToolbarMsg::NumberedList => self.list_popover.popdown(),
+            ToolbarMsg::Heading(_) => self.heading_popover.popdown(),
+            ToolbarMsg::Table(_) => self.table_popover.popdown(),
             _ => {}
         }
         let _ = sender.output(msg);
@@ -0,0 +1,113 @@
+use crate::core::note_service::NoteService;
+use crate::db::note_repository::NoteRepository;
+use crate::models::note::Note;
+use crate::utils::paths;
+use relm4::{ComponentSender, Worker};
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long typing has to pause before dirty notes are written.
+pub const AUTOSAVE_DELAY: Duration = Duration::from_millis(800);
+
+/// The longest wait before retrying after failed saves.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How long closing waits for the worker before writing the notes itself.
+pub const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before retrying after `failures` failed saves in a row,
+/// twice as long after each one.
+pub fn retry_delay(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    AUTOSAVE_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+/// What the header bar shows about pending writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveState {
+    Saved,
+    Unsaved,
+    Saving,
+    Failed(String),
+}
+
+impl SaveState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SaveState::Saved => "Saved",
+            SaveState::Unsaved => "Edited",
+            SaveState::Saving => "Saving…",
+            SaveState::Failed(_) => "Not saved",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AutosaveMsg {
+    Save(Note),
+    /// Answer with `Synced` once every save queued before this one has been
+    /// handled.
+    Sync,
+}
+
+#[derive(Debug)]
+pub enum AutosaveOutput {
+    Saved(Uuid),
+    Failed(Uuid, String),
+    Synced,
+}
+
+/// Writes notes on a background thread through its own database connection,
+/// so typing never waits on SQLite.
+pub struct AutosaveWorker {
+    note_service: Result<NoteService, String>,
+}
+
+impl Worker for AutosaveWorker {
+    type Init = PathBuf;
+    type Input = AutosaveMsg;
+    type Output = AutosaveOutput;
+
+    fn init(db_path: Self::Init, _sender: ComponentSender<Self>) -> Self {
+        let note_service = crate::db::init_db(&db_path)
+            .map(|conn| {
+                let storage_dir = paths::storage_dir(&db_path);
+                NoteService::new(NoteRepository::new(conn), storage_dir)
+            })
+            .map_err(|e| format!("Failed to open database: {}", e));
+        Self { note_service }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            AutosaveMsg::Save(note) => {
+                let result = match &self.note_service {
+                    Ok(service) => service.save_content(&note).map_err(|e| e.to_string()),
+                    Err(e) => Err(e.clone()),
+                };
+                let _ = sender.output(match result {
+                    Ok(()) => AutosaveOutput::Saved(note.id),
+                    Err(e) => AutosaveOutput::Failed(note.id, e),
+                });
+            }
+            AutosaveMsg::Sync => {
+                let _ = sender.output(AutosaveOutput::Synced);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_up_to_a_limit() {
+        assert_eq!(retry_delay(1), AUTOSAVE_DELAY);
+        assert_eq!(retry_delay(2), AUTOSAVE_DELAY * 2);
+        assert_eq!(retry_delay(4), AUTOSAVE_DELAY * 8);
+        assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+}
@@ -1,15 +1,39 @@
 use crate::db::note_repository::NoteRepository;
+use crate::models::attachment::{self, Attachment};
+use crate::models::folder::{Folder, NoteDisposal};
 use crate::models::note::Note;
-use rusqlite::Result;
+use crate::models::revision::Revision;
+use crate::models::search::SearchHit;
+use crate::models::tag::TagCount;
+use crate::utils::diff::{self, DiffLine};
+use crate::utils::{links, paths, tags};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{ffi, Error, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+const TRASH_RETENTION_KEY: &str = "trash_retention_days";
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+// Edits closer together than this share one revision
+const REVISION_WINDOW_MINUTES: i64 = 10;
+// Titles suggested while typing a `[[wiki link]]`
+const TITLE_SUGGESTIONS: usize = 8;
+const INDEX_VERSION_KEY: &str = "index_version";
+// Bump when tag or link parsing changes, so every note is indexed again
+const INDEX_VERSION: u32 = 2;
+
 pub struct NoteService {
     repo: NoteRepository,
+    // The database's directory; attachment paths are relative to it
+    storage_dir: PathBuf,
 }
 
 impl NoteService {
-    pub fn new(repo: NoteRepository) -> Self {
-        Self { repo }
+    pub fn new(repo: NoteRepository, storage_dir: PathBuf) -> Self {
+        Self { repo, storage_dir }
     }
 
     pub fn get_all_notes(&self) -> Result<Vec<Note>> {
@@ -19,26 +43,491 @@ impl NoteService {
     pub fn create_note(&self, title: String, content: String) -> Result<Note> {
         let note = Note::new(title, content);
         self.repo.create(&note)?;
+        self.repo.set_note_tags(note.id, &note.tags)?;
+        self.save_links(&note)?;
+        self.repo
+            .record_revision(&note, Duration::minutes(REVISION_WINDOW_MINUTES))?;
         Ok(note)
     }
 
     pub fn update_note(&self, note: &Note) -> Result<()> {
-        self.repo.update(note)
+        self.repo.update(note)?;
+        self.save_tags(note)?;
+        self.save_links(note)?;
+        self.repo
+            .record_revision(note, Duration::minutes(REVISION_WINDOW_MINUTES))
+    }
+
+    /// Save a note's title and content. Used by autosave, which must not
+    /// overwrite folder moves or trash state made since the edit was queued.
+    pub fn save_content(&self, note: &Note) -> Result<()> {
+        if self.repo.update_content(note)? == 0 {
+            // Deleted while the save was queued
+            return Ok(());
+        }
+        self.save_tags(note)?;
+        self.save_links(note)?;
+        self.repo
+            .record_revision(note, Duration::minutes(REVISION_WINDOW_MINUTES))
+    }
+
+    /// Revision history of a note, newest first.
+    pub fn get_revisions(&self, note_id: Uuid) -> Result<Vec<Revision>> {
+        self.repo.get_revisions(note_id)
+    }
+
+    pub fn get_revision(&self, id: Uuid) -> Result<Option<Revision>> {
+        self.repo.get_revision(id)
+    }
+
+    /// Line diff between two revisions, from `old` to `new`.
+    pub fn diff_revisions(&self, old: Uuid, new: Uuid) -> Result<Vec<DiffLine>> {
+        let old = self
+            .repo
+            .get_revision(old)?
+            .ok_or(Error::QueryReturnedNoRows)?;
+        let new = self
+            .repo
+            .get_revision(new)?
+            .ok_or(Error::QueryReturnedNoRows)?;
+        Ok(diff::diff_lines(&old.content, &new.content))
     }
 
+    /// Put a note back to the state captured in a revision. The restore is
+    /// saved as a new edit, so it shows up in (and can be undone from) the
+    /// history like any other change.
+    pub fn restore_revision(&self, revision_id: Uuid) -> Result<Note> {
+        let revision = self
+            .repo
+            .get_revision(revision_id)?
+            .ok_or(Error::QueryReturnedNoRows)?;
+        let mut note = self
+            .repo
+            .get(revision.note_id)?
+            .ok_or(Error::QueryReturnedNoRows)?;
+
+        note.title = revision.title;
+        note.content = revision.content;
+        note.updated_at = Utc::now();
+        note.tags = tags::extract_tags(&note.content);
+        self.repo.update(&note)?;
+        self.repo.set_note_tags(note.id, &note.tags)?;
+        self.save_links(&note)?;
+        // Always start a fresh revision so the state being replaced survives
+        self.repo.record_revision(&note, Duration::zero())?;
+        Ok(note)
+    }
+
+    /// Tags are derived from the content, so they are re-parsed on every save
+    /// rather than trusted from the caller.
+    fn save_tags(&self, note: &Note) -> Result<()> {
+        self.repo
+            .set_note_tags(note.id, &tags::extract_tags(&note.content))
+    }
+
+    /// Links are derived from the content too, like tags.
+    fn save_links(&self, note: &Note) -> Result<()> {
+        self.repo
+            .set_note_links(note.id, &links::linked_titles(&note.content))
+    }
+
+    /// Re-derive the tags and links of every note if they were indexed by
+    /// an older parser, or never: the migrations only create the tables.
+    /// Returns how many notes were indexed.
+    pub fn reindex_if_stale(&self) -> Result<usize> {
+        let indexed = self
+            .repo
+            .get_setting(INDEX_VERSION_KEY)?
+            .and_then(|v| v.parse::<u32>().ok());
+        if indexed == Some(INDEX_VERSION) {
+            return Ok(0);
+        }
+
+        let notes = self.repo.get_all_with_trashed()?;
+        for note in &notes {
+            self.save_tags(note)?;
+            self.save_links(note)?;
+        }
+        self.repo
+            .set_setting(INDEX_VERSION_KEY, &INDEX_VERSION.to_string())?;
+        Ok(notes.len())
+    }
+
+    /// The note a `[[wiki link]]` to `title` leads to.
+    pub fn find_note_by_title(&self, title: &str) -> Result<Option<Note>> {
+        self.repo.find_by_title(title)
+    }
+
+    /// Titles of other notes to suggest for a `[[wiki link]]` being typed.
+    pub fn suggest_titles(&self, query: &str, exclude: Uuid) -> Result<Vec<String>> {
+        self.repo.search_titles(query, exclude, TITLE_SUGGESTIONS)
+    }
+
+    /// Other notes that link to the note `id`, titled `title`.
+    pub fn get_backlinks(&self, id: Uuid, title: &str) -> Result<Vec<Note>> {
+        if title.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut notes = self.repo.get_linking_notes(title)?;
+        notes.retain(|note| note.id != id);
+        Ok(notes)
+    }
+
+    /// Point the links to the note `id` at its new title after a rename.
+    /// Links stay as they are if another note still has the old title, or
+    /// if the new one is blank. Returns the notes that were rewritten.
+    pub fn rename_links(&self, id: Uuid, old: &str, new: &str) -> Result<Vec<Note>> {
+        if old.trim().is_empty() || new.trim().is_empty() || old.trim() == new.trim() {
+            return Ok(Vec::new());
+        }
+        if self
+            .repo
+            .find_by_title(old)?
+            .is_some_and(|other| other.id != id)
+        {
+            return Ok(Vec::new());
+        }
+
+        let mut renamed = Vec::new();
+        for mut note in self.repo.get_linking_notes(old)? {
+            if note.id == id {
+                continue;
+            }
+            if let Some(content) = links::rename_wiki_links(&note.content, old, new) {
+                note.content = content;
+                note.updated_at = Utc::now();
+                self.update_note(&note)?;
+                renamed.push(note);
+            }
+        }
+        Ok(renamed)
+    }
+
+    pub fn get_tag_counts(&self) -> Result<Vec<TagCount>> {
+        self.repo.get_tag_counts()
+    }
+
+    pub fn get_trashed_notes(&self) -> Result<Vec<Note>> {
+        self.repo.get_trashed()
+    }
+
+    /// Move a note to the trash. Returns the deletion timestamp.
+    pub fn trash_note(&self, id: Uuid) -> Result<DateTime<Utc>> {
+        let now = Utc::now();
+        self.repo.trash(id, now)?;
+        Ok(now)
+    }
+
+    pub fn restore_note(&self, id: Uuid) -> Result<()> {
+        self.repo.restore(id)
+    }
+
+    /// Permanently delete a note, bypassing the trash.
     pub fn delete_note(&self, id: Uuid) -> Result<()> {
         self.repo.delete(id)
     }
 
-    pub fn rename_folder(&self, old_name: &str, new_name: &str) -> Result<()> {
-        self.repo.rename_folder(old_name, new_name)
+    pub fn empty_trash(&self) -> Result<usize> {
+        self.repo.empty_trash()
+    }
+
+    /// Days trashed notes are kept before being purged. 0 keeps them forever.
+    pub fn trash_retention_days(&self) -> Result<u32> {
+        Ok(self
+            .repo
+            .get_setting(TRASH_RETENTION_KEY)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS))
+    }
+
+    pub fn set_trash_retention_days(&self, days: u32) -> Result<()> {
+        self.repo
+            .set_setting(TRASH_RETENTION_KEY, &days.to_string())
+    }
+
+    /// Permanently delete notes that have been in the trash longer than the
+    /// retention period. Returns how many were removed.
+    pub fn purge_expired_trash(&self) -> Result<usize> {
+        let days = self.trash_retention_days()?;
+        if days == 0 {
+            return Ok(0);
+        }
+        self.repo
+            .purge_trashed_before(Utc::now() - Duration::days(days as i64))
+    }
+
+    pub fn rename_folder(&self, id: Uuid, name: &str) -> Result<()> {
+        self.repo.rename_folder(id, name)
     }
 
-    pub fn get_folders(&self) -> Result<Vec<String>> {
+    pub fn get_folders(&self) -> Result<Vec<Folder>> {
         self.repo.get_folders()
     }
 
-    pub fn add_folder(&self, name: &str) -> Result<()> {
-        self.repo.add_folder(name)
+    pub fn add_folder(&self, name: &str, parent_id: Option<Uuid>) -> Result<Folder> {
+        let folder = Folder::new(name.to_string(), parent_id);
+        self.repo.add_folder(&folder)?;
+        Ok(folder)
+    }
+
+    /// Move a folder (with everything in it) under `parent_id`, or to the top
+    /// level. Moving a folder into itself or one of its descendants is refused.
+    pub fn move_folder(&self, id: Uuid, parent_id: Option<Uuid>) -> Result<()> {
+        if let Some(parent_id) = parent_id {
+            if self.folder_subtree(id)?.contains(&parent_id) {
+                return Err(constraint_error("Cannot move a folder into itself"));
+            }
+        }
+        self.repo.move_folder(id, parent_id)
+    }
+
+    /// `id` followed by all folders nested anywhere below it.
+    pub fn folder_subtree(&self, id: Uuid) -> Result<Vec<Uuid>> {
+        let folders = self.repo.get_folders()?;
+        let mut subtree = vec![id];
+        let mut next = 0;
+        while next < subtree.len() {
+            let parent = subtree[next];
+            subtree.extend(
+                folders
+                    .iter()
+                    .filter(|f| f.parent_id == Some(parent))
+                    .map(|f| f.id),
+            );
+            next += 1;
+        }
+        Ok(subtree)
+    }
+
+    /// Notes outside the trash in a folder and its subfolders.
+    pub fn count_notes_in_folder(&self, id: Uuid) -> Result<usize> {
+        self.repo.count_notes_in_folders(&self.folder_subtree(id)?)
+    }
+
+    /// Delete a folder together with its subfolders. Their notes are moved or
+    /// trashed according to `notes`. Returns the number of notes affected.
+    pub fn delete_folder(&self, id: Uuid, notes: NoteDisposal) -> Result<usize> {
+        let subtree = self.folder_subtree(id)?;
+        if let NoteDisposal::MoveTo(Some(target)) = notes {
+            if subtree.contains(&target) {
+                return Err(constraint_error(
+                    "Cannot move notes into a folder that is being deleted",
+                ));
+            }
+        }
+        self.repo.delete_folders(&subtree, notes, Utc::now())
+    }
+
+    /// Copy `source` into the attachments directory as an attachment of
+    /// `note_id`. The original file can then move or go away.
+    pub fn attach_file(
+        &self,
+        note_id: Uuid,
+        source: &Path,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Attachment> {
+        let file_name = source.file_name().map_or_else(
+            || "Attachment".to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        self.store_attachment(note_id, &file_name, mime_type, |path| {
+            fs::copy(source, path).map(|_| ())
+        })
+    }
+
+    /// Store `bytes` (a pasted image, say) as an attachment of `note_id`.
+    pub fn attach_bytes(
+        &self,
+        note_id: Uuid,
+        file_name: &str,
+        mime_type: Option<&str>,
+        bytes: &[u8],
+    ) -> anyhow::Result<Attachment> {
+        self.store_attachment(note_id, file_name, mime_type, |path| fs::write(path, bytes))
+    }
+
+    /// Writes the file under a fresh id, keeping the extension so other apps
+    /// still recognise it, then records it.
+    fn store_attachment(
+        &self,
+        note_id: Uuid,
+        file_name: &str,
+        mime_type: Option<&str>,
+        write: impl FnOnce(&Path) -> io::Result<()>,
+    ) -> anyhow::Result<Attachment> {
+        let id = Uuid::new_v4();
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| format!(".{}", e.to_lowercase()))
+            .unwrap_or_default();
+        let file_path = format!("{}/{}{}", paths::ATTACHMENTS_DIR, id, extension);
+
+        let path = self.storage_dir.join(&file_path);
+        fs::create_dir_all(self.storage_dir.join(paths::ATTACHMENTS_DIR))?;
+        write(&path)?;
+
+        let attachment = Attachment {
+            id,
+            note_id,
+            file_name: file_name.to_string(),
+            file_path,
+            mime_type: mime_type.map(str::to_string),
+            size_bytes: fs::metadata(&path)?.len(),
+            created_at: Utc::now(),
+        };
+        if let Err(e) = self.repo.add_attachment(&attachment) {
+            let _ = fs::remove_file(&path);
+            return Err(e.into());
+        }
+        Ok(attachment)
+    }
+
+    pub fn get_attachment(&self, id: Uuid) -> Result<Option<Attachment>> {
+        self.repo.get_attachment(id)
+    }
+
+    /// Where an attachment's file is on disk.
+    pub fn attachment_path(&self, attachment: &Attachment) -> PathBuf {
+        self.storage_dir.join(&attachment.file_path)
+    }
+
+    /// Remove attachments that nothing links to any more: not a note, in the
+    /// trash or not, and not a revision a note could be restored to. Files
+    /// in the attachments directory without a record go too. Returns how
+    /// many files were removed.
+    pub fn clean_up_attachments(&self) -> anyhow::Result<usize> {
+        let referenced: HashSet<Uuid> = self
+            .repo
+            .all_contents()?
+            .iter()
+            .flat_map(|content| attachment::referenced_ids(content))
+            .collect();
+
+        let mut kept = HashSet::new();
+        for attachment in self.repo.get_all_attachments()? {
+            if referenced.contains(&attachment.id) {
+                kept.insert(self.attachment_path(&attachment));
+            } else {
+                self.repo.delete_attachment(attachment.id)?;
+            }
+        }
+
+        let entries = match fs::read_dir(self.storage_dir.join(paths::ATTACHMENTS_DIR)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_file() && !kept.contains(&path) {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Full-text search over titles and content, best matches first.
+    pub fn search(&self, query: &str) -> Result<Vec<SearchHit>> {
+        self.repo.search(query, 500)
+    }
+}
+
+fn constraint_error(message: &str) -> Error {
+    Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_CONSTRAINT),
+        Some(message.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn service() -> NoteService {
+        let repo = NoteRepository::new(db::get_memory_db().unwrap());
+        NoteService::new(repo, std::env::temp_dir())
+    }
+
+    fn note_in(service: &NoteService, folder: &Folder) -> Note {
+        let mut note = service
+            .create_note("Note".to_string(), String::new())
+            .unwrap();
+        note.folder_id = Some(folder.id);
+        service.update_note(&note).unwrap();
+        note
+    }
+
+    fn find(service: &NoteService, id: Uuid) -> Option<Note> {
+        let mut notes = service.get_all_notes().unwrap();
+        notes.extend(service.get_trashed_notes().unwrap());
+        notes.into_iter().find(|n| n.id == id)
+    }
+
+    #[test]
+    fn deleting_a_folder_moves_its_notes() {
+        let service = service();
+        let projects = service.add_folder("Projects", None).unwrap();
+        let meetings = service.add_folder("Meetings", Some(projects.id)).unwrap();
+        let archive = service.add_folder("Archive", None).unwrap();
+        let note = note_in(&service, &meetings);
+
+        assert_eq!(service.count_notes_in_folder(projects.id).unwrap(), 1);
+        let moved = service
+            .delete_folder(projects.id, NoteDisposal::MoveTo(Some(archive.id)))
+            .unwrap();
+        assert_eq!(moved, 1);
+        assert_eq!(find(&service, note.id).unwrap().folder_id, Some(archive.id));
+        let folders: Vec<Uuid> = service
+            .get_folders()
+            .unwrap()
+            .iter()
+            .map(|f| f.id)
+            .collect();
+        assert!(folders.contains(&archive.id));
+        assert!(!folders.contains(&projects.id) && !folders.contains(&meetings.id));
+    }
+
+    #[test]
+    fn deleting_a_folder_can_trash_its_notes() {
+        let service = service();
+        let projects = service.add_folder("Projects", None).unwrap();
+        let note = note_in(&service, &projects);
+        service
+            .delete_folder(projects.id, NoteDisposal::Trash)
+            .unwrap();
+        assert!(find(&service, note.id).unwrap().is_deleted);
+    }
+
+    #[test]
+    fn notes_cannot_move_into_a_folder_being_deleted() {
+        let service = service();
+        let projects = service.add_folder("Projects", None).unwrap();
+        let meetings = service.add_folder("Meetings", Some(projects.id)).unwrap();
+        let note = note_in(&service, &projects);
+        let target = NoteDisposal::MoveTo(Some(meetings.id));
+        assert!(service.delete_folder(projects.id, target).is_err());
+        assert_eq!(
+            find(&service, note.id).unwrap().folder_id,
+            Some(projects.id)
+        );
+    }
+
+    #[test]
+    fn folders_cannot_move_into_themselves() {
+        let service = service();
+        let projects = service.add_folder("Projects", None).unwrap();
+        let meetings = service.add_folder("Meetings", Some(projects.id)).unwrap();
+        assert!(service.move_folder(projects.id, Some(meetings.id)).is_err());
+        assert!(service.move_folder(projects.id, Some(projects.id)).is_err());
+        service.move_folder(meetings.id, None).unwrap();
+        service.move_folder(projects.id, Some(meetings.id)).unwrap();
+        assert_eq!(
+            service.folder_subtree(meetings.id).unwrap(),
+            [meetings.id, projects.id]
+        );
     }
 }
@@ -0,0 +1,552 @@
+use rusqlite::{ffi, params, Connection, Error, Result, Transaction};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// A single schema step. Steps are applied in order, each inside its own
+/// transaction, and `PRAGMA user_version` records the last applied version.
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base notes and folders tables",
+        up: base_schema,
+    },
+    Migration {
+        version: 2,
+        description: "full-text search index",
+        up: notes_fts,
+    },
+    Migration {
+        version: 3,
+        description: "soft delete and settings",
+        up: soft_delete,
+    },
+    Migration {
+        version: 4,
+        description: "note revision history",
+        up: note_revisions,
+    },
+    Migration {
+        version: 5,
+        description: "tags",
+        up: tags,
+    },
+    Migration {
+        version: 6,
+        description: "folder ids and nesting",
+        up: folder_ids,
+    },
+    Migration {
+        version: 7,
+        description: "attachments",
+        up: attachments,
+    },
+    Migration {
+        version: 8,
+        description: "links between notes",
+        up: note_links,
+    },
+];
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Bring the database up to `latest_version()`.
+pub fn run(conn: &mut Connection) -> Result<()> {
+    run_to(conn, latest_version())
+}
+
+/// Apply every pending migration up to and including `target`.
+pub fn run_to(conn: &mut Connection, target: i32) -> Result<()> {
+    let current = current_version(conn)?;
+
+    // Refuse to touch a database written by a newer build rather than guess.
+    if current > latest_version() {
+        return Err(Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_MISMATCH),
+            Some(format!(
+                "database schema version {} is newer than supported version {}",
+                current,
+                latest_version()
+            )),
+        ));
+    }
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+    {
+        let tx = conn.transaction()?;
+        if let Err(e) = (migration.up)(&tx) {
+            eprintln!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            );
+            return Err(e);
+        }
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+fn column_exists(tx: &Transaction, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// v1: the schema the app shipped with before versioning existed. Databases
+// created by those builds are at user_version 0 but may already have these
+// tables, so every statement here has to tolerate existing objects.
+fn base_schema(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS notes (
+            id TEXT PRIMARY KEY,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            folder TEXT
+        )",
+        [],
+    )?;
+
+    // Very early databases predate the folder column.
+    if !column_exists(tx, "notes", "folder")? {
+        tx.execute("ALTER TABLE notes ADD COLUMN folder TEXT", [])?;
+    }
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS folders (
+            name TEXT PRIMARY KEY
+        )",
+        [],
+    )?;
+
+    // Seed default folders for fresh databases only
+    let count: i64 = tx.query_row("SELECT COUNT(*) FROM folders", [], |row| row.get(0))?;
+    if count == 0 {
+        tx.execute("INSERT INTO folders (name) VALUES ('Personal')", [])?;
+        tx.execute("INSERT INTO folders (name) VALUES ('Work')", [])?;
+    }
+
+    Ok(())
+}
+
+// v2: FTS5 index over note titles and content. It is an external-content
+// table reading from `notes`, kept in sync by triggers.
+fn notes_fts(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE notes_fts USING fts5(
+            title,
+            content,
+            content='notes',
+            content_rowid='rowid',
+            tokenize='unicode61 remove_diacritics 2',
+            prefix='2 3'
+        );
+
+        CREATE TRIGGER notes_fts_ai AFTER INSERT ON notes BEGIN
+            INSERT INTO notes_fts(rowid, title, content)
+            VALUES (new.rowid, new.title, new.content);
+        END;
+
+        CREATE TRIGGER notes_fts_ad AFTER DELETE ON notes BEGIN
+            INSERT INTO notes_fts(notes_fts, rowid, title, content)
+            VALUES ('delete', old.rowid, old.title, old.content);
+        END;
+
+        CREATE TRIGGER notes_fts_au AFTER UPDATE OF title, content ON notes BEGIN
+            INSERT INTO notes_fts(notes_fts, rowid, title, content)
+            VALUES ('delete', old.rowid, old.title, old.content);
+            INSERT INTO notes_fts(rowid, title, content)
+            VALUES (new.rowid, new.title, new.content);
+        END;
+
+        INSERT INTO notes_fts(notes_fts) VALUES ('rebuild');",
+    )
+}
+
+// v3: trash support (`is_deleted`/`deleted_at` on notes) and the key-value
+// settings table that holds the trash retention period.
+fn soft_delete(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE notes ADD COLUMN is_deleted INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE notes ADD COLUMN deleted_at TEXT;
+        CREATE INDEX idx_notes_is_deleted ON notes(is_deleted);
+
+        CREATE TABLE settings (
+            key TEXT PRIMARY KEY,
+            value TEXT,
+            updated_at INTEGER NOT NULL
+        );",
+    )
+}
+
+// v4: per-note revision snapshots. Existing notes get their current state as
+// the first revision so there is something to diff against.
+fn note_revisions(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE note_revisions (
+            id TEXT PRIMARY KEY,
+            note_id TEXT NOT NULL,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            FOREIGN KEY(note_id) REFERENCES notes(id)
+        );
+        CREATE INDEX idx_note_revisions_note ON note_revisions(note_id, created_at);
+
+        CREATE TRIGGER note_revisions_ad AFTER DELETE ON notes BEGIN
+            DELETE FROM note_revisions WHERE note_id = old.id;
+        END;",
+    )?;
+
+    let mut stmt = tx.prepare("SELECT id, title, content, updated_at FROM notes")?;
+    let notes = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (id, title, content, updated_at) in notes {
+        tx.execute(
+            "INSERT INTO note_revisions (id, note_id, title, content, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![Uuid::new_v4().to_string(), id, title, content, updated_at],
+        )?;
+    }
+    Ok(())
+}
+
+// v5: `#tags` parsed out of note content. The tables start out empty;
+// `NoteService::reindex_if_stale` fills them in at startup, so changes to
+// the tag parser never change what this step does.
+fn tags(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE tags (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            color TEXT,
+            created_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE note_tags (
+            note_id TEXT NOT NULL,
+            tag_id TEXT NOT NULL,
+            PRIMARY KEY (note_id, tag_id),
+            FOREIGN KEY(note_id) REFERENCES notes(id),
+            FOREIGN KEY(tag_id) REFERENCES tags(id)
+        );
+        CREATE INDEX idx_note_tags_tag ON note_tags(tag_id);
+
+        CREATE TRIGGER note_tags_ad AFTER DELETE ON notes BEGIN
+            DELETE FROM note_tags WHERE note_id = old.id;
+            DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM note_tags);
+        END;",
+    )
+}
+
+// v6: folders get UUID ids and a parent, and notes point at the folder id
+// instead of its name. SQLite can't change a primary key in place, so the
+// folders table is rebuilt. Folder names only have to be unique among
+// siblings now, ignoring case, so names that only differ in case are merged
+// into one folder.
+fn folder_ids(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE folders_v6 (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            parent_id TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );",
+    )?;
+
+    // Notes may name folders that were never in the folders table. The
+    // folders table's spelling of a name wins over the notes'.
+    let mut stmt = tx.prepare(
+        "SELECT name FROM (
+             SELECT name, 0 AS source FROM folders
+             UNION ALL
+             SELECT folder, 1 FROM notes WHERE folder IS NOT NULL AND folder != ''
+         )
+         ORDER BY source, name",
+    )?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+
+    // NOCASE, like the index below, only folds ASCII letters
+    let mut seen = HashSet::new();
+    let now = chrono::Utc::now().timestamp_millis();
+    for name in names {
+        if !seen.insert(name.to_ascii_lowercase()) {
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO folders_v6 (id, name, parent_id, created_at, updated_at)
+             VALUES (?1, ?2, NULL, ?3, ?3)",
+            params![Uuid::new_v4().to_string(), name, now],
+        )?;
+    }
+
+    tx.execute_batch(
+        "ALTER TABLE notes ADD COLUMN folder_id TEXT;
+        UPDATE notes SET folder_id =
+            (SELECT id FROM folders_v6 WHERE name = notes.folder COLLATE NOCASE);
+        ALTER TABLE notes DROP COLUMN folder;
+        CREATE INDEX idx_notes_folder ON notes(folder_id);
+
+        DROP TABLE folders;
+        ALTER TABLE folders_v6 RENAME TO folders;
+        CREATE INDEX idx_folders_parent ON folders(parent_id);
+        CREATE UNIQUE INDEX idx_folders_sibling_name
+            ON folders(ifnull(parent_id, ''), name COLLATE NOCASE);",
+    )
+}
+
+// v7: files attached to notes. The files themselves live in the
+// `attachments/` directory next to the database; `file_path` is relative to
+// the database's directory.
+fn attachments(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE attachments (
+            id TEXT PRIMARY KEY,
+            note_id TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            mime_type TEXT,
+            size_bytes INTEGER,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY(note_id) REFERENCES notes(id)
+        );
+        CREATE INDEX idx_attachments_note ON attachments(note_id);
+
+        CREATE TRIGGER attachments_ad AFTER DELETE ON notes BEGIN
+            DELETE FROM attachments WHERE note_id = old.id;
+        END;",
+    )
+}
+
+// v8: `[[wiki links]]` between notes. Links are kept by the title they name,
+// so they can point at notes that don't exist yet. Like tags, existing
+// notes are indexed by `NoteService::reindex_if_stale` rather than here.
+fn note_links(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE note_links (
+            note_id TEXT NOT NULL,
+            target_title TEXT NOT NULL COLLATE NOCASE,
+            PRIMARY KEY (note_id, target_title),
+            FOREIGN KEY(note_id) REFERENCES notes(id)
+        );
+        CREATE INDEX idx_note_links_target ON note_links(target_title);
+
+        CREATE TRIGGER note_links_ad AFTER DELETE ON notes BEGIN
+            DELETE FROM note_links WHERE note_id = old.id;
+        END;",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_object(conn: &Connection, name: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+            params![name],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+            > 0
+    }
+
+    fn folder_names(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM folders ORDER BY name")
+            .unwrap();
+        let names = stmt.query_map([], |row| row.get(0)).unwrap();
+        names.collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn fresh_database_reaches_latest_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(folder_names(&conn), ["Personal", "Work"]);
+        // Running again has nothing left to do
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn legacy_database_without_folder_column() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE notes (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE folders (name TEXT PRIMARY KEY);
+            INSERT INTO folders (name) VALUES ('Ideas');
+            INSERT INTO notes VALUES ('a', 'Hello', 'hello world',
+                '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');",
+        )
+        .unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+
+        run(&mut conn).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        // Existing folders are kept and the defaults aren't added
+        assert_eq!(folder_names(&conn), ["Ideas"]);
+        let folder_id: Option<String> = conn
+            .query_row("SELECT folder_id FROM notes WHERE id = 'a'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(folder_id, None);
+        // The search index and revision history pick up the existing note
+        let found: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notes_fts WHERE notes_fts MATCH 'hello'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(found, 1);
+        let revisions: i64 = conn
+            .query_row("SELECT COUNT(*) FROM note_revisions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(revisions, 1);
+    }
+
+    #[test]
+    fn folders_differing_in_case_are_merged() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run_to(&mut conn, 5).unwrap();
+        conn.execute_batch(
+            "DELETE FROM folders;
+            INSERT INTO folders (name) VALUES ('Work'), ('work');
+            INSERT INTO notes (id, title, content, created_at, updated_at, folder) VALUES
+                ('a', 'A', '', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00', 'work'),
+                ('b', 'B', '', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00', 'WORK'),
+                ('c', 'C', '', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00', 'ideas'),
+                ('d', 'D', '', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00', 'Ideas');",
+        )
+        .unwrap();
+
+        run(&mut conn).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(folder_names(&conn), ["Ideas", "Work"]);
+        let folder_of = |note: &str| -> String {
+            conn.query_row(
+                "SELECT folders.name FROM notes JOIN folders ON folders.id = notes.folder_id
+                 WHERE notes.id = ?1",
+                params![note],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(folder_of("a"), "Work");
+        assert_eq!(folder_of("b"), "Work");
+        assert_eq!(folder_of("c"), "Ideas");
+        assert_eq!(folder_of("d"), "Ideas");
+    }
+
+    #[test]
+    fn each_step_applies_in_order() {
+        // An object each step creates
+        let created = [
+            (1, "notes"),
+            (2, "notes_fts"),
+            (3, "settings"),
+            (4, "note_revisions"),
+            (5, "note_tags"),
+            (6, "idx_folders_sibling_name"),
+            (7, "attachments"),
+            (8, "note_links"),
+        ];
+        assert_eq!(created.len(), MIGRATIONS.len());
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        for (version, name) in created {
+            assert!(
+                !has_object(&conn, name),
+                "{} exists before v{}",
+                name,
+                version
+            );
+            run_to(&mut conn, version).unwrap();
+            assert_eq!(current_version(&conn).unwrap(), version);
+            assert!(
+                has_object(&conn, name),
+                "v{} didn't create {}",
+                version,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn failed_step_rolls_back() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run_to(&mut conn, 2).unwrap();
+        // v3 creates this table, so it fails halfway through
+        conn.execute_batch("CREATE TABLE settings (key TEXT)")
+            .unwrap();
+
+        assert!(run(&mut conn).is_err());
+
+        assert_eq!(current_version(&conn).unwrap(), 2);
+        // The columns v3 added before failing are gone again
+        assert!(conn.prepare("SELECT is_deleted FROM notes").is_err());
+        assert!(!has_object(&conn, "note_revisions"));
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+
+        match run(&mut conn) {
+            Err(Error::SqliteFailure(_, Some(message))) => {
+                assert!(message.contains("newer than supported"), "{}", message);
+            }
+            other => panic!("expected a version error, got {:?}", other),
+        }
+        assert_eq!(current_version(&conn).unwrap(), latest_version() + 1);
+    }
+}
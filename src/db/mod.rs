@@ -1,56 +1,23 @@
+pub mod migrations;
 pub mod note_repository;
 use rusqlite::{Connection, Result};
 use std::path::Path;
+use std::time::Duration;
 
 pub fn init_db<P: AsRef<Path>>(path: P) -> Result<Connection> {
-    let conn = Connection::open(path)?;
+    let mut conn = Connection::open(path)?;
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS notes (
-            id TEXT PRIMARY KEY,
-            title TEXT NOT NULL,
-            content TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            folder TEXT
-        )",
-        [],
-    )?;
-
-    // Migration: Add folder column if it doesn't exist
-    let _ = conn.execute("ALTER TABLE notes ADD COLUMN folder TEXT", []);
-
-    // Create folders table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS folders (
-            name TEXT PRIMARY KEY
-        )",
-        [],
-    )?;
-
-    // Insert default folders if table is empty
-    let count: i64 = conn.query_row("SELECT COUNT(*) FROM folders", [], |row| row.get(0))?;
-    if count == 0 {
-        conn.execute("INSERT INTO folders (name) VALUES ('Personal')", [])?;
-        conn.execute("INSERT INTO folders (name) VALUES ('Work')", [])?;
-    }
+    // The UI and the autosave worker each hold a connection; WAL lets reads
+    // proceed during a write and the timeout covers the rest.
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.busy_timeout(Duration::from_secs(5))?;
 
+    migrations::run(&mut conn)?;
     Ok(conn)
 }
 
 pub fn get_memory_db() -> Result<Connection> {
-    let conn = Connection::open_in_memory()?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS notes (
-            id TEXT PRIMARY KEY,
-            title TEXT NOT NULL,
-            content TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        )",
-        [],
-    )?;
-
+    let mut conn = Connection::open_in_memory()?;
+    migrations::run(&mut conn)?;
     Ok(conn)
 }
@@ -1,6 +1,13 @@
+use crate::models::attachment::Attachment;
+use crate::models::folder::{Folder, NoteDisposal};
 use crate::models::note::Note;
+use crate::models::revision::Revision;
+use crate::models::search::SearchHit;
+use crate::models::tag::TagCount;
+use chrono::Duration;
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Result};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, Error, OptionalExtension, Params, Result, Row};
 use uuid::Uuid;
 
 pub struct NoteRepository {
@@ -14,24 +21,50 @@ impl NoteRepository {
 
     pub fn create(&self, note: &Note) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO notes (id, title, content, created_at, updated_at, folder) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO notes (id, title, content, created_at, updated_at, folder_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
                 note.id.to_string(),
                 note.title,
                 note.content,
                 note.created_at.to_rfc3339(),
                 note.updated_at.to_rfc3339(),
-                note.folder,
+                note.folder_id.map(|id| id.to_string()),
             ],
         )?;
         Ok(())
     }
 
     pub fn get_all(&self) -> Result<Vec<Note>> {
-        let mut stmt = self.conn.prepare("SELECT id, title, content, created_at, updated_at, folder FROM notes ORDER BY updated_at DESC")?;
-        let note_iter = stmt.query_map([], |row| {
+        self.query_notes("WHERE is_deleted = 0 ORDER BY updated_at DESC", [])
+    }
+
+    pub fn get(&self, id: Uuid) -> Result<Option<Note>> {
+        Ok(self
+            .query_notes("WHERE id = ?1", params![id.to_string()])?
+            .pop())
+    }
+
+    /// Every note, in the trash or not.
+    pub fn get_all_with_trashed(&self) -> Result<Vec<Note>> {
+        self.query_notes("", [])
+    }
+
+    pub fn get_trashed(&self) -> Result<Vec<Note>> {
+        self.query_notes("WHERE is_deleted = 1 ORDER BY deleted_at DESC", [])
+    }
+
+    fn query_notes<P: Params>(&self, clause: &str, params: P) -> Result<Vec<Note>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, title, content, created_at, updated_at, folder_id, is_deleted, deleted_at,
+                (SELECT group_concat(tags.name, ' ') FROM note_tags
+                 JOIN tags ON tags.id = note_tags.tag_id
+                 WHERE note_tags.note_id = notes.id)
+             FROM notes {}",
+            clause
+        ))?;
+        let note_iter = stmt.query_map(params, |row| {
             Ok(Note {
-                id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap_or_default(),
+                id: uuid_column(row, 0)?,
                 title: row.get(1)?,
                 content: row.get(2)?,
                 created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(3)?)
@@ -40,57 +73,222 @@ impl NoteRepository {
                 updated_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?)
                     .unwrap_or(Utc::now().into())
                     .with_timezone(&Utc),
-                folder: row.get(5)?,
+                folder_id: optional_uuid_column(row, 5)?,
+                is_deleted: row.get(6)?,
+                deleted_at: row
+                    .get::<_, Option<String>>(7)?
+                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                    .map(|d| d.with_timezone(&Utc)),
+                tags: split_tags(row.get::<_, Option<String>>(8)?),
             })
         })?;
 
-        let mut notes = Vec::new();
-        for note in note_iter {
-            notes.push(note?);
-        }
-        Ok(notes)
+        skip_malformed(note_iter)
     }
 
     pub fn update(&self, note: &Note) -> Result<()> {
         self.conn.execute(
-            "UPDATE notes SET title = ?1, content = ?2, updated_at = ?3, folder = ?4 WHERE id = ?5",
+            "UPDATE notes SET title = ?1, content = ?2, updated_at = ?3, folder_id = ?4 WHERE id = ?5",
             params![
                 note.title,
                 note.content,
                 note.updated_at.to_rfc3339(),
-                note.folder,
+                note.folder_id.map(|id| id.to_string()),
                 note.id.to_string(),
             ],
         )?;
         Ok(())
     }
 
+    /// Write only the editable text of a note, leaving folder and trash state
+    /// alone. Returns the number of rows changed (0 if the note is gone).
+    pub fn update_content(&self, note: &Note) -> Result<usize> {
+        self.conn.execute(
+            "UPDATE notes SET title = ?1, content = ?2, updated_at = ?3 WHERE id = ?4",
+            params![
+                note.title,
+                note.content,
+                note.updated_at.to_rfc3339(),
+                note.id.to_string(),
+            ],
+        )
+    }
+
+    /// Replace the tags of a note, dropping tags no note uses any more.
+    pub fn set_note_tags(&self, note_id: Uuid, tags: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM note_tags WHERE note_id = ?1",
+            params![note_id.to_string()],
+        )?;
+        let now = Utc::now().timestamp_millis();
+        for tag in tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO tags (id, name, created_at) VALUES (?1, ?2, ?3)",
+                params![Uuid::new_v4().to_string(), tag, now],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO note_tags (note_id, tag_id)
+                 SELECT ?1, id FROM tags WHERE name = ?2",
+                params![note_id.to_string(), tag],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM note_tags)",
+            [],
+        )?;
+        tx.commit()
+    }
+
+    /// Replace the titles a note links to.
+    pub fn set_note_links(&self, note_id: Uuid, titles: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM note_links WHERE note_id = ?1",
+            params![note_id.to_string()],
+        )?;
+        for title in titles {
+            tx.execute(
+                "INSERT OR IGNORE INTO note_links (note_id, target_title) VALUES (?1, ?2)",
+                params![note_id.to_string(), title],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Notes outside the trash that link to `title`, most recent first.
+    pub fn get_linking_notes(&self, title: &str) -> Result<Vec<Note>> {
+        self.query_notes(
+            "WHERE is_deleted = 0
+               AND id IN (SELECT note_id FROM note_links WHERE target_title = ?1)
+             ORDER BY updated_at DESC",
+            params![title.trim()],
+        )
+    }
+
+    /// The note outside the trash titled `title`, ignoring case and
+    /// surrounding whitespace. The most recently edited one wins a tie.
+    pub fn find_by_title(&self, title: &str) -> Result<Option<Note>> {
+        Ok(self
+            .query_notes(
+                "WHERE is_deleted = 0 AND trim(title) = ?1 COLLATE NOCASE
+                 ORDER BY updated_at DESC LIMIT 1",
+                params![title.trim()],
+            )?
+            .pop())
+    }
+
+    /// Titles of notes outside the trash containing `query`, those starting
+    /// with it first.
+    pub fn search_titles(&self, query: &str, exclude: Uuid, limit: usize) -> Result<Vec<String>> {
+        let pattern = query
+            .trim()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let mut stmt = self.conn.prepare(
+            "SELECT title FROM notes
+             WHERE is_deleted = 0 AND id != ?1 AND trim(title) != ''
+               AND title LIKE '%' || ?2 || '%' ESCAPE '\\'
+             ORDER BY title LIKE ?2 || '%' ESCAPE '\\' DESC, updated_at DESC
+             LIMIT ?3",
+        )?;
+        let titles = stmt
+            .query_map(params![exclude.to_string(), pattern, limit as i64], |row| {
+                row.get(0)
+            })?;
+        titles.collect()
+    }
+
+    /// Every tag with the number of notes outside the trash using it.
+    pub fn get_tag_counts(&self) -> Result<Vec<TagCount>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tags.name, COUNT(notes.id) FROM tags
+             JOIN note_tags ON note_tags.tag_id = tags.id
+             JOIN notes ON notes.id = note_tags.note_id AND notes.is_deleted = 0
+             GROUP BY tags.id
+             ORDER BY tags.name",
+        )?;
+        let counts = stmt.query_map([], |row| {
+            Ok(TagCount {
+                name: row.get(0)?,
+                count: row.get::<_, i64>(1)? as usize,
+            })
+        })?;
+        counts.collect()
+    }
+
+    /// Move a note to the trash (soft delete).
+    pub fn trash(&self, id: Uuid, deleted_at: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE notes SET is_deleted = 1, deleted_at = ?1 WHERE id = ?2",
+            params![deleted_at.to_rfc3339(), id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    pub fn restore(&self, id: Uuid) -> Result<()> {
+        self.conn.execute(
+            "UPDATE notes SET is_deleted = 0, deleted_at = NULL WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Permanently remove a note.
     pub fn delete(&self, id: Uuid) -> Result<()> {
         self.conn
             .execute("DELETE FROM notes WHERE id = ?1", params![id.to_string()])?;
         Ok(())
     }
 
-    pub fn rename_folder(&self, old_name: &str, new_name: &str) -> Result<()> {
-        // Update the folder name in the folders table
+    /// Permanently remove trashed notes deleted before `cutoff`.
+    pub fn purge_trashed_before(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        self.conn.execute(
+            "DELETE FROM notes WHERE is_deleted = 1 AND deleted_at < ?1",
+            params![cutoff.to_rfc3339()],
+        )
+    }
+
+    pub fn empty_trash(&self) -> Result<usize> {
+        self.conn
+            .execute("DELETE FROM notes WHERE is_deleted = 1", [])
+    }
+
+    pub fn rename_folder(&self, id: Uuid, name: &str) -> Result<()> {
         self.conn.execute(
-            "UPDATE folders SET name = ?1 WHERE name = ?2",
-            params![new_name, old_name],
+            "UPDATE folders SET name = ?1, updated_at = ?2 WHERE id = ?3",
+            params![name, Utc::now().timestamp_millis(), id.to_string()],
         )?;
+        Ok(())
+    }
 
-        // Update the folder column in the notes table
+    pub fn move_folder(&self, id: Uuid, parent_id: Option<Uuid>) -> Result<()> {
         self.conn.execute(
-            "UPDATE notes SET folder = ?1 WHERE folder = ?2",
-            params![new_name, old_name],
+            "UPDATE folders SET parent_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![
+                parent_id.map(|id| id.to_string()),
+                Utc::now().timestamp_millis(),
+                id.to_string()
+            ],
         )?;
         Ok(())
     }
 
-    pub fn get_folders(&self) -> Result<Vec<String>> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT name FROM folders ORDER BY name")?;
-        let folder_iter = stmt.query_map([], |row| row.get(0))?;
+    pub fn get_folders(&self) -> Result<Vec<Folder>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, parent_id, created_at, updated_at FROM folders
+             ORDER BY name COLLATE NOCASE",
+        )?;
+        let folder_iter = stmt.query_map([], |row| {
+            Ok(Folder {
+                id: uuid_column(row, 0)?,
+                name: row.get(1)?,
+                parent_id: optional_uuid_column(row, 2)?,
+                created_at: DateTime::from_timestamp_millis(row.get(3)?).unwrap_or_default(),
+                updated_at: DateTime::from_timestamp_millis(row.get(4)?).unwrap_or_default(),
+            })
+        })?;
 
         let mut folders = Vec::new();
         for folder in folder_iter {
@@ -99,9 +297,455 @@ impl NoteRepository {
         Ok(folders)
     }
 
-    pub fn add_folder(&self, name: &str) -> Result<()> {
+    /// Delete the given folders, handling the notes in them as `notes` says.
+    /// Returns how many notes (outside the trash) were moved or trashed.
+    pub fn delete_folders(
+        &self,
+        ids: &[Uuid],
+        notes: NoteDisposal,
+        now: DateTime<Utc>,
+    ) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut affected = 0;
+        for id in ids {
+            let id = id.to_string();
+            match notes {
+                NoteDisposal::MoveTo(target) => {
+                    affected += tx.execute(
+                        "UPDATE notes SET folder_id = ?1 WHERE folder_id = ?2 AND is_deleted = 0",
+                        params![target.map(|t| t.to_string()), id],
+                    )?;
+                }
+                NoteDisposal::Trash => {
+                    affected += tx.execute(
+                        "UPDATE notes SET is_deleted = 1, deleted_at = ?1
+                         WHERE folder_id = ?2 AND is_deleted = 0",
+                        params![now.to_rfc3339(), id],
+                    )?;
+                }
+            }
+            // Notes already in the trash would otherwise point at nothing
+            tx.execute(
+                "UPDATE notes SET folder_id = NULL WHERE folder_id = ?1",
+                params![id],
+            )?;
+            tx.execute("DELETE FROM folders WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(affected)
+    }
+
+    /// Number of notes outside the trash in any of the given folders.
+    pub fn count_notes_in_folders(&self, ids: &[Uuid]) -> Result<usize> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT COUNT(*) FROM notes WHERE folder_id = ?1 AND is_deleted = 0")?;
+        let mut count = 0;
+        for id in ids {
+            count += stmt.query_row(params![id.to_string()], |row| row.get::<_, i64>(0))? as usize;
+        }
+        Ok(count)
+    }
+
+    pub fn add_folder(&self, folder: &Folder) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO folders (id, name, parent_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                folder.id.to_string(),
+                folder.name,
+                folder.parent_id.map(|id| id.to_string()),
+                folder.created_at.timestamp_millis(),
+                folder.updated_at.timestamp_millis(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Snapshot `note` into its revision history. If the newest revision was
+    /// opened less than `window` ago it is overwritten instead, so a burst of
+    /// edits becomes one revision.
+    pub fn record_revision(&self, note: &Note, window: Duration) -> Result<()> {
+        let latest = self
+            .conn
+            .query_row(
+                "SELECT id, title, content, created_at FROM note_revisions
+                 WHERE note_id = ?1 ORDER BY created_at DESC LIMIT 1",
+                params![note.id.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        if let Some((id, title, content, created_at)) = latest {
+            if title == note.title && content == note.content {
+                return Ok(());
+            }
+            if parse_timestamp(&created_at) > note.updated_at - window {
+                self.conn.execute(
+                    "UPDATE note_revisions SET title = ?1, content = ?2, updated_at = ?3 WHERE id = ?4",
+                    params![note.title, note.content, note.updated_at.to_rfc3339(), id],
+                )?;
+                return Ok(());
+            }
+        }
+
+        self.conn.execute(
+            "INSERT INTO note_revisions (id, note_id, title, content, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![
+                Uuid::new_v4().to_string(),
+                note.id.to_string(),
+                note.title,
+                note.content,
+                note.updated_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Revisions of a note, newest first.
+    pub fn get_revisions(&self, note_id: Uuid) -> Result<Vec<Revision>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, note_id, title, content, created_at, updated_at FROM note_revisions
+             WHERE note_id = ?1 ORDER BY created_at DESC",
+        )?;
+        let revision_iter = stmt.query_map(params![note_id.to_string()], revision_from_row)?;
+
+        let mut revisions = Vec::new();
+        for revision in revision_iter {
+            revisions.push(revision?);
+        }
+        Ok(revisions)
+    }
+
+    pub fn get_revision(&self, id: Uuid) -> Result<Option<Revision>> {
+        self.conn
+            .query_row(
+                "SELECT id, note_id, title, content, created_at, updated_at FROM note_revisions
+                 WHERE id = ?1",
+                params![id.to_string()],
+                revision_from_row,
+            )
+            .optional()
+    }
+
+    pub fn add_attachment(&self, attachment: &Attachment) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO attachments (id, note_id, file_name, file_path, mime_type, size_bytes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                attachment.id.to_string(),
+                attachment.note_id.to_string(),
+                attachment.file_name,
+                attachment.file_path,
+                attachment.mime_type,
+                attachment.size_bytes as i64,
+                attachment.created_at.timestamp_millis(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_attachment(&self, id: Uuid) -> Result<Option<Attachment>> {
+        self.conn
+            .query_row(
+                "SELECT id, note_id, file_name, file_path, mime_type, size_bytes, created_at
+                 FROM attachments WHERE id = ?1",
+                params![id.to_string()],
+                attachment_from_row,
+            )
+            .optional()
+    }
+
+    pub fn get_all_attachments(&self) -> Result<Vec<Attachment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, note_id, file_name, file_path, mime_type, size_bytes, created_at
+             FROM attachments",
+        )?;
+        let attachment_iter = stmt.query_map([], attachment_from_row)?;
+
+        let mut attachments = Vec::new();
+        for attachment in attachment_iter {
+            attachments.push(attachment?);
+        }
+        Ok(attachments)
+    }
+
+    pub fn delete_attachment(&self, id: Uuid) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM attachments WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// The content of every note, trashed ones included, and of every
+    /// revision: everything that may link to an attachment.
+    pub fn all_contents(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT content FROM notes UNION ALL SELECT content FROM note_revisions")?;
+        let contents = stmt.query_map([], |row| row.get(0))?;
+        contents.collect()
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
         self.conn
-            .execute("INSERT INTO folders (name) VALUES (?1)", params![name])?;
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map(Option::flatten)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value, Utc::now().timestamp_millis()],
+        )?;
         Ok(())
     }
+
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        // Title matches weigh more than content matches
+        let mut stmt = self.conn.prepare(
+            "SELECT notes.id, snippet(notes_fts, -1, char(2), char(3), '…', 12)
+             FROM notes_fts
+             JOIN notes ON notes.rowid = notes_fts.rowid
+             WHERE notes_fts MATCH ?1 AND notes.is_deleted = 0
+             ORDER BY bm25(notes_fts, 10.0, 1.0)
+             LIMIT ?2",
+        )?;
+        let hit_iter = stmt.query_map(params![fts_query, limit as i64], |row| {
+            let (snippet, matches) = split_snippet(&row.get::<_, String>(1)?);
+            Ok(SearchHit {
+                note_id: uuid_column(row, 0)?,
+                snippet,
+                matches,
+            })
+        })?;
+
+        skip_malformed(hit_iter)
+    }
+}
+
+fn parse_timestamp(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn revision_from_row(row: &Row) -> Result<Revision> {
+    Ok(Revision {
+        id: uuid_column(row, 0)?,
+        note_id: uuid_column(row, 1)?,
+        title: row.get(2)?,
+        content: row.get(3)?,
+        created_at: parse_timestamp(&row.get::<_, String>(4)?),
+        updated_at: parse_timestamp(&row.get::<_, String>(5)?),
+    })
+}
+
+fn attachment_from_row(row: &Row) -> Result<Attachment> {
+    Ok(Attachment {
+        id: uuid_column(row, 0)?,
+        note_id: uuid_column(row, 1)?,
+        file_name: row.get(2)?,
+        file_path: row.get(3)?,
+        mime_type: row.get(4)?,
+        size_bytes: row.get::<_, Option<i64>>(5)?.unwrap_or(0) as u64,
+        created_at: DateTime::from_timestamp_millis(row.get(6)?).unwrap_or_default(),
+    })
+}
+
+/// Turn free text from the search box into an FTS5 query.
+///
+/// Double-quoted runs become phrase queries and bare words become prefix
+/// queries, so results narrow as the user types. Everything is quoted, which
+/// keeps FTS5 operators and punctuation from producing syntax errors.
+fn fts_query(input: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    for (i, part) in input.split('"').enumerate() {
+        // Odd segments sit between a pair of quotes
+        if i % 2 == 1 {
+            if !part.trim().is_empty() {
+                terms.push(format!("\"{}\"", part.trim()));
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            let word = word.trim_end_matches('*');
+            if !word.is_empty() {
+                terms.push(format!("\"{}\"*", word));
+            }
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Strip the \x02/\x03 markers `snippet()` puts around matches and return the
+/// clean text plus the byte ranges they enclosed.
+fn split_snippet(raw: &str) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::with_capacity(raw.len());
+    let mut matches = Vec::new();
+    let mut open = None;
+    for c in raw.chars() {
+        match c {
+            '\u{2}' => open = Some(text.len()),
+            '\u{3}' => {
+                if let Some(start) = open.take() {
+                    matches.push((start, text.len()));
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+    (text, matches)
+}
+
+/// A UUID column. A malformed id is an error rather than the nil UUID, which
+/// would quietly stand in for a different row.
+fn uuid_column(row: &Row, index: usize) -> Result<Uuid> {
+    parse_uuid(index, &row.get::<_, String>(index)?)
+}
+
+/// A nullable UUID column, like a folder's parent.
+fn optional_uuid_column(row: &Row, index: usize) -> Result<Option<Uuid>> {
+    row.get::<_, Option<String>>(index)?
+        .map(|value| parse_uuid(index, &value))
+        .transpose()
+}
+
+fn parse_uuid(index: usize, value: &str) -> Result<Uuid> {
+    Uuid::parse_str(value)
+        .map_err(|e| Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+/// Collects the rows, leaving out notes with a malformed id so that one bad
+/// row doesn't hide every other note.
+fn skip_malformed<T>(rows: impl Iterator<Item = Result<T>>) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    for row in rows {
+        match row {
+            Ok(item) => items.push(item),
+            Err(e @ Error::FromSqlConversionFailure(..)) => {
+                eprintln!("Skipping note with a malformed id: {}", e)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(items)
+}
+
+fn split_tags(tags: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    tags.sort();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn repository_with(notes: &[(&str, &str)]) -> NoteRepository {
+        let repo = NoteRepository::new(db::get_memory_db().unwrap());
+        for (title, content) in notes {
+            repo.create(&Note::new(title.to_string(), content.to_string()))
+                .unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn fts_query_quotes_every_term() {
+        assert_eq!(fts_query("foo ba*"), Some("\"foo\"* \"ba\"*".into()));
+        assert_eq!(
+            fts_query("a \"exact  phrase\" b"),
+            Some("\"a\"* \"exact  phrase\" \"b\"*".into())
+        );
+        // An unclosed quote still starts a phrase
+        assert_eq!(fts_query("a \"open"), Some("\"a\"* \"open\"".into()));
+        assert_eq!(fts_query("  \"\" * "), None);
+    }
+
+    #[test]
+    fn fts_operators_are_searched_as_words() {
+        let repo = repository_with(&[("Plans", "this AND that, NOT the other (maybe)")]);
+        for query in [
+            "AND",
+            "NOT that",
+            "(maybe",
+            "title:plans",
+            "-other",
+            "^this",
+            "a OR",
+        ] {
+            let hits = repo.search(query, 10);
+            assert!(hits.is_ok(), "{:?}: {:?}", query, hits);
+        }
+        assert_eq!(repo.search("NOT the", 10).unwrap().len(), 1);
+        assert_eq!(repo.search("\"that, not\"", 10).unwrap().len(), 1);
+        assert!(repo.search("\"that the\"", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_snippet_marks_matches() {
+        let (text, matches) = split_snippet("a \u{2}bé\u{3} c \u{2}d\u{3}");
+        assert_eq!(text, "a bé c d");
+        assert_eq!(matches, [(2, 5), (8, 9)]);
+    }
+
+    #[test]
+    fn split_snippet_with_unbalanced_markers() {
+        // A match cut off by the snippet's end, or its start
+        assert_eq!(split_snippet("a \u{2}b"), ("a b".to_string(), vec![]));
+        assert_eq!(split_snippet("a\u{3} b"), ("a b".to_string(), vec![]));
+        // A second opening marker starts the match over
+        assert_eq!(
+            split_snippet("\u{2}a \u{2}b\u{3}"),
+            ("a b".to_string(), vec![(2, 3)])
+        );
+    }
+
+    #[test]
+    fn notes_with_malformed_ids_are_skipped() {
+        let repo = repository_with(&[("Good", "shared word")]);
+        repo.conn
+            .execute(
+                "INSERT INTO notes (id, title, content, created_at, updated_at)
+                 VALUES ('not-a-uuid', 'Bad', 'shared word',
+                     '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00')",
+                [],
+            )
+            .unwrap();
+
+        let notes = repo.get_all().unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].title, "Good");
+        assert_eq!(repo.search("shared", 10).unwrap().len(), 1);
+    }
 }
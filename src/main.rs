@@ -3,13 +3,27 @@ mod app;
 mod components;
 mod core;
 mod db;
+mod markdown;
 mod models;
 mod utils;
 
 use app::App;
+use std::io;
+use std::process;
 
 fn main() {
     println!("🍉 Watermelon is starting...");
+    let db_path = match utils::paths::resolve_db_path(std::env::args().skip(1)) {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+            eprintln!("{}", e);
+            eprintln!("Usage: watermelon [--db <path>]");
+            process::exit(2);
+        }
+        Err(e) => panic!("Failed to prepare data directory: {}", e),
+    };
+    println!("Using database at {}", db_path.display());
+
     let app = relm4::RelmApp::new("com.aashiqumar.watermelon");
 
     // Load CSS
@@ -25,5 +39,5 @@ fn main() {
         }
     }
 
-    app.run::<App>(());
+    app.run::<App>(db_path);
 }
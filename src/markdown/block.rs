@@ -0,0 +1,374 @@
+// Block structure: one pass over the lines of the text
+
+use super::inline::parse_inlines;
+use super::table::{delimiter_row, split_row};
+use super::{Span, SpanKind};
+use std::ops::Range;
+
+/// A line without its line ending, as a byte range into the text.
+#[derive(Clone, Copy)]
+struct Line {
+    start: usize,
+    end: usize,
+}
+
+fn lines(text: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for raw in text.split_inclusive('\n') {
+        let content = raw.trim_end_matches(['\n', '\r']);
+        lines.push(Line {
+            start,
+            end: start + content.len(),
+        });
+        start += raw.len();
+    }
+    lines
+}
+
+pub(super) fn parse_blocks(text: &str) -> Vec<Span> {
+    let lines = lines(text);
+    let mut blocks = Vec::new();
+    let mut paragraph: Option<Range<usize>> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let s = &text[line.start..line.end];
+        let (indent, rest) = block_indent(s);
+
+        if s.trim().is_empty() {
+            flush_paragraph(text, &mut paragraph, &mut blocks);
+            i += 1;
+            continue;
+        }
+
+        if let Some((ch, len)) = rest.and_then(opening_fence) {
+            flush_paragraph(text, &mut paragraph, &mut blocks);
+            let (span, next) = code_block(text, &lines, i, indent, ch, len);
+            blocks.push(span);
+            i = next;
+            continue;
+        }
+
+        let block = rest.and_then(|rest| {
+            let start = line.start + indent;
+            heading(text, start, line.end, rest)
+                .or_else(|| {
+                    thematic_break(rest)
+                        .then(|| Span::new(SpanKind::ThematicBreak, line.start..line.end))
+                })
+                .or_else(|| {
+                    rest.starts_with('>')
+                        .then(|| block_quote(text, start, line.end, rest))
+                })
+        });
+        if let Some(span) = block {
+            flush_paragraph(text, &mut paragraph, &mut blocks);
+            blocks.push(span);
+            i += 1;
+            continue;
+        }
+
+        if let Some(span) = list_item(text, line) {
+            flush_paragraph(text, &mut paragraph, &mut blocks);
+            blocks.push(span);
+            i += 1;
+            continue;
+        }
+
+        if let Some((span, next)) = table(text, &lines, i) {
+            flush_paragraph(text, &mut paragraph, &mut blocks);
+            blocks.push(span);
+            i = next;
+            continue;
+        }
+
+        match &mut paragraph {
+            Some(range) => range.end = line.end,
+            None => paragraph = Some(line.start..line.end),
+        }
+        i += 1;
+    }
+    flush_paragraph(text, &mut paragraph, &mut blocks);
+    blocks
+}
+
+fn flush_paragraph(text: &str, paragraph: &mut Option<Range<usize>>, blocks: &mut Vec<Span>) {
+    if let Some(range) = paragraph.take() {
+        let mut span = Span::new(SpanKind::Paragraph, range.clone());
+        span.children = parse_inlines(text, range);
+        blocks.push(span);
+    }
+}
+
+/// Splits off up to three spaces of indentation. Lines indented further
+/// can't start a heading, fence, rule or quote.
+fn block_indent(line: &str) -> (usize, Option<&str>) {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        (indent, None)
+    } else {
+        (indent, Some(&line[indent..]))
+    }
+}
+
+/// `(fence char, fence length)` if `rest` opens a code fence.
+fn opening_fence(rest: &str) -> Option<(u8, usize)> {
+    let ch = *rest.as_bytes().first()?;
+    if ch != b'`' && ch != b'~' {
+        return None;
+    }
+    let len = rest.bytes().take_while(|&b| b == ch).count();
+    // Backtick fences can't have backticks in the info string
+    if len < 3 || (ch == b'`' && rest[len..].contains('`')) {
+        return None;
+    }
+    Some((ch, len))
+}
+
+fn closes_fence(line: &str, ch: u8, len: usize) -> bool {
+    let (_, Some(rest)) = block_indent(line) else {
+        return false;
+    };
+    let run = rest.bytes().take_while(|&b| b == ch).count();
+    run >= len && rest[run..].trim().is_empty()
+}
+
+/// A fenced code block starting at `lines[first]`. Without a closing fence
+/// it runs to the end of the text. Returns the span and the next line index.
+fn code_block(
+    text: &str,
+    lines: &[Line],
+    first: usize,
+    indent: usize,
+    ch: u8,
+    len: usize,
+) -> (Span, usize) {
+    let open = lines[first];
+    let info = text[open.start + indent + len..open.end]
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .to_string();
+
+    let mut children = vec![Span::new(SpanKind::Marker, open.start + indent..open.end)];
+    // An unclosed block takes everything, including a final line break
+    let mut end = text.len();
+    let mut next = lines.len();
+    for (j, line) in lines.iter().enumerate().skip(first + 1) {
+        if closes_fence(&text[line.start..line.end], ch, len) {
+            children.push(Span::new(SpanKind::Marker, line.start..line.end));
+            end = line.end;
+            next = j + 1;
+            break;
+        }
+    }
+
+    let mut span = Span::new(SpanKind::CodeBlock { info }, open.start..end);
+    span.children = children;
+    (span, next)
+}
+
+/// An ATX heading: 1-6 `#` followed by a space or the end of the line,
+/// with an optional closing run of `#`.
+fn heading(text: &str, start: usize, end: usize, rest: &str) -> Option<Span> {
+    let level = heading_level(rest)?;
+    let after = &rest[level..];
+
+    let content_start = start + level + (after.len() - after.trim_start().len());
+    let mut content_end = (start + rest.trim_end().len()).max(content_start);
+    // A closing sequence must be preceded by whitespace (or be all there is)
+    let body = &text[content_start..content_end];
+    let without_closing = body.trim_end_matches('#');
+    if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        content_end = content_start + without_closing.trim_end().len();
+    }
+
+    let mut span = Span::new(SpanKind::Heading(level as u8), start..end);
+    span.children
+        .push(Span::new(SpanKind::Marker, start..content_start));
+    span.children
+        .extend(parse_inlines(text, content_start..content_end));
+    if content_end < end {
+        span.children
+            .push(Span::new(SpanKind::Marker, content_end..end));
+    }
+    Some(span)
+}
+
+fn heading_level(rest: &str) -> Option<usize> {
+    let level = rest.bytes().take_while(|&b| b == b'#').count();
+    let after = &rest[level..];
+    ((1..=6).contains(&level) && (after.is_empty() || after.starts_with([' ', '\t'])))
+        .then_some(level)
+}
+
+/// Three or more `-`, `*` or `_` (the same one), optionally spaced out.
+fn thematic_break(rest: &str) -> bool {
+    let mut chars = rest.chars().filter(|c| !matches!(c, ' ' | '\t'));
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    matches!(first, '-' | '*' | '_') && chars.clone().all(|c| c == first) && chars.count() >= 2
+}
+
+/// A `>` line; its content is parsed as inline text. Each quoted line is
+/// its own span, so a quote's lines never depend on each other.
+fn block_quote(text: &str, start: usize, end: usize, rest: &str) -> Span {
+    // Nested quotes (`> >`) share one marker
+    let marker_len = rest.len() - rest.trim_start_matches(['>', ' ', '\t']).len();
+    let content_start = start + marker_len;
+
+    let mut span = Span::new(SpanKind::BlockQuote, start..end);
+    span.children
+        .push(Span::new(SpanKind::Marker, start..content_start));
+    span.children
+        .extend(parse_inlines(text, content_start..end));
+    span
+}
+
+/// Length of a list marker (`-`, `*`, `+`, `1.` or `1)`) at the start of
+/// `rest`, including the space after it.
+pub(super) fn list_marker_len(rest: &str) -> Option<usize> {
+    let bytes = rest.as_bytes();
+    let marker = match bytes.first()? {
+        b'-' | b'*' | b'+' => 1,
+        b'0'..=b'9' => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits > 9 || !matches!(bytes.get(digits), Some(b'.' | b')')) {
+                return None;
+            }
+            digits + 1
+        }
+        _ => return None,
+    };
+    match bytes.get(marker) {
+        None => Some(marker),
+        Some(b' ' | b'\t') => Some(marker + 1),
+        _ => None,
+    }
+}
+
+/// A single list item line. Nested items are recognised at any indent.
+fn list_item(text: &str, line: Line) -> Option<Span> {
+    let s = &text[line.start..line.end];
+    let indent = s.len() - s.trim_start_matches([' ', '\t']).len();
+    let marker_start = line.start + indent;
+    let marker_end = marker_start + list_marker_len(&s[indent..])?;
+
+    let mut span = Span::new(SpanKind::ListItem, line.start..line.end);
+    span.children
+        .push(Span::new(SpanKind::Marker, marker_start..marker_end));
+
+    let mut content_start = marker_end;
+    if let Some(checked) = task_marker(&text[marker_end..line.end]) {
+        span.children.push(Span::new(
+            SpanKind::TaskMarker { checked },
+            marker_end..marker_end + 3,
+        ));
+        content_start = marker_end + 3;
+    }
+
+    span.children
+        .extend(parse_inlines(text, content_start..line.end));
+    Some(span)
+}
+
+/// A pipe table: a header row, a delimiter row with as many cells, then
+/// body rows up to a blank line or the start of another block. Returns the
+/// span and the next line index.
+fn table(text: &str, lines: &[Line], first: usize) -> Option<(Span, usize)> {
+    let header = lines[first];
+    let delimiter = *lines.get(first + 1)?;
+    let header_text = &text[header.start..header.end];
+    let delimiter_text = &text[delimiter.start..delimiter.end];
+    // The delimiter row must not be a block of its own (`- | -` is a list)
+    if !header_text.contains('|') || starts_block(delimiter_text) {
+        return None;
+    }
+    let columns = delimiter_row(delimiter_text)?.len();
+    if split_row(header_text).cells.len() != columns {
+        return None;
+    }
+
+    let mut next = first + 2;
+    while next < lines.len() && !starts_block(&text[lines[next].start..lines[next].end]) {
+        next += 1;
+    }
+    let last = lines[next - 1];
+
+    let mut span = Span::new(SpanKind::Table, header.start..last.end);
+    for (j, line) in lines[first..next].iter().enumerate() {
+        let kind = match j {
+            0 => SpanKind::TableHeader,
+            1 => SpanKind::TableDelimiter,
+            _ => SpanKind::TableRow,
+        };
+        span.children.push(table_row(text, *line, kind));
+    }
+    Some((span, next))
+}
+
+fn table_row(text: &str, line: Line, kind: SpanKind) -> Span {
+    let s = &text[line.start..line.end];
+    let row = split_row(s);
+    let mut span = Span::new(kind.clone(), line.start..line.end);
+
+    let mut children: Vec<Span> = row
+        .pipes
+        .iter()
+        .map(|&p| Span::new(SpanKind::Marker, line.start + p..line.start + p + 1))
+        .collect();
+    if kind != SpanKind::TableDelimiter {
+        for cell in row.cells {
+            let raw = &s[cell.clone()];
+            let start = line.start + cell.start + (raw.len() - raw.trim_start().len());
+            let end = line.start + cell.start + raw.trim_end().len();
+            if start < end {
+                let mut cell_span = Span::new(SpanKind::TableCell, start..end);
+                cell_span.children = parse_inlines(text, start..end);
+                children.push(cell_span);
+            }
+        }
+    }
+    children.sort_by_key(|c| c.range.start);
+    span.children = children;
+    span
+}
+
+/// Whether `after` (what follows a list marker) starts with a task marker,
+/// and whether it's checked.
+pub(super) fn task_marker(after: &str) -> Option<bool> {
+    let checked = match after.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    (after.len() == 3 || after[3..].starts_with([' ', '\t'])).then_some(checked)
+}
+
+/// Whether `line` is parsed the same no matter what comes before it (outside
+/// code fences): blank lines and lines that open their own block. Any other
+/// line may continue a paragraph from the lines above.
+pub(super) fn starts_block(line: &str) -> bool {
+    if line.trim().is_empty() || list_marker_len(line.trim_start_matches([' ', '\t'])).is_some() {
+        return true;
+    }
+    let (_, Some(rest)) = block_indent(line) else {
+        return false;
+    };
+    opening_fence(rest).is_some()
+        || heading_level(rest).is_some()
+        || thematic_break(rest)
+        || rest.starts_with('>')
+}
+
+/// Whether `line` could open or close a code fence.
+pub(super) fn is_fence(line: &str) -> bool {
+    let (_, Some(rest)) = block_indent(line) else {
+        return false;
+    };
+    let ch = rest.bytes().next();
+    matches!(ch, Some(b'`' | b'~')) && rest.bytes().take_while(|&b| Some(b) == ch).count() >= 3
+}
@@ -0,0 +1,322 @@
+// Inline formatting: toggling `**bold**`, `_italic_`, `~~strikethrough~~`,
+// `==highlight==`, `` `code` `` and `++underline++` around a selection
+//
+// Everything here works on the parsed blocks around the selection; the
+// editor picks the blocks and applies the edits to its buffer.
+
+use super::{parse, Span, SpanKind};
+use std::ops::Range;
+
+/// The inline formats the toolbar can toggle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bold,
+    Italic,
+    Strikethrough,
+    Highlight,
+    Code,
+    Underline,
+}
+
+impl Format {
+    pub const ALL: [Format; 6] = [
+        Format::Bold,
+        Format::Italic,
+        Format::Strikethrough,
+        Format::Highlight,
+        Format::Code,
+        Format::Underline,
+    ];
+
+    fn kind(self) -> SpanKind {
+        match self {
+            Format::Bold => SpanKind::Strong,
+            Format::Italic => SpanKind::Emphasis,
+            Format::Strikethrough => SpanKind::Strikethrough,
+            Format::Highlight => SpanKind::Highlight,
+            Format::Code => SpanKind::Code,
+            Format::Underline => SpanKind::Underline,
+        }
+    }
+
+    /// The marker written on both sides of `range` of `text` to format it.
+    fn marker(self, text: &str, range: Range<usize>) -> &'static str {
+        match self {
+            Format::Bold => "**",
+            // `_` doesn't work inside a word, `*` does
+            Format::Italic => {
+                let before = text[..range.start].chars().next_back();
+                let after = text[range.end..].chars().next();
+                if before.is_some_and(char::is_alphanumeric)
+                    || after.is_some_and(char::is_alphanumeric)
+                {
+                    "*"
+                } else {
+                    "_"
+                }
+            }
+            Format::Strikethrough => "~~",
+            Format::Highlight => "==",
+            // A longer fence lets the code hold single backticks
+            Format::Code if text[range].contains('`') => "``",
+            Format::Code => "`",
+            Format::Underline => "++",
+        }
+    }
+}
+
+/// What a formatting command changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toggle {
+    /// Byte ranges of the text with their replacements, in order
+    pub edits: Vec<(Range<usize>, String)>,
+    /// The selection afterwards, in bytes of the edited text
+    pub selection: Range<usize>,
+}
+
+impl Toggle {
+    /// `text` with the edits made.
+    pub fn apply(&self, text: &str) -> String {
+        let mut edited = String::with_capacity(text.len());
+        let mut last = 0;
+        for (range, with) in &self.edits {
+            edited.push_str(&text[last..range.start]);
+            edited.push_str(with);
+            last = range.end;
+        }
+        edited.push_str(&text[last..]);
+        edited
+    }
+}
+
+/// A span of some format with its markers.
+struct Formatted {
+    range: Range<usize>,
+    open: Range<usize>,
+    close: Range<usize>,
+}
+
+fn formatted(blocks: &[Span], format: Format) -> Vec<Formatted> {
+    let kind = format.kind();
+    let mut found = Vec::new();
+    for block in blocks {
+        block.walk(&mut |span, _| {
+            if span.kind != kind {
+                return;
+            }
+            let mut markers = span.children.iter().filter(|c| c.kind == SpanKind::Marker);
+            if let (Some(open), Some(close)) = (markers.next(), markers.next_back()) {
+                found.push(Formatted {
+                    range: span.range.clone(),
+                    open: open.range.clone(),
+                    close: close.range.clone(),
+                });
+            }
+        });
+    }
+    found
+}
+
+/// Whether `selection` is formatted by `span`: it lies within the span,
+/// and a cursor isn't just outside it.
+fn covers(span: &Formatted, selection: &Range<usize>) -> bool {
+    if selection.is_empty() {
+        span.range.start < selection.start && selection.start < span.range.end
+    } else {
+        span.range.start <= selection.start && selection.end <= span.range.end
+    }
+}
+
+/// The formats of `selection`, in bytes of the text `blocks` were parsed
+/// from. A command for one of these would remove it.
+pub fn active(blocks: &[Span], selection: Range<usize>) -> Vec<Format> {
+    Format::ALL
+        .into_iter()
+        .filter(|&format| {
+            formatted(blocks, format)
+                .iter()
+                .any(|span| covers(span, &selection))
+        })
+        .collect()
+}
+
+/// Turns `format` off for `selection` of `text` if it's on, by removing the
+/// markers of the span it's in, or on otherwise. Turning it on wraps the
+/// selection, less any whitespace at its ends, and drops the markers of
+/// spans of the format inside it, so nothing gets formatted twice. Markers
+/// between a word and punctuation wouldn't be read as formatting, so then
+/// the whole words are wrapped, and if even that wouldn't format them
+/// nothing changes. With no selection it adds an empty pair of markers for
+/// the cursor to type into, or removes the empty pair the cursor is in.
+pub fn toggle(text: &str, blocks: &[Span], selection: Range<usize>, format: Format) -> Toggle {
+    let spans = formatted(blocks, format);
+
+    // The innermost span around the selection
+    if let Some(span) = spans.iter().rev().find(|span| covers(span, &selection)) {
+        let removed = [span.open.clone(), span.close.clone()];
+        return Toggle {
+            selection: without(&removed, selection.start)..without(&removed, selection.end),
+            edits: removed.into_iter().map(|r| (r, String::new())).collect(),
+        };
+    }
+
+    let selected = &text[selection.clone()];
+    let start = selection.start + (selected.len() - selected.trim_start().len());
+    let end = (selection.end - (selected.len() - selected.trim_end().len())).max(start);
+    let marker = format.marker(text, start..end);
+
+    if start == end && text[..start].ends_with(marker) && text[start..].starts_with(marker) {
+        let pair = start - marker.len()..start + marker.len();
+        return Toggle {
+            selection: pair.start..pair.start,
+            edits: vec![(pair, String::new())],
+        };
+    }
+
+    let toggle = wrap(text, &spans, start..end, format);
+    // An empty pair is only formatting once something is typed into it
+    if start == end || parses_as(text, &toggle, format) {
+        return toggle;
+    }
+    let words = word_start(text, start)..word_end(text, end);
+    let toggle = wrap(text, &spans, words, format);
+    if parses_as(text, &toggle, format) {
+        return toggle;
+    }
+    Toggle {
+        edits: Vec::new(),
+        selection,
+    }
+}
+
+/// Puts the markers of `format` around `range`, dropping those of the
+/// spans of it inside.
+fn wrap(text: &str, spans: &[Formatted], range: Range<usize>, format: Format) -> Toggle {
+    let Range { start, end } = range;
+    let marker = format.marker(text, start..end);
+    let mut edits = vec![(start..start, marker.to_string())];
+    let mut removed = 0;
+    for span in spans
+        .iter()
+        .filter(|span| start <= span.range.start && span.range.end <= end)
+    {
+        for r in [&span.open, &span.close] {
+            removed += r.len();
+            edits.push((r.clone(), String::new()));
+        }
+    }
+    edits.push((end..end, marker.to_string()));
+    // Insertions go before removals starting at the same place
+    edits.sort_by_key(|(range, with)| (range.start, with.is_empty()));
+
+    let content = start + marker.len();
+    Toggle {
+        edits,
+        selection: content..content + (end - start) - removed,
+    }
+}
+
+/// Whether the text `toggle` wraps reads back as formatted by it.
+fn parses_as(text: &str, toggle: &Toggle, format: Format) -> bool {
+    let edited = toggle.apply(text);
+    formatted(&parse(&edited), format).iter().any(|span| {
+        span.open.end == toggle.selection.start && span.close.start == toggle.selection.end
+    })
+}
+
+/// The start of the run of non-whitespace `pos` is in.
+fn word_start(text: &str, pos: usize) -> usize {
+    text[..pos]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+/// The end of the run of non-whitespace `pos` is in.
+fn word_end(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .find(char::is_whitespace)
+        .map_or(text.len(), |i| pos + i)
+}
+
+/// Where `pos` ends up once the `removed` ranges are gone.
+fn without(removed: &[Range<usize>], pos: usize) -> usize {
+    removed
+        .iter()
+        .map(|r| pos.min(r.end).saturating_sub(r.start))
+        .fold(pos, |pos, len| pos - len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `text` after toggling `format` for `selection`, with the selection
+    /// afterwards.
+    fn toggled(text: &str, selection: Range<usize>, format: Format) -> (String, Range<usize>) {
+        let toggle = toggle(text, &parse(text), selection, format);
+        (toggle.apply(text), toggle.selection)
+    }
+
+    /// Toggles twice, checking the first turns `format` on for the result.
+    fn round_trip(text: &str, selection: Range<usize>, format: Format, on: &str) {
+        let (edited, selection) = toggled(text, selection, format);
+        assert_eq!(edited, on);
+        assert!(active(&parse(&edited), selection.clone()).contains(&format));
+        let (back, _) = toggled(&edited, selection, format);
+        assert_eq!(back, text);
+    }
+
+    #[test]
+    fn wraps_and_unwraps() {
+        round_trip("a b c", 2..3, Format::Bold, "a **b** c");
+        round_trip("a b c", 1..4, Format::Highlight, "a ==b== c");
+        round_trip("hello", 0..5, Format::Italic, "_hello_");
+        round_trip("hello", 1..3, Format::Italic, "h*el*lo");
+        round_trip("run it", 4..6, Format::Code, "run `it`");
+        round_trip("a ` b", 0..5, Format::Code, "``a ` b``");
+        round_trip("gone", 0..4, Format::Strikethrough, "~~gone~~");
+        round_trip("see", 0..3, Format::Underline, "++see++");
+    }
+
+    #[test]
+    fn cursor_inside_span_unwraps() {
+        let (edited, selection) = toggled("a **bold** c", 5..5, Format::Bold);
+        assert_eq!(edited, "a bold c");
+        assert_eq!(selection, 3..3);
+    }
+
+    #[test]
+    fn empty_pair_at_cursor() {
+        let (edited, selection) = toggled("a  c", 2..2, Format::Bold);
+        assert_eq!(edited, "a **** c");
+        assert_eq!(selection, 4..4);
+        let (back, selection) = toggled(&edited, selection, Format::Bold);
+        assert_eq!(back, "a  c");
+        assert_eq!(selection, 2..2);
+    }
+
+    #[test]
+    fn merges_spans_inside_the_selection() {
+        let (edited, _) = toggled("**a** b **c**", 0..13, Format::Bold);
+        assert_eq!(edited, "**a b c**");
+    }
+
+    #[test]
+    fn punctuation_next_to_a_word_takes_the_whole_word() {
+        // `x**,** x,` wouldn't be bold
+        round_trip("x, x,", 1..3, Format::Bold, "**x,** x,");
+        // Nor would `*don'*t` be italic
+        round_trip("don't", 0..4, Format::Italic, "_don't_");
+    }
+
+    #[test]
+    fn refuses_what_would_not_format() {
+        // Half of a code span can't be bold
+        let text = "a `b c` d";
+        let toggle = toggle(text, &parse(text), 3..4, Format::Bold);
+        assert!(toggle.edits.is_empty());
+        assert_eq!(toggle.selection, 3..4);
+    }
+}
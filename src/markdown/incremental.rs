@@ -0,0 +1,232 @@
+// Re-parsing only the blocks an edit touched
+//
+// The editor records edits as a `DirtyRange` and, when it re-highlights,
+// asks `reparse` which whole lines need restyling. Everything outside that
+// range keeps its tags: the range grows over the paragraph around the edit,
+// back to the opening fence when it starts inside code, and forward until
+// the fence structure after it agrees with what was highlighted before.
+
+use super::block::{is_fence, starts_block};
+use super::{parse, Span, SpanKind};
+use std::ops::Range;
+
+/// The part of a text edited since it was last highlighted, in chars.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirtyRange(Option<(usize, usize)>);
+
+impl DirtyRange {
+    /// Records `len` chars inserted at `pos`.
+    pub fn inserted(self, pos: usize, len: usize) -> Self {
+        let shift = |x: usize| if x > pos { x + len } else { x };
+        self.union(self.0.map(|(a, b)| (shift(a), shift(b))), pos, pos + len)
+    }
+
+    /// Records the deletion of the chars in `start..end`.
+    pub fn deleted(self, start: usize, end: usize) -> Self {
+        let shift = |x: usize| {
+            if x >= end {
+                x - (end - start)
+            } else {
+                x.min(start)
+            }
+        };
+        self.union(self.0.map(|(a, b)| (shift(a), shift(b))), start, start)
+    }
+
+    fn union(self, shifted: Option<(usize, usize)>, start: usize, end: usize) -> Self {
+        Self(Some(match shifted {
+            Some((a, b)) => (a.min(start), b.max(end)),
+            None => (start, end),
+        }))
+    }
+
+    /// The dirty chars, if anything was edited.
+    pub fn range(self) -> Option<Range<usize>> {
+        self.0.map(|(start, end)| start..end)
+    }
+}
+
+/// The text being highlighted, line by line, with what the last
+/// highlighting pass made of it.
+pub trait LineSource {
+    fn line_count(&self) -> usize;
+
+    /// Line `n` without its line ending.
+    fn line(&self, n: usize) -> String;
+
+    /// The text of `lines`, including the line ending after the last one.
+    fn text(&self, lines: Range<usize>) -> String;
+
+    /// Whether line `n` was inside a fenced code block (after its opening
+    /// fence) when the text was last highlighted.
+    fn in_code(&self, n: usize) -> bool;
+}
+
+#[derive(Debug)]
+pub struct Reparse {
+    /// Lines to restyle; everything else keeps its styling.
+    pub lines: Range<usize>,
+    /// The text of those lines. Span ranges are relative to it.
+    pub text: String,
+    pub blocks: Vec<Span>,
+}
+
+/// Parses the blocks around the lines in `dirty`.
+pub fn reparse(source: &impl LineSource, dirty: Range<usize>) -> Reparse {
+    let count = source.line_count().max(1);
+    let mut start = dirty.start.min(count - 1);
+    let mut end = dirty.end.clamp(start + 1, count);
+
+    // Start where parsing can't depend on earlier lines: outside code, after
+    // a line that ends any paragraph. The edited line itself may have been a
+    // paragraph line, so its old paragraph is restyled with it.
+    while start > 0 && (source.in_code(start) || !starts_block(&source.line(start - 1))) {
+        start -= 1;
+    }
+    // Likewise, lines after the edit may have belonged to its paragraph
+    while end < count && !starts_block(&source.line(end)) {
+        end += 1;
+    }
+
+    loop {
+        let text = source.text(start..end);
+        let blocks = parse(&text);
+
+        if end < count {
+            if ends_in_open_fence(&blocks) {
+                // Take in lines up to one that might close the fence
+                end += 1;
+                while end < count && !is_fence(&source.line(end - 1)) {
+                    end += 1;
+                }
+                continue;
+            }
+            if source.in_code(end) {
+                // The code block that used to continue here has changed
+                while end < count && source.in_code(end) {
+                    end += 1;
+                }
+                continue;
+            }
+        }
+
+        return Reparse {
+            lines: start..end,
+            text,
+            blocks,
+        };
+    }
+}
+
+fn ends_in_open_fence(blocks: &[Span]) -> bool {
+    blocks.last().is_some_and(|block| {
+        matches!(block.kind, SpanKind::CodeBlock { .. })
+            && block
+                .children
+                .iter()
+                .filter(|c| c.kind == SpanKind::Marker)
+                .count()
+                < 2
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_edited() {
+        assert_eq!(DirtyRange::default().range(), None);
+    }
+
+    #[test]
+    fn inserts_merge() {
+        let dirty = DirtyRange::default().inserted(5, 1).inserted(6, 1);
+        assert_eq!(dirty.range(), Some(5..7));
+        // An insert before the range shifts it and takes in the gap
+        assert_eq!(dirty.inserted(2, 3).range(), Some(2..10));
+        // One after it leaves the start alone
+        assert_eq!(dirty.inserted(9, 2).range(), Some(5..11));
+    }
+
+    #[test]
+    fn deletes_merge() {
+        let dirty = DirtyRange::default().inserted(5, 4);
+        // Inside the range, it shrinks
+        assert_eq!(dirty.deleted(6, 8).range(), Some(5..7));
+        // Over all of it and more, only the spot of the delete is left
+        assert_eq!(dirty.deleted(3, 12).range(), Some(3..3));
+        // Before it, it moves back and takes in the spot of the delete
+        assert_eq!(dirty.deleted(0, 2).range(), Some(0..7));
+        // After it, it grows to the spot
+        assert_eq!(dirty.deleted(12, 15).range(), Some(5..12));
+        assert_eq!(DirtyRange::default().deleted(4, 6).range(), Some(4..4));
+    }
+
+    /// Lines with whether each was inside code when last highlighted.
+    struct Lines(Vec<(&'static str, bool)>);
+
+    impl LineSource for Lines {
+        fn line_count(&self) -> usize {
+            self.0.len()
+        }
+
+        fn line(&self, n: usize) -> String {
+            self.0[n].0.to_string()
+        }
+
+        fn text(&self, lines: Range<usize>) -> String {
+            let end = lines.end;
+            let mut text: Vec<&str> = self.0[lines].iter().map(|(line, _)| *line).collect();
+            if end < self.0.len() {
+                text.push("");
+            }
+            text.join("\n")
+        }
+
+        fn in_code(&self, n: usize) -> bool {
+            self.0[n].1
+        }
+    }
+
+    #[test]
+    fn reparse_takes_in_the_paragraph() {
+        let source = Lines(vec![
+            ("# Title", false),
+            ("one", false),
+            ("two", false),
+            ("three", false),
+            ("", false),
+            ("after", false),
+        ]);
+        assert_eq!(reparse(&source, 2..3).lines, 1..4);
+        assert_eq!(reparse(&source, 5..6).lines, 5..6);
+    }
+
+    #[test]
+    fn reparse_goes_back_to_the_opening_fence() {
+        let source = Lines(vec![
+            ("# Title", false),
+            ("```", false),
+            ("code", true),
+            ("more", true),
+            ("```", true),
+            ("after", false),
+        ]);
+        assert_eq!(reparse(&source, 3..4).lines, 1..5);
+    }
+
+    #[test]
+    fn reparse_follows_a_new_fence_to_its_end() {
+        // The first line was just made a fence; the rest were plain text
+        let source = Lines(vec![
+            ("```", false),
+            ("a", false),
+            ("", false),
+            ("b", false),
+            ("```", false),
+            ("c", false),
+        ]);
+        assert_eq!(reparse(&source, 0..1).lines, 0..5);
+    }
+}
@@ -0,0 +1,431 @@
+// Inline spans: code spans, escapes, links, emphasis and the extensions
+
+use super::{nest, Span, SpanKind};
+use crate::utils::tags;
+use std::ops::Range;
+
+/// A run of `*`, `_`, `~`, `=` or `+` that may open or close a span.
+struct Delimiter {
+    ch: u8,
+    /// The unused part of the run. Openers give up characters from the end,
+    /// closers from the start, so markers always hug the content.
+    range: Range<usize>,
+    run_len: usize,
+    can_open: bool,
+    can_close: bool,
+}
+
+/// A `[` or `![` waiting for its `]`.
+struct Bracket {
+    start: usize,
+    image: bool,
+    /// Index of the first delimiter pushed after the bracket
+    delimiters: usize,
+    // Links can't contain links, so brackets before a link are deactivated
+    active: bool,
+}
+
+/// Parses the inline content of `range` in `text` into nested spans.
+pub(super) fn parse_inlines(text: &str, range: Range<usize>) -> Vec<Span> {
+    let bytes = text.as_bytes();
+    let end = range.end;
+    let mut spans = Vec::new();
+    let mut delimiters: Vec<Delimiter> = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+    let mut i = range.start;
+
+    while i < end {
+        match bytes[i] {
+            b'\\' if i + 1 < end && bytes[i + 1].is_ascii_punctuation() => {
+                spans.push(Span::new(SpanKind::Escape, i..i + 2));
+                spans.push(Span::new(SpanKind::Marker, i..i + 1));
+                i += 2;
+            }
+            b'`' => {
+                let run = run_len(bytes, i, end);
+                match code_span_close(bytes, i + run, end, run) {
+                    Some(close) => {
+                        push_marked(&mut spans, SpanKind::Code, i, close + run, run, run);
+                        i = close + run;
+                    }
+                    // An unmatched run is literal text
+                    None => i += run,
+                }
+            }
+            b'<' => match autolink_end(bytes, i, end) {
+                Some(close) => {
+                    push_marked(&mut spans, SpanKind::Autolink, i, close, 1, 1);
+                    i = close;
+                }
+                None => i += 1,
+            },
+            b'!' if i + 1 < end && bytes[i + 1] == b'[' => {
+                brackets.push(Bracket {
+                    start: i,
+                    image: true,
+                    delimiters: delimiters.len(),
+                    active: true,
+                });
+                i += 2;
+            }
+            b'[' => {
+                if let Some(close) = wiki_link_end(bytes, i, end) {
+                    push_marked(&mut spans, SpanKind::WikiLink, i, close, 2, 2);
+                    i = close;
+                } else {
+                    brackets.push(Bracket {
+                        start: i,
+                        image: false,
+                        delimiters: delimiters.len(),
+                        active: true,
+                    });
+                    i += 1;
+                }
+            }
+            b']' => {
+                i = close_bracket(text, i, end, &mut brackets, &mut delimiters, &mut spans);
+            }
+            ch @ (b'*' | b'_' | b'~' | b'=' | b'+') => {
+                let run = run_len(bytes, i, end);
+                // GFM strikethrough takes one or two tildes; highlight and
+                // underline exactly two `=` or `+`
+                let usable = match ch {
+                    b'~' => run <= 2,
+                    b'=' | b'+' => run == 2,
+                    _ => true,
+                };
+                if usable {
+                    delimiters.push(delimiter(text, ch, i..i + run));
+                }
+                i += run;
+            }
+            b'h' | b'w' if bare_url_may_start(text, i) => match bare_url_end(text, i, end) {
+                Some(url_end) => {
+                    spans.push(Span::new(SpanKind::BareUrl, i..url_end));
+                    i = url_end;
+                }
+                None => i += 1,
+            },
+            b'#' if text[..i].chars().next_back().is_none_or(tags::starts_word) => {
+                match tags::tag_end(&text[..end], i) {
+                    Some(tag_end) => {
+                        spans.push(Span::new(SpanKind::Tag, i..tag_end));
+                        i = tag_end;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += text[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    process_emphasis(&mut delimiters, 0, &mut spans);
+    nest(spans)
+}
+
+/// Pushes a span of `start..end` with `open` and `close` bytes of markers.
+fn push_marked(
+    spans: &mut Vec<Span>,
+    kind: SpanKind,
+    start: usize,
+    end: usize,
+    open: usize,
+    close: usize,
+) {
+    spans.push(Span::new(kind, start..end));
+    spans.push(Span::new(SpanKind::Marker, start..start + open));
+    spans.push(Span::new(SpanKind::Marker, end - close..end));
+}
+
+fn run_len(bytes: &[u8], start: usize, end: usize) -> usize {
+    bytes[start..end]
+        .iter()
+        .take_while(|&&b| b == bytes[start])
+        .count()
+}
+
+/// Start of the backtick run of exactly `len` that closes a code span.
+fn code_span_close(bytes: &[u8], from: usize, end: usize, len: usize) -> Option<usize> {
+    let mut j = from;
+    while j < end {
+        if bytes[j] == b'`' {
+            let run = run_len(bytes, j, end);
+            if run == len {
+                return Some(j);
+            }
+            j += run;
+        } else {
+            j += 1;
+        }
+    }
+    None
+}
+
+/// End (after `>`) of an autolink like `<https://example.com>` at `start`.
+fn autolink_end(bytes: &[u8], start: usize, end: usize) -> Option<usize> {
+    let scheme_start = start + 1;
+    if !bytes.get(scheme_start)?.is_ascii_alphabetic() {
+        return None;
+    }
+    let mut i = scheme_start;
+    while i < end && (bytes[i].is_ascii_alphanumeric() || matches!(bytes[i], b'+' | b'.' | b'-')) {
+        i += 1;
+    }
+    if !(2..=32).contains(&(i - scheme_start)) || i >= end || bytes[i] != b':' {
+        return None;
+    }
+    for (j, &b) in bytes.iter().enumerate().take(end).skip(i + 1) {
+        match b {
+            b'>' => return Some(j + 1),
+            b'<' | b' ' | b'\t' | b'\n' | b'\r' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// End (after `]]`) of a `[[Note Title]]` wiki link at `start`. The title
+/// can't be blank or contain brackets.
+fn wiki_link_end(bytes: &[u8], start: usize, end: usize) -> Option<usize> {
+    if !bytes[start..end].starts_with(b"[[") {
+        return None;
+    }
+    let title = start + 2;
+    let close = (title..end).find(|&j| matches!(bytes[j], b'[' | b']' | b'\n'))?;
+    let blank = bytes[title..close].iter().all(u8::is_ascii_whitespace);
+    (!blank && bytes[close..end].starts_with(b"]]")).then_some(close + 2)
+}
+
+/// Bare urls start a line or follow whitespace, an emphasis delimiter or `(`.
+fn bare_url_may_start(text: &str, pos: usize) -> bool {
+    text[..pos]
+        .chars()
+        .next_back()
+        .is_none_or(|c| c.is_whitespace() || matches!(c, '*' | '_' | '~' | '('))
+}
+
+/// End of a bare url like `https://example.com` or `www.example.com` at
+/// `start`. Trailing punctuation and unbalanced `)`s are left out, so a url
+/// can end a sentence or sit in parentheses.
+fn bare_url_end(text: &str, start: usize, end: usize) -> Option<usize> {
+    let rest = &text[start..end];
+    let prefix = ["https://", "http://", "www."]
+        .into_iter()
+        .find(|prefix| rest.starts_with(prefix))?;
+    let len = rest
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '[' | ']' | '"' | '`'))
+        .unwrap_or(rest.len());
+
+    let mut url = &rest[..len];
+    loop {
+        if let Some(trimmed) = url.strip_suffix(['?', '!', '.', ',', ':', ';', '*', '_', '~', '\''])
+        {
+            url = trimmed;
+        } else if url.ends_with(')') && url.matches(')').count() > url.matches('(').count() {
+            url = &url[..url.len() - 1];
+        } else {
+            break;
+        }
+    }
+    (url.len() > prefix.len()).then_some(start + url.len())
+}
+
+/// Handles a `]` at `pos`: turns the innermost open bracket into a link or
+/// image when an inline destination follows. Returns where to continue.
+fn close_bracket(
+    text: &str,
+    pos: usize,
+    end: usize,
+    brackets: &mut Vec<Bracket>,
+    delimiters: &mut Vec<Delimiter>,
+    spans: &mut Vec<Span>,
+) -> usize {
+    let Some(bracket) = brackets.pop() else {
+        return pos + 1;
+    };
+    if !bracket.active {
+        return pos + 1;
+    }
+    let Some((url, close)) = link_destination(text.as_bytes(), pos + 1, end) else {
+        return pos + 1;
+    };
+
+    let (kind, open) = if bracket.image {
+        (SpanKind::Image, 2)
+    } else {
+        (SpanKind::Link, 1)
+    };
+    spans.push(Span::new(kind, bracket.start..close));
+    spans.push(Span::new(
+        SpanKind::Marker,
+        bracket.start..bracket.start + open,
+    ));
+    spans.push(Span::new(SpanKind::Marker, pos..url.start));
+    if !url.is_empty() {
+        spans.push(Span::new(SpanKind::LinkUrl, url.clone()));
+    }
+    spans.push(Span::new(SpanKind::Marker, url.end..close));
+
+    // Emphasis inside the link text can't pair with anything outside it
+    process_emphasis(delimiters, bracket.delimiters, spans);
+    delimiters.truncate(bracket.delimiters);
+    if !bracket.image {
+        for earlier in brackets.iter_mut().filter(|b| !b.image) {
+            earlier.active = false;
+        }
+    }
+    close
+}
+
+/// Parses `(url "title")` at `pos`. Returns the url range and the position
+/// after the closing parenthesis.
+fn link_destination(bytes: &[u8], pos: usize, end: usize) -> Option<(Range<usize>, usize)> {
+    if pos >= end || bytes[pos] != b'(' {
+        return None;
+    }
+    let skip_space = |mut i: usize| {
+        while i < end && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+
+    let mut i = skip_space(pos + 1);
+    let url = if i < end && bytes[i] == b'<' {
+        let start = i + 1;
+        let close = (start..end).find(|&j| matches!(bytes[j], b'>' | b'<' | b'\n'))?;
+        if bytes[close] != b'>' {
+            return None;
+        }
+        i = close + 1;
+        start..close
+    } else {
+        let start = i;
+        let mut depth = 0;
+        while i < end {
+            match bytes[i] {
+                b'\\' if i + 1 < end => {
+                    i += 2;
+                    continue;
+                }
+                b'(' => depth += 1,
+                b')' if depth == 0 => break,
+                b')' => depth -= 1,
+                b if b.is_ascii_whitespace() || b.is_ascii_control() => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        if depth != 0 {
+            return None;
+        }
+        start..i
+    };
+
+    let after_url = i;
+    i = skip_space(i);
+    // A title needs whitespace between it and the url
+    if i > after_url && i < end && matches!(bytes[i], b'"' | b'\'' | b'(') {
+        let quote = if bytes[i] == b'(' { b')' } else { bytes[i] };
+        let mut j = i + 1;
+        while j < end && bytes[j] != quote {
+            j += if bytes[j] == b'\\' { 2 } else { 1 };
+        }
+        if j >= end {
+            return None;
+        }
+        i = skip_space(j + 1);
+    }
+
+    (i < end && bytes[i] == b')').then(|| (url, i + 1))
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+/// Classifies a delimiter run by its neighbours (CommonMark's flanking
+/// rules). The edges of the text count as whitespace.
+fn delimiter(text: &str, ch: u8, range: Range<usize>) -> Delimiter {
+    let before = text[..range.start].chars().next_back().unwrap_or('\n');
+    let after = text[range.end..].chars().next().unwrap_or('\n');
+
+    let left = !after.is_whitespace()
+        && (!is_punctuation(after) || before.is_whitespace() || is_punctuation(before));
+    let right = !before.is_whitespace()
+        && (!is_punctuation(before) || after.is_whitespace() || is_punctuation(after));
+
+    // `_` doesn't work inside words, so snake_case stays plain
+    let (can_open, can_close) = if ch == b'_' {
+        (
+            left && (!right || is_punctuation(before)),
+            right && (!left || is_punctuation(after)),
+        )
+    } else {
+        (left, right)
+    };
+
+    Delimiter {
+        ch,
+        run_len: range.len(),
+        range,
+        can_open,
+        can_close,
+    }
+}
+
+fn can_pair(opener: &Delimiter, closer: &Delimiter) -> bool {
+    if opener.ch != closer.ch || !opener.can_open || opener.range.is_empty() {
+        return false;
+    }
+    match opener.ch {
+        // The "rule of 3" keeps `*a**b*` from pairing the wrong runs
+        b'*' | b'_' => {
+            !((opener.can_close || closer.can_open)
+                && (opener.run_len + closer.run_len).is_multiple_of(3)
+                && !(opener.run_len.is_multiple_of(3) && closer.run_len.is_multiple_of(3)))
+        }
+        _ => opener.range.len() == closer.range.len(),
+    }
+}
+
+/// Pairs delimiters from `bottom` on, innermost first, and emits their spans.
+/// Delimiters between a pair are dropped, which keeps the spans nested.
+fn process_emphasis(delimiters: &mut Vec<Delimiter>, bottom: usize, spans: &mut Vec<Span>) {
+    let mut c = bottom;
+    while c < delimiters.len() {
+        if !delimiters[c].can_close || delimiters[c].range.is_empty() {
+            c += 1;
+            continue;
+        }
+        let Some(o) = (bottom..c)
+            .rev()
+            .find(|&o| can_pair(&delimiters[o], &delimiters[c]))
+        else {
+            c += 1;
+            continue;
+        };
+
+        let (opener, closer) = (&delimiters[o], &delimiters[c]);
+        let used = match opener.ch {
+            b'*' | b'_' if opener.range.len() >= 2 && closer.range.len() >= 2 => 2,
+            b'*' | b'_' => 1,
+            _ => closer.range.len(),
+        };
+        let kind = match (opener.ch, used) {
+            (b'~', _) => SpanKind::Strikethrough,
+            (b'=', _) => SpanKind::Highlight,
+            (b'+', _) => SpanKind::Underline,
+            (_, 2) => SpanKind::Strong,
+            _ => SpanKind::Emphasis,
+        };
+        let start = opener.range.end - used;
+        let end = closer.range.start + used;
+        push_marked(spans, kind, start, end, used, used);
+
+        delimiters[o].range.end -= used;
+        delimiters[c].range.start += used;
+        delimiters.drain(o + 1..c);
+        c = o + 1;
+    }
+}
@@ -0,0 +1,252 @@
+// List editing: continuing, nesting and toggling list items
+//
+// Everything here works on single lines of text; the editor decides which
+// lines to look at and applies the edits to its buffer.
+
+use super::block::{list_marker_len, task_marker};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// `-`, `*` or `+`
+    Bullet(char),
+    /// A number and its delimiter, `.` or `)`
+    Ordered(u32, char),
+}
+
+/// The kinds of list the toolbar can toggle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+    Bullet,
+    Numbered,
+    Task,
+}
+
+/// The prefix of a list item line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListItem {
+    /// Leading whitespace, in bytes
+    pub indent: usize,
+    pub marker: Marker,
+    /// Where the marker and the space after it end, in bytes; a task
+    /// marker starts here
+    pub marker_end: usize,
+    /// Whether a task item is checked
+    pub task: Option<bool>,
+    /// Indent, marker and task marker with the spaces after them, in bytes
+    pub prefix_len: usize,
+}
+
+impl ListItem {
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim_start_matches([' ', '\t']);
+        let indent = line.len() - rest.len();
+        let marker_len = list_marker_len(rest)?;
+
+        let marker = match rest.as_bytes()[0] {
+            b @ (b'-' | b'*' | b'+') => Marker::Bullet(b as char),
+            _ => {
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                let delimiter = rest.as_bytes()[digits] as char;
+                Marker::Ordered(rest[..digits].parse().ok()?, delimiter)
+            }
+        };
+
+        let marker_end = indent + marker_len;
+        let mut prefix_len = marker_end;
+        let task = task_marker(&line[prefix_len..]);
+        if task.is_some() {
+            prefix_len += 3;
+            prefix_len += line[prefix_len..].starts_with([' ', '\t']) as usize;
+        }
+
+        Some(Self {
+            indent,
+            marker,
+            marker_end,
+            task,
+            prefix_len,
+        })
+    }
+
+    /// Width of the marker and the space after it: how far nested items are
+    /// indented under this one.
+    pub fn marker_width(&self) -> usize {
+        match self.marker {
+            Marker::Bullet(_) => 2,
+            Marker::Ordered(n, _) => n.to_string().len() + 2,
+        }
+    }
+
+    /// The prefix for the item after this one, without the indent. Numbers
+    /// count up and tasks start unchecked.
+    pub fn next_prefix(&self) -> String {
+        let marker = match self.marker {
+            Marker::Bullet(c) => format!("{} ", c),
+            Marker::Ordered(n, delimiter) => format!("{}{} ", n + 1, delimiter),
+        };
+        match self.task {
+            Some(_) => format!("{}[ ] ", marker),
+            None => marker,
+        }
+    }
+
+    /// The `[ ]` of a task item, in bytes.
+    pub fn task_range(&self) -> Option<Range<usize>> {
+        self.task.map(|_| self.marker_end..self.marker_end + 3)
+    }
+
+    fn is(&self, kind: ListKind) -> bool {
+        match kind {
+            ListKind::Bullet => matches!(self.marker, Marker::Bullet(_)) && self.task.is_none(),
+            ListKind::Numbered => matches!(self.marker, Marker::Ordered(..)) && self.task.is_none(),
+            ListKind::Task => self.task.is_some(),
+        }
+    }
+}
+
+/// The indent Tab gives `item`: nested under the nearest sibling above it.
+/// `above` holds the list items above, nearest first. Without a sibling to
+/// nest under, the indent stays as it is.
+pub fn indented(above: &[ListItem], item: &ListItem) -> usize {
+    match above.iter().find(|a| a.indent <= item.indent) {
+        Some(sibling) if sibling.indent == item.indent => sibling.indent + sibling.marker_width(),
+        _ => item.indent,
+    }
+}
+
+/// The indent Shift-Tab gives `item`: its parent's, or none at the top.
+pub fn outdented(above: &[ListItem], item: &ListItem) -> usize {
+    above
+        .iter()
+        .find(|a| a.indent < item.indent)
+        .map_or(0, |parent| parent.indent)
+}
+
+/// Toggles `kind` on `lines`: when every non-blank line already is such an
+/// item their markers are removed, otherwise every line becomes one. Returns
+/// a byte range of each line to replace and its replacement, if it changes.
+pub fn toggle(lines: &[&str], kind: ListKind) -> Vec<Option<(Range<usize>, String)>> {
+    let items: Vec<Option<ListItem>> = lines.iter().map(|l| ListItem::parse(l)).collect();
+    let all = lines
+        .iter()
+        .zip(&items)
+        .filter(|(line, _)| !line.trim().is_empty())
+        .all(|(_, item)| item.is_some_and(|item| item.is(kind)));
+
+    let mut number = 0;
+    lines
+        .iter()
+        .zip(&items)
+        .map(|(line, item)| {
+            if line.trim().is_empty() {
+                return None;
+            }
+            let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let prefix_end = item.map_or(indent, |item| item.prefix_len);
+            let prefix = if all {
+                String::new()
+            } else {
+                number += 1;
+                match (kind, item) {
+                    (ListKind::Bullet, _) => "- ".to_string(),
+                    (ListKind::Numbered, _) => format!("{}. ", number),
+                    (ListKind::Task, Some(item)) if item.task.is_some() => return None,
+                    // Other items keep their marker
+                    (ListKind::Task, Some(item)) => {
+                        format!("{} [ ] ", line[indent..item.prefix_len].trim_end())
+                    }
+                    (ListKind::Task, None) => "- [ ] ".to_string(),
+                }
+            };
+            (line[indent..prefix_end] != prefix).then_some((indent..prefix_end, prefix))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(line: &str) -> ListItem {
+        ListItem::parse(line).unwrap()
+    }
+
+    /// `lines` after toggling `kind` on them.
+    fn toggled(lines: &[&str], kind: ListKind) -> Vec<String> {
+        lines
+            .iter()
+            .zip(toggle(lines, kind))
+            .map(|(line, edit)| {
+                let mut line = line.to_string();
+                if let Some((range, prefix)) = edit {
+                    line.replace_range(range, &prefix);
+                }
+                line
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_item_prefixes() {
+        let task = item("  * [x] done");
+        assert_eq!(task.indent, 2);
+        assert_eq!(task.marker, Marker::Bullet('*'));
+        assert_eq!(task.task, Some(true));
+        assert_eq!(task.task_range(), Some(4..7));
+        assert_eq!(task.prefix_len, 8);
+        assert_eq!(item("12) c").marker, Marker::Ordered(12, ')'));
+        assert!(ListItem::parse("-a").is_none());
+        assert!(ListItem::parse("plain").is_none());
+    }
+
+    #[test]
+    fn next_prefix_counts_up_and_unchecks() {
+        assert_eq!(item("9. a").next_prefix(), "10. ");
+        assert_eq!(item("+ [X] done").next_prefix(), "+ [ ] ");
+    }
+
+    #[test]
+    fn indent_nests_under_the_sibling_above() {
+        let above = [item("  - b"), item("- a")];
+        assert_eq!(indented(&above, &item("- c")), 2);
+        assert_eq!(indented(&[item("10. a")], &item("11. b")), 4);
+        // The first item has nothing to nest under
+        assert_eq!(indented(&[], &item("- a")), 0);
+    }
+
+    #[test]
+    fn outdent_moves_to_the_parent() {
+        let above = [item("  - b"), item("- a")];
+        assert_eq!(outdented(&above, &item("    - c")), 2);
+        assert_eq!(outdented(&above, &item("  - c")), 0);
+        assert_eq!(outdented(&[], &item("  - c")), 0);
+    }
+
+    #[test]
+    fn toggle_turns_lines_into_items() {
+        let lines = ["a", "", "- b", "  1. c"];
+        assert_eq!(
+            toggled(&lines, ListKind::Numbered),
+            vec!["1. a", "", "2. b", "  3. c"]
+        );
+        assert_eq!(
+            toggled(&lines, ListKind::Task),
+            vec!["- [ ] a", "", "- [ ] b", "  1. [ ] c"]
+        );
+    }
+
+    #[test]
+    fn toggle_removes_markers_when_all_are_items() {
+        assert_eq!(
+            toggled(&["- a", "", "  * b"], ListKind::Bullet),
+            vec!["a", "", "  b"]
+        );
+        assert_eq!(toggled(&["- [x] a"], ListKind::Task), vec!["a"]);
+        // Not every line is a bullet item yet
+        assert_eq!(
+            toggled(&["- a", "1. b"], ListKind::Bullet),
+            vec!["- a", "- b"]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// Markdown parsing for the editor
+//
+// The parser follows CommonMark for the constructs the editor renders
+// (ATX headings, block quotes, fenced code, thematic breaks, list items,
+// emphasis, code spans, links and autolinks) plus the app's extensions:
+// GFM tables, bare urls, [[wiki links]], ~~strikethrough~~, ==highlight==,
+// ++underline++, `- [ ]` task markers and #tags.
+// It never fails: anything it does not recognise is plain text.
+//
+// Known simplifications: no setext headings, indented code blocks or
+// reference links, and each block quote or list line is parsed on its own.
+
+mod block;
+pub mod format;
+pub mod incremental;
+mod inline;
+pub mod list;
+pub mod syntax;
+pub mod table;
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpanKind {
+    // Blocks
+    Paragraph,
+    Heading(u8),
+    BlockQuote,
+    /// Fenced code; `info` is the first word after the opening fence.
+    CodeBlock {
+        info: String,
+    },
+    ThematicBreak,
+    ListItem,
+    /// `[ ]`, `[x]` or `[X]` right after a list marker.
+    TaskMarker {
+        checked: bool,
+    },
+    /// A pipe table; children are its rows, whose children are the pipes
+    /// (as markers) and the cells.
+    Table,
+    TableHeader,
+    /// The `| --- | :-: |` row under the header.
+    TableDelimiter,
+    TableRow,
+    TableCell,
+
+    // Inlines
+    Emphasis,
+    Strong,
+    Strikethrough,
+    Highlight,
+    Underline,
+    Code,
+    /// `[text](url)`; children are the markers, the text's inlines and a
+    /// `LinkUrl`.
+    Link,
+    Image,
+    LinkUrl,
+    /// `<https://...>`
+    Autolink,
+    /// A bare `https://...` or `www.` url, as GFM links them.
+    BareUrl,
+    /// `[[Note Title]]`, a link to another note; its markers are the
+    /// brackets.
+    WikiLink,
+    /// A backslash escape; its marker is the backslash.
+    Escape,
+    Tag,
+
+    /// Syntax characters of the parent span (`**`, `# `, a code fence line...).
+    Marker,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub kind: SpanKind,
+    /// Byte range in the parsed text.
+    pub range: Range<usize>,
+    pub children: Vec<Span>,
+}
+
+impl Span {
+    fn new(kind: SpanKind, range: Range<usize>) -> Self {
+        Self {
+            kind,
+            range,
+            children: Vec::new(),
+        }
+    }
+
+    /// Visits this span and all its descendants, parents first.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Span, Option<&'a Span>)) {
+        fn visit<'a>(
+            span: &'a Span,
+            parent: Option<&'a Span>,
+            f: &mut impl FnMut(&'a Span, Option<&'a Span>),
+        ) {
+            f(span, parent);
+            for child in &span.children {
+                visit(child, Some(span), f);
+            }
+        }
+        visit(self, None, f);
+    }
+}
+
+/// Parses `text` into a tree of block spans with their inline children.
+pub fn parse(text: &str) -> Vec<Span> {
+    block::parse_blocks(text)
+}
+
+/// Builds a tree out of properly nested spans. Spans sharing a start are
+/// ordered outermost first.
+fn nest(mut spans: Vec<Span>) -> Vec<Span> {
+    spans.sort_by(|a, b| {
+        a.range
+            .start
+            .cmp(&b.range.start)
+            .then(b.range.end.cmp(&a.range.end))
+    });
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<Span> = Vec::new();
+    for span in spans {
+        while stack
+            .last()
+            .is_some_and(|top| span.range.start >= top.range.end)
+        {
+            let done = stack.pop().unwrap();
+            attach(&mut stack, &mut roots, done);
+        }
+        stack.push(span);
+    }
+    while let Some(done) = stack.pop() {
+        attach(&mut stack, &mut roots, done);
+    }
+    roots
+}
+
+fn attach(stack: &mut [Span], roots: &mut Vec<Span>, span: Span) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(span),
+        None => roots.push(span),
+    }
+}
@@ -0,0 +1,470 @@
+// Syntax highlighting for fenced code blocks
+//
+// A small lexer driven by a per-language grammar: enough to colour
+// keywords, types, strings, numbers and comments, not to parse the code.
+// The fence's info string picks the grammar; anything unknown still gets
+// strings, numbers and the common comment styles.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+}
+
+struct Grammar {
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    /// Identifiers starting with an uppercase letter are types (`String`, `Path`)
+    capitalized_types: bool,
+    /// SQL keywords are matched in any case
+    ignore_case: bool,
+    line_comments: &'static [&'static str],
+    /// A `#` comment has to start a word, so `${#list}` stays code
+    hash_needs_word_start: bool,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Longest first, so `"""` wins over `"`
+    quotes: &'static [&'static str],
+    /// Whether single-char quotes may span lines (triple quotes always can)
+    multiline_strings: bool,
+    /// Rust's `'a'` is a char but `'a` is a lifetime
+    char_literals: bool,
+    /// A `'` right after a word is an apostrophe, not a quote (`don't`)
+    apostrophes: bool,
+}
+
+const RUST: Grammar = Grammar {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+        "type", "unsafe", "use", "where", "while",
+    ],
+    types: &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+        "f32", "f64", "bool", "char", "str",
+    ],
+    capitalized_types: true,
+    ignore_case: false,
+    line_comments: &["//"],
+    hash_needs_word_start: false,
+    block_comment: Some(("/*", "*/")),
+    quotes: &["\""],
+    multiline_strings: true,
+    char_literals: true,
+    apostrophes: false,
+};
+
+const PYTHON: Grammar = Grammar {
+    keywords: &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "case", "class",
+        "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+        "if", "import", "in", "is", "lambda", "match", "nonlocal", "not", "or", "pass", "raise",
+        "return", "try", "while", "with", "yield",
+    ],
+    types: &[
+        "bool",
+        "bytes",
+        "dict",
+        "float",
+        "frozenset",
+        "int",
+        "list",
+        "object",
+        "set",
+        "str",
+        "tuple",
+        "type",
+    ],
+    capitalized_types: true,
+    ignore_case: false,
+    line_comments: &["#"],
+    hash_needs_word_start: false,
+    block_comment: None,
+    quotes: &["\"\"\"", "'''", "\"", "'"],
+    multiline_strings: false,
+    char_literals: false,
+    apostrophes: false,
+};
+
+const SQL: Grammar = Grammar {
+    keywords: &[
+        "add",
+        "all",
+        "alter",
+        "and",
+        "as",
+        "asc",
+        "begin",
+        "between",
+        "by",
+        "case",
+        "check",
+        "commit",
+        "conflict",
+        "create",
+        "cross",
+        "default",
+        "delete",
+        "desc",
+        "distinct",
+        "do",
+        "drop",
+        "else",
+        "end",
+        "exists",
+        "false",
+        "foreign",
+        "from",
+        "full",
+        "group",
+        "having",
+        "if",
+        "in",
+        "index",
+        "inner",
+        "insert",
+        "into",
+        "is",
+        "join",
+        "key",
+        "left",
+        "like",
+        "limit",
+        "not",
+        "null",
+        "offset",
+        "on",
+        "or",
+        "order",
+        "outer",
+        "primary",
+        "references",
+        "replace",
+        "returning",
+        "right",
+        "rollback",
+        "select",
+        "set",
+        "table",
+        "then",
+        "transaction",
+        "trigger",
+        "true",
+        "union",
+        "unique",
+        "update",
+        "using",
+        "values",
+        "view",
+        "when",
+        "where",
+        "with",
+    ],
+    types: &[
+        "bigint",
+        "blob",
+        "boolean",
+        "char",
+        "date",
+        "decimal",
+        "double",
+        "float",
+        "int",
+        "integer",
+        "json",
+        "jsonb",
+        "numeric",
+        "real",
+        "serial",
+        "smallint",
+        "text",
+        "time",
+        "timestamp",
+        "uuid",
+        "varchar",
+    ],
+    capitalized_types: false,
+    ignore_case: true,
+    line_comments: &["--"],
+    hash_needs_word_start: false,
+    block_comment: Some(("/*", "*/")),
+    quotes: &["'", "\""],
+    multiline_strings: true,
+    char_literals: false,
+    apostrophes: false,
+};
+
+const BASH: Grammar = Grammar {
+    keywords: &[
+        "break", "case", "continue", "do", "done", "elif", "else", "esac", "exit", "export", "fi",
+        "for", "function", "if", "in", "local", "readonly", "return", "select", "then", "until",
+        "while",
+    ],
+    // Builtins, coloured like types
+    types: &[
+        "alias", "cd", "declare", "echo", "eval", "exec", "printf", "read", "set", "shift",
+        "source", "test", "trap", "unset",
+    ],
+    capitalized_types: false,
+    ignore_case: false,
+    line_comments: &["#"],
+    hash_needs_word_start: true,
+    block_comment: None,
+    quotes: &["\"", "'"],
+    multiline_strings: true,
+    char_literals: false,
+    apostrophes: false,
+};
+
+const FALLBACK: Grammar = Grammar {
+    keywords: &[],
+    types: &[],
+    capitalized_types: false,
+    ignore_case: false,
+    line_comments: &["//", "#"],
+    hash_needs_word_start: true,
+    block_comment: Some(("/*", "*/")),
+    quotes: &["\"", "'", "`"],
+    multiline_strings: false,
+    char_literals: false,
+    apostrophes: true,
+};
+
+fn grammar(info: &str) -> &'static Grammar {
+    match info.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => &RUST,
+        "python" | "py" | "python3" => &PYTHON,
+        "sql" | "sqlite" | "postgres" | "postgresql" | "mysql" => &SQL,
+        "bash" | "sh" | "shell" | "zsh" => &BASH,
+        _ => &FALLBACK,
+    }
+}
+
+/// Token ranges in `code`, a code block's content, for the language named by
+/// the fence's info string.
+pub fn highlight(info: &str, code: &str) -> Vec<(Range<usize>, Token)> {
+    let grammar = grammar(info);
+    let bytes = code.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < code.len() {
+        let rest = &code[i..];
+        let prev = code[..i].chars().next_back();
+
+        if let Some(comment) = grammar.line_comments.iter().find(|c| rest.starts_with(*c)) {
+            let word_start = prev.is_none_or(|c| c.is_whitespace());
+            if *comment != "#" || !grammar.hash_needs_word_start || word_start {
+                let end = rest.find('\n').map_or(code.len(), |n| i + n);
+                tokens.push((i..end, Token::Comment));
+                i = end;
+                continue;
+            }
+        }
+
+        if let Some((open, close)) = grammar
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            let end = rest[open.len()..]
+                .find(close)
+                .map_or(code.len(), |n| i + open.len() + n + close.len());
+            tokens.push((i..end, Token::Comment));
+            i = end;
+            continue;
+        }
+
+        let after_word = prev.is_some_and(is_word_char);
+
+        if let Some(quote) = grammar
+            .quotes
+            .iter()
+            .find(|q| rest.starts_with(*q))
+            .filter(|q| !(grammar.apostrophes && after_word && **q == "'"))
+        {
+            let multiline = grammar.multiline_strings || quote.len() > 1;
+            let end = string_end(code, i + quote.len(), quote, multiline);
+            tokens.push((i..end, Token::String));
+            i = end;
+            continue;
+        }
+
+        if grammar.char_literals && bytes[i] == b'\'' {
+            if let Some(end) = char_literal_end(code, i) {
+                tokens.push((i..end, Token::String));
+                i = end;
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap();
+
+        if c.is_ascii_digit() && !after_word {
+            // Good enough for 42, 1_000, 0xFF, 2.5e3 and suffixes like 10u32
+            let end = i + rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            // Don't swallow the `..` of a range
+            let end = code[i..end].find("..").map_or(end, |n| i + n);
+            tokens.push((i..end, Token::Number));
+            i = end;
+            continue;
+        }
+
+        if (c.is_alphabetic() || c == '_') && !after_word {
+            let end = i + rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+            let word = &code[i..end];
+            if let Some(token) = classify(grammar, word) {
+                tokens.push((i..end, token));
+            }
+            i = end;
+            continue;
+        }
+
+        i += c.len_utf8();
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn classify(grammar: &Grammar, word: &str) -> Option<Token> {
+    let matches = |list: &[&str]| {
+        if grammar.ignore_case {
+            list.iter().any(|w| w.eq_ignore_ascii_case(word))
+        } else {
+            list.contains(&word)
+        }
+    };
+
+    if matches(grammar.keywords) {
+        Some(Token::Keyword)
+    } else if matches(grammar.types)
+        || (grammar.capitalized_types && word.starts_with(|c: char| c.is_uppercase()))
+    {
+        Some(Token::Type)
+    } else {
+        None
+    }
+}
+
+/// End of a string whose contents start at `from`. An unterminated string
+/// runs to the end of its line, or of the code if strings can span lines.
+fn string_end(code: &str, from: usize, quote: &str, multiline: bool) -> usize {
+    let mut chars = code[from..].char_indices();
+    while let Some((n, c)) = chars.next() {
+        let at = from + n;
+        if c == '\\' {
+            chars.next();
+        } else if code[at..].starts_with(quote) {
+            return at + quote.len();
+        } else if c == '\n' && !multiline {
+            return at;
+        }
+    }
+    code.len()
+}
+
+/// End of a Rust char literal like `'a'` or `'\n'` at `start`, or `None`
+/// for a lifetime.
+fn char_literal_end(code: &str, start: usize) -> Option<usize> {
+    let mut chars = code[start + 1..].char_indices();
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        // Escapes run up to the closing quote: '\n', '\u{1F349}'
+        let close = code[start + 2..].find('\'')?;
+        let end = start + 2 + close + 1;
+        return (!code[start..end].contains('\n')).then_some(end);
+    }
+    match chars.next() {
+        Some((n, '\'')) => Some(start + 1 + n + 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Token::*;
+
+    fn tokens<'a>(info: &str, code: &'a str) -> Vec<(Token, &'a str)> {
+        highlight(info, code)
+            .into_iter()
+            .map(|(range, token)| (token, &code[range]))
+            .collect()
+    }
+
+    #[test]
+    fn rust() {
+        assert_eq!(
+            tokens("rust", "let s: String = \"a \\\" b\"; // note"),
+            vec![
+                (Keyword, "let"),
+                (Type, "String"),
+                (String, "\"a \\\" b\""),
+                (Comment, "// note"),
+            ]
+        );
+        // Chars are strings, lifetimes aren't
+        assert_eq!(
+            tokens("rs", "fn f<'a>(c: char) { 'x' }"),
+            vec![(Keyword, "fn"), (Type, "char"), (String, "'x'")]
+        );
+        assert_eq!(
+            tokens("rust", "0x1F + 1_000 /* x */"),
+            vec![(Number, "0x1F"), (Number, "1_000"), (Comment, "/* x */")]
+        );
+    }
+
+    #[test]
+    fn python_triple_quotes_span_lines() {
+        assert_eq!(
+            tokens("py", "x = '''a\nb''' # c"),
+            vec![(String, "'''a\nb'''"), (Comment, "# c")]
+        );
+        assert_eq!(
+            tokens("python", "return None"),
+            vec![(Keyword, "return"), (Keyword, "None")]
+        );
+    }
+
+    #[test]
+    fn sql_keywords_in_any_case() {
+        assert_eq!(
+            tokens("SQL", "select id FROM t -- c"),
+            vec![(Keyword, "select"), (Keyword, "FROM"), (Comment, "-- c")]
+        );
+    }
+
+    #[test]
+    fn bash_hash_needs_a_word_start() {
+        assert_eq!(
+            tokens("sh", "echo ${#list} # real"),
+            vec![(Type, "echo"), (Comment, "# real")]
+        );
+    }
+
+    #[test]
+    fn unknown_languages_get_the_basics() {
+        assert_eq!(
+            tokens("", "don't `x` 42 // c"),
+            vec![(String, "`x`"), (Number, "42"), (Comment, "// c")]
+        );
+        assert!(tokens("nonsense", "fn let").is_empty());
+    }
+
+    #[test]
+    fn unterminated_strings_stop_at_the_line_end() {
+        assert_eq!(
+            tokens("py", "'it\nx = 1"),
+            vec![(String, "'it"), (Number, "1")]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+// GFM pipe tables
+//
+// The block parser uses the row helpers here to find tables; the editor uses
+// `Table` to edit one and write it back out as canonical Markdown, with every
+// column padded to the same width so the table lines up in a monospace font.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// The cells of one row as byte ranges into the line, untrimmed, and the
+/// positions of the pipes between them.
+pub(super) struct Row {
+    pub cells: Vec<Range<usize>>,
+    pub pipes: Vec<usize>,
+}
+
+/// Splits a row at its unescaped pipes. The outer pipes are optional.
+pub(super) fn split_row(line: &str) -> Row {
+    let bytes = line.as_bytes();
+    let mut pipes = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'|' => {
+                pipes.push(i);
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+
+    let mut bounds = vec![0];
+    bounds.extend(pipes.iter().flat_map(|&p| [p, p + 1]));
+    bounds.push(line.len());
+    let mut cells: Vec<Range<usize>> = bounds.chunks(2).map(|b| b[0]..b[1]).collect();
+
+    if cells.len() > 1 && line[cells[0].clone()].trim().is_empty() {
+        cells.remove(0);
+    }
+    if cells.len() > 1 && line[cells[cells.len() - 1].clone()].trim().is_empty() {
+        cells.pop();
+    }
+    Row { cells, pipes }
+}
+
+/// The column alignments if `line` is a delimiter row like `| --- | :-: |`.
+pub(super) fn delimiter_row(line: &str) -> Option<Vec<Alignment>> {
+    split_row(line)
+        .cells
+        .iter()
+        .map(|cell| {
+            let cell = line[cell.clone()].trim();
+            let left = cell.starts_with(':');
+            let right = cell.len() > 1 && cell.ends_with(':');
+            let dashes = &cell[left as usize..cell.len() - right as usize];
+            if dashes.is_empty() || !dashes.bytes().all(|b| b == b'-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            })
+        })
+        .collect()
+}
+
+/// A table as the editor edits it: cell text without the pipes and padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// An empty table with `columns` columns named "Column 1", "Column 2"...
+    pub fn new(columns: usize) -> Self {
+        Self {
+            alignments: vec![Alignment::None; columns],
+            header: (1..=columns).map(|n| format!("Column {}", n)).collect(),
+            rows: vec![vec![String::new(); columns]],
+        }
+    }
+
+    /// Parses the lines of a table block. Rows with more cells than the
+    /// header widen the table rather than losing text.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let header = cells(lines.next()?);
+        let mut alignments = delimiter_row(lines.next()?)?;
+        if alignments.len() != header.len() {
+            return None;
+        }
+
+        let mut table = Self {
+            alignments: Vec::new(),
+            header,
+            rows: lines.map(cells).collect(),
+        };
+        let columns = table
+            .rows
+            .iter()
+            .map(Vec::len)
+            .fold(table.header.len(), usize::max);
+        alignments.resize(columns, Alignment::None);
+        table.alignments = alignments;
+        for row in std::iter::once(&mut table.header).chain(&mut table.rows) {
+            row.resize(columns, String::new());
+        }
+        Some(table)
+    }
+
+    pub fn columns(&self) -> usize {
+        self.alignments.len()
+    }
+
+    /// Column widths in chars, at least 3 for the delimiter row's dashes.
+    fn widths(&self) -> Vec<usize> {
+        (0..self.columns())
+            .map(|col| {
+                std::iter::once(&self.header)
+                    .chain(&self.rows)
+                    .map(|row| row[col].chars().count())
+                    .fold(3, usize::max)
+            })
+            .collect()
+    }
+
+    /// The table as canonical Markdown, one line per row and no trailing
+    /// line break.
+    pub fn to_markdown(&self) -> String {
+        let widths = self.widths();
+        let mut lines = vec![self.format_row(&self.header, &widths)];
+
+        let delimiter: Vec<String> = self
+            .alignments
+            .iter()
+            .zip(&widths)
+            .map(|(alignment, &width)| match alignment {
+                Alignment::None => "-".repeat(width),
+                Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+                Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+                Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+            })
+            .collect();
+        lines.push(format!("| {} |", delimiter.join(" | ")));
+
+        for row in &self.rows {
+            lines.push(self.format_row(row, &widths));
+        }
+        lines.join("\n")
+    }
+
+    fn format_row(&self, row: &[String], widths: &[usize]) -> String {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .zip(&self.alignments)
+            .map(|((cell, &width), alignment)| {
+                let pad = width - cell.chars().count();
+                let (before, after) = match alignment {
+                    Alignment::Right => (pad, 0),
+                    Alignment::Center => (pad / 2, pad - pad / 2),
+                    _ => (0, pad),
+                };
+                format!("{}{}{}", " ".repeat(before), cell, " ".repeat(after))
+            })
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    }
+
+    /// Where the text of a cell starts and ends in `to_markdown`'s output,
+    /// in chars. `line` counts the delimiter row, so body rows start at 2.
+    pub fn cell_range(&self, line: usize, col: usize) -> Range<usize> {
+        let widths = self.widths();
+        let line_len = widths.iter().map(|w| w + 3).sum::<usize>() + 1;
+        let start = line * (line_len + 1) + 2 + widths[..col].iter().map(|w| w + 3).sum::<usize>();
+        let row = match line {
+            0 => &self.header,
+            1 => return start..start + widths[col],
+            _ => &self.rows[line - 2],
+        };
+        let len = row[col].chars().count();
+        let before = match self.alignments[col] {
+            Alignment::Right => widths[col] - len,
+            Alignment::Center => (widths[col] - len) / 2,
+            _ => 0,
+        };
+        start + before..start + before + len
+    }
+
+    /// Adds an empty row before body row `at`.
+    pub fn insert_row(&mut self, at: usize) {
+        self.rows
+            .insert(at.min(self.rows.len()), vec![String::new(); self.columns()]);
+    }
+
+    pub fn remove_row(&mut self, at: usize) {
+        if at < self.rows.len() {
+            self.rows.remove(at);
+        }
+    }
+
+    /// Adds an empty column before column `at`.
+    pub fn insert_column(&mut self, at: usize) {
+        let at = at.min(self.columns());
+        self.alignments.insert(at, Alignment::None);
+        for row in std::iter::once(&mut self.header).chain(&mut self.rows) {
+            row.insert(at, String::new());
+        }
+    }
+
+    /// Removes column `at`, unless it's the only one.
+    pub fn remove_column(&mut self, at: usize) {
+        if self.columns() < 2 || at >= self.columns() {
+            return;
+        }
+        self.alignments.remove(at);
+        for row in std::iter::once(&mut self.header).chain(&mut self.rows) {
+            row.remove(at);
+        }
+    }
+}
+
+fn cells(line: &str) -> Vec<String> {
+    split_row(line)
+        .cells
+        .into_iter()
+        .map(|cell| line[cell].trim().to_string())
+        .collect()
+}
+
+/// The column of the cell containing byte `pos` of a table line.
+pub fn column_at(line: &str, pos: usize) -> usize {
+    let row = split_row(line);
+    row.cells
+        .iter()
+        .position(|cell| pos <= cell.end)
+        .unwrap_or(row.cells.len().saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "| Name | Qty |\n|:--|--:|\n| apple | 3 |\n| b \\| c | 10 | extra |";
+
+    /// The text of every cell of `table` as `cell_range` finds it in `markdown`.
+    fn cell_texts(table: &Table, markdown: &str) -> Vec<String> {
+        let chars: Vec<char> = markdown.chars().collect();
+        (0..table.rows.len() + 2)
+            .filter(|&line| line != 1)
+            .flat_map(|line| (0..table.columns()).map(move |col| (line, col)))
+            .map(|(line, col)| chars[table.cell_range(line, col)].iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn escaped_pipes_round_trip() {
+        let table = Table::parse(TABLE).unwrap();
+        assert_eq!(table.rows[1], vec!["b \\| c", "10", "extra"]);
+        let markdown = table.to_markdown();
+        assert_eq!(Table::parse(&markdown).unwrap(), table);
+        assert_eq!(Table::parse(&markdown).unwrap().to_markdown(), markdown);
+    }
+
+    #[test]
+    fn canonical_markdown_pads_and_aligns() {
+        let table = Table::parse(TABLE).unwrap();
+        assert_eq!(
+            table.alignments,
+            [Alignment::Left, Alignment::Right, Alignment::None]
+        );
+        assert_eq!(
+            table.to_markdown(),
+            "| Name   | Qty |       |\n\
+             | :----- | --: | ----- |\n\
+             | apple  |   3 |       |\n\
+             | b \\| c |  10 | extra |"
+        );
+    }
+
+    #[test]
+    fn cell_ranges_point_at_the_cells() {
+        let table = Table::parse("| aé | b |\n|:-:|---|\n| x | long cell |").unwrap();
+        let markdown = table.to_markdown();
+        let cells: Vec<String> = std::iter::once(&table.header)
+            .chain(&table.rows)
+            .flatten()
+            .cloned()
+            .collect();
+        assert_eq!(cell_texts(&table, &markdown), cells);
+    }
+
+    #[test]
+    fn not_a_table() {
+        assert!(Table::parse("| a | b |\n| x | y |").is_none());
+        assert!(Table::parse("| a | b |\n|---|").is_none());
+        assert!(Table::parse("| a |").is_none());
+    }
+
+    #[test]
+    fn editing_rows_and_columns() {
+        let mut table = Table::new(2);
+        table.insert_column(1);
+        assert_eq!(table.header, ["Column 1", "", "Column 2"]);
+        table.remove_column(0);
+        table.remove_column(0);
+        table.remove_column(0);
+        assert_eq!(table.columns(), 1);
+        table.insert_row(9);
+        table.remove_row(0);
+        table.remove_row(9);
+        assert_eq!(table.rows.len(), 1);
+    }
+
+    #[test]
+    fn column_at_a_position() {
+        let line = "| a | bb | c";
+        assert_eq!(column_at(line, 0), 0);
+        assert_eq!(column_at(line, 6), 1);
+        assert_eq!(column_at(line, line.len()), 2);
+    }
+}
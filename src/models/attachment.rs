@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Notes link to their attachments as `local://<attachment id>`.
+pub const URL_PREFIX: &str = "local://";
+
+/// A file copied into the attachments directory for a note.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub note_id: Uuid,
+    /// Name of the file it was copied from
+    pub file_name: String,
+    /// Where the copy lives, relative to the database's directory
+    pub file_path: String,
+    pub mime_type: Option<String>,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Attachment {
+    pub fn url(&self) -> String {
+        format!("{}{}", URL_PREFIX, self.id)
+    }
+
+    /// How a note links to it: an image for pictures, a plain link otherwise.
+    pub fn markdown(&self) -> String {
+        // Brackets would end the link text early
+        let mut name = String::new();
+        for c in self.file_name.chars() {
+            if matches!(c, '[' | ']' | '\\') {
+                name.push('\\');
+            }
+            name.push(c);
+        }
+        let bang = if self.is_image() { "!" } else { "" };
+        format!("{}[{}]({})", bang, name, self.url())
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.mime_type
+            .as_deref()
+            .is_some_and(|mime| mime.starts_with("image/"))
+    }
+}
+
+/// The attachment id of a `local://` url.
+pub fn id_from_url(url: &str) -> Option<Uuid> {
+    Uuid::parse_str(url.strip_prefix(URL_PREFIX)?).ok()
+}
+
+/// Ids of the attachments `text` links to.
+pub fn referenced_ids(text: &str) -> impl Iterator<Item = Uuid> + '_ {
+    text.match_indices(URL_PREFIX).filter_map(|(i, _)| {
+        let start = i + URL_PREFIX.len();
+        Uuid::try_parse(text.get(start..start + 36)?).ok()
+    })
+}
@@ -0,0 +1,239 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Folder {
+    pub id: Uuid,
+    pub name: String,
+    /// Enclosing folder, None for top-level folders.
+    pub parent_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Folder {
+    pub fn new(name: String, parent_id: Option<Uuid>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            parent_id,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// What happens to the notes of a folder that is being deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDisposal {
+    /// Move them to another folder, or out of any folder with None.
+    MoveTo(Option<Uuid>),
+    Trash,
+}
+
+pub const MAX_FOLDER_NAME_LEN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FolderNameError {
+    Empty,
+    TooLong,
+    Duplicate(String),
+}
+
+impl fmt::Display for FolderNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FolderNameError::Empty => write!(f, "Enter a folder name"),
+            FolderNameError::TooLong => write!(
+                f,
+                "Folder names can be at most {} characters",
+                MAX_FOLDER_NAME_LEN
+            ),
+            FolderNameError::Duplicate(name) => {
+                write!(f, "A folder named “{}” already exists here", name)
+            }
+        }
+    }
+}
+
+/// Check a folder name for a folder under `parent_id`. `renaming` is the
+/// folder being renamed, which doesn't clash with itself. Returns the trimmed
+/// name. Sibling names are compared case-insensitively, like the database's
+/// unique index does.
+pub fn validate_name(
+    name: &str,
+    parent_id: Option<Uuid>,
+    renaming: Option<Uuid>,
+    folders: &[Folder],
+) -> Result<String, FolderNameError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(FolderNameError::Empty);
+    }
+    if name.chars().count() > MAX_FOLDER_NAME_LEN {
+        return Err(FolderNameError::TooLong);
+    }
+    let taken = folders.iter().any(|f| {
+        f.parent_id == parent_id && Some(f.id) != renaming && f.name.eq_ignore_ascii_case(name)
+    });
+    if taken {
+        return Err(FolderNameError::Duplicate(name.to_string()));
+    }
+    Ok(name.to_string())
+}
+
+/// `base`, or `base 2`, `base 3`, … whichever is free under `parent_id`.
+pub fn unique_name(base: &str, parent_id: Option<Uuid>, folders: &[Folder]) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => base.to_string(),
+            n => format!("{} {}", base, n),
+        })
+        .find(|name| validate_name(name, parent_id, None, folders).is_ok())
+        .unwrap_or_else(|| base.to_string())
+}
+
+/// Parent of `folder`; a dangling parent id counts as top level.
+fn parent_of(folder: &Folder, folders: &[Folder]) -> Option<Uuid> {
+    folder
+        .parent_id
+        .filter(|id| folders.iter().any(|f| f.id == *id))
+}
+
+/// Every folder in tree order with its full path, e.g. "Work / Meetings".
+pub fn paths(folders: &[Folder]) -> Vec<(Uuid, String)> {
+    fn collect(
+        folders: &[Folder],
+        parent_id: Option<Uuid>,
+        prefix: &str,
+        paths: &mut Vec<(Uuid, String)>,
+    ) {
+        for folder in folders
+            .iter()
+            .filter(|f| parent_of(f, folders) == parent_id)
+        {
+            let path = if prefix.is_empty() {
+                folder.name.clone()
+            } else {
+                format!("{} / {}", prefix, folder.name)
+            };
+            paths.push((folder.id, path.clone()));
+            collect(folders, Some(folder.id), &path, paths);
+        }
+    }
+
+    let mut paths = Vec::new();
+    collect(folders, None, "", &mut paths);
+    paths
+}
+
+/// Whether folder `id` is `ancestor` or somewhere below it.
+pub fn is_within(id: Uuid, ancestor: Uuid, folders: &[Folder]) -> bool {
+    let mut current = Some(id);
+    // Bounded, in case the parent ids ever form a loop
+    for _ in 0..=folders.len() {
+        match current {
+            Some(id) if id == ancestor => return true,
+            Some(id) => {
+                current = folders
+                    .iter()
+                    .find(|f| f.id == id)
+                    .and_then(|f| parent_of(f, folders))
+            }
+            None => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(name: &str, parent: Option<&Folder>) -> Folder {
+        Folder::new(name.to_string(), parent.map(|p| p.id))
+    }
+
+    #[test]
+    fn names_are_trimmed_and_checked() {
+        let work = folder("Work", None);
+        let folders = [work.clone(), folder("Meetings", Some(&work))];
+        assert_eq!(
+            validate_name("  Home ", None, None, &folders),
+            Ok("Home".into())
+        );
+        assert_eq!(
+            validate_name(" ", None, None, &folders),
+            Err(FolderNameError::Empty)
+        );
+        let long = "x".repeat(MAX_FOLDER_NAME_LEN + 1);
+        assert_eq!(
+            validate_name(&long, None, None, &folders),
+            Err(FolderNameError::TooLong)
+        );
+    }
+
+    #[test]
+    fn sibling_names_clash_ignoring_case() {
+        let work = folder("Work", None);
+        let folders = [work.clone(), folder("Meetings", Some(&work))];
+        assert_eq!(
+            validate_name("work", None, None, &folders),
+            Err(FolderNameError::Duplicate("work".into()))
+        );
+        // Under another parent, or renaming the folder itself, it's free
+        assert!(validate_name("Work", Some(work.id), None, &folders).is_ok());
+        assert!(validate_name("WORK", None, Some(work.id), &folders).is_ok());
+        assert!(validate_name("meetings", None, None, &folders).is_ok());
+    }
+
+    #[test]
+    fn unique_names_count_up() {
+        let folders = [folder("New Folder", None), folder("new folder 2", None)];
+        assert_eq!(unique_name("New Folder", None, &folders), "New Folder 3");
+        assert_eq!(unique_name("Other", None, &folders), "Other");
+    }
+
+    #[test]
+    fn paths_in_tree_order() {
+        let work = folder("Work", None);
+        let meetings = folder("Meetings", Some(&work));
+        let mut orphan = folder("Orphan", None);
+        orphan.parent_id = Some(Uuid::new_v4());
+        let folders = [meetings.clone(), orphan.clone(), work.clone()];
+        assert_eq!(
+            paths(&folders),
+            vec![
+                (orphan.id, "Orphan".to_string()),
+                (work.id, "Work".to_string()),
+                (meetings.id, "Work / Meetings".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn is_within_follows_parents() {
+        let work = folder("Work", None);
+        let meetings = folder("Meetings", Some(&work));
+        let notes = folder("Notes", Some(&meetings));
+        let folders = [work.clone(), meetings.clone(), notes.clone()];
+        assert!(is_within(notes.id, work.id, &folders));
+        assert!(is_within(work.id, work.id, &folders));
+        assert!(!is_within(work.id, notes.id, &folders));
+    }
+
+    #[test]
+    fn folder_cycles_end() {
+        let mut a = folder("A", None);
+        let b = folder("B", Some(&a));
+        a.parent_id = Some(b.id);
+        let c = folder("C", None);
+        let folders = [a.clone(), b.clone(), c.clone()];
+        assert!(is_within(a.id, b.id, &folders));
+        assert!(!is_within(a.id, c.id, &folders));
+        // Neither is at the top, so a loop never shows up in the tree
+        assert_eq!(paths(&folders), vec![(c.id, "C".to_string())]);
+    }
+}
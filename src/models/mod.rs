@@ -1 +1,6 @@
+pub mod attachment;
+pub mod folder;
 pub mod note;
+pub mod revision;
+pub mod search;
+pub mod tag;
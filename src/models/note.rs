@@ -1,3 +1,4 @@
+use crate::utils::tags;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
@@ -8,7 +9,11 @@ pub struct Note {
     pub content: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
-    pub folder: Option<String>,
+    pub folder_id: Option<Uuid>,
+    pub is_deleted: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// `#tags` found in the content, see `utils::tags::extract_tags`.
+    pub tags: Vec<String>,
 }
 
 impl Note {
@@ -17,10 +22,13 @@ impl Note {
         Self {
             id: Uuid::new_v4(),
             title,
+            tags: tags::extract_tags(&content),
             content,
             created_at: now,
             updated_at: now,
-            folder: None,
+            folder_id: None,
+            is_deleted: false,
+            deleted_at: None,
         }
     }
 }
@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// A snapshot of a note. Edits made within a short window are folded into a
+/// single revision, so `created_at` is when the window opened and
+/// `updated_at` is the last edit captured in it.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub id: Uuid,
+    pub note_id: Uuid,
+    pub title: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,10 @@
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub note_id: Uuid,
+    /// Short excerpt of the best matching region of the note.
+    pub snippet: String,
+    /// Byte ranges of the matched terms inside `snippet`.
+    pub matches: Vec<(usize, usize)>,
+}
@@ -0,0 +1,25 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    pub name: String,
+    /// Number of notes outside the trash that use the tag.
+    pub count: usize,
+}
+
+/// How several selected tags combine when filtering notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMatch {
+    /// Notes with at least one of the tags.
+    #[default]
+    Any,
+    /// Notes with every one of the tags.
+    All,
+}
+
+impl TagMatch {
+    pub fn matches(&self, note_tags: &[String], selected: &[String]) -> bool {
+        match self {
+            TagMatch::Any => selected.iter().any(|t| note_tags.contains(t)),
+            TagMatch::All => selected.iter().all(|t| note_tags.contains(t)),
+        }
+    }
+}
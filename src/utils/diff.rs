@@ -0,0 +1,182 @@
+// Line-based diff (Myers' O(ND) algorithm)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+impl DiffLine {
+    fn new(kind: DiffKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Diff `old` against `new` line by line.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Trim the common head and tail first; edits are usually local, and this
+    // keeps the O(ND) part small for long notes.
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut lines: Vec<DiffLine> = a[..prefix]
+        .iter()
+        .map(|l| DiffLine::new(DiffKind::Unchanged, l))
+        .collect();
+    lines.extend(myers(
+        &a[prefix..a.len() - suffix],
+        &b[prefix..b.len() - suffix],
+    ));
+    lines.extend(
+        a[a.len() - suffix..]
+            .iter()
+            .map(|l| DiffLine::new(DiffKind::Unchanged, l)),
+    );
+    lines
+}
+
+fn myers(a: &[&str], b: &[&str]) -> Vec<DiffLine> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    if max == 0 {
+        return Vec::new();
+    }
+
+    // v[k + max] is the furthest x reached on diagonal k. trace[d] keeps the
+    // diagonals -d..=d of v as they were at the start of round d.
+    let mut v = vec![0isize; 2 * max as usize + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let at = |k: isize| (k + max) as usize;
+
+    'search: for d in 0..=max {
+        trace.push(v[at(-d)..=at(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk the trace backwards to recover the edit script.
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { get(prev_k) };
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(DiffLine::new(DiffKind::Unchanged, a[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(DiffLine::new(DiffKind::Added, b[y as usize - 1]));
+                y -= 1;
+            } else {
+                ops.push(DiffLine::new(DiffKind::Removed, a[x as usize - 1]));
+                x -= 1;
+            }
+        }
+    }
+
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffKind::*;
+
+    fn diff(old: &str, new: &str) -> Vec<(DiffKind, String)> {
+        diff_lines(old, new)
+            .into_iter()
+            .map(|line| (line.kind, line.text))
+            .collect()
+    }
+
+    fn lines(kind: DiffKind, text: &[&str]) -> Vec<(DiffKind, String)> {
+        text.iter().map(|line| (kind, line.to_string())).collect()
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(diff("", "").is_empty());
+        assert_eq!(diff("", "a\nb"), lines(Added, &["a", "b"]));
+        assert_eq!(diff("a\nb", ""), lines(Removed, &["a", "b"]));
+    }
+
+    #[test]
+    fn only_insertions() {
+        assert_eq!(
+            diff("a\nc", "a\nb\nc\nd"),
+            vec![
+                (Unchanged, "a".to_string()),
+                (Added, "b".to_string()),
+                (Unchanged, "c".to_string()),
+                (Added, "d".to_string()),
+            ]
+        );
+        assert_eq!(
+            diff("b", "a\nb"),
+            vec![(Added, "a".to_string()), (Unchanged, "b".to_string())]
+        );
+    }
+
+    #[test]
+    fn unchanged_text() {
+        assert_eq!(diff("a\nb", "a\nb"), lines(Unchanged, &["a", "b"]));
+    }
+
+    #[test]
+    fn replaced_line() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nx\nc"),
+            vec![
+                (Unchanged, "a".to_string()),
+                (Removed, "b".to_string()),
+                (Added, "x".to_string()),
+                (Unchanged, "c".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+// Finding and replacing text in a note, for the editor's find bar.
+
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    /// Read the query as a regular expression rather than plain text
+    pub regex: bool,
+}
+
+/// A query ready to run over a note's text. Plain text queries are escaped
+/// into a regex too, so both kinds match the same way.
+#[derive(Debug, Clone)]
+pub struct Finder {
+    regex: Regex,
+    options: FindOptions,
+}
+
+impl Finder {
+    /// `None` for an empty query; an error if a regex query doesn't compile.
+    pub fn new(query: &str, options: FindOptions) -> Result<Option<Self>, regex::Error> {
+        if query.is_empty() {
+            return Ok(None);
+        }
+        let pattern = if options.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .build()?;
+        Ok(Some(Self { regex, options }))
+    }
+
+    /// The byte ranges of every match in `text`, in order. Empty matches,
+    /// like those of `a*`, are left out, and so are matches taking in an
+    /// embedded attachment (U+FFFC), which replacing would delete.
+    pub fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        self.regex
+            .find_iter(text)
+            .filter(|m| !m.is_empty() && !m.as_str().contains('\u{FFFC}'))
+            .map(|m| m.range())
+            .collect()
+    }
+
+    /// What the match at `range` of `text` is replaced with. In regex mode
+    /// `$1` or `${name}` in `replacement` stand for the match's groups.
+    pub fn replacement(&self, text: &str, range: Range<usize>, replacement: &str) -> String {
+        if !self.options.regex {
+            return replacement.to_string();
+        }
+        match self.regex.captures_at(text, range.start) {
+            Some(captures) if captures.get(0).map(|m| m.range()) == Some(range) => {
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                expanded
+            }
+            _ => replacement.to_string(),
+        }
+    }
+}
+
+/// The words and quoted phrases of a sidebar search, the way the search
+/// reads them: `"exact phrase"`s as they are, other words on their own and
+/// without a trailing `*`.
+pub fn search_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for (i, part) in query.split('"').enumerate() {
+        // Odd segments sit between a pair of quotes
+        if i % 2 == 1 {
+            if !part.trim().is_empty() {
+                terms.push(part.trim().to_string());
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            let word = word.trim_end_matches('*');
+            if !word.is_empty() {
+                terms.push(word.to_string());
+            }
+        }
+    }
+    terms
+}
+
+/// The term of `terms` found first in `text`, ignoring case, with where it
+/// was found.
+pub fn first_term<'a>(text: &str, terms: &'a [String]) -> Option<(&'a str, Range<usize>)> {
+    let options = FindOptions::default();
+    terms
+        .iter()
+        .filter_map(|term| {
+            let finder = Finder::new(term, options).ok()??;
+            let range = finder.matches(text).into_iter().next()?;
+            Some((term.as_str(), range))
+        })
+        .min_by_key(|(_, range)| range.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGEX: FindOptions = FindOptions {
+        case_sensitive: false,
+        regex: true,
+    };
+
+    fn compile(query: &str, options: FindOptions) -> Finder {
+        Finder::new(query, options).unwrap().unwrap()
+    }
+
+    #[test]
+    fn plain_text_is_not_a_regex() {
+        let finder = compile("a.c", FindOptions::default());
+        assert_eq!(finder.matches("abc a.c"), vec![4..7]);
+        assert_eq!(finder.replacement("a.c", 0..3, "$1"), "$1");
+    }
+
+    #[test]
+    fn regex_mode() {
+        let finder = compile(r"(\w+)@(\w+)", REGEX);
+        let text = "x a@b y";
+        assert_eq!(finder.matches(text), vec![2..5]);
+        assert_eq!(finder.replacement(text, 2..5, "$2 at $1"), "b at a");
+        // Lines are anchored on their own, and empty matches are skipped
+        assert_eq!(compile("^x", REGEX).matches("x\nx"), vec![0..1, 2..3]);
+        assert_eq!(compile("a*", REGEX).matches("baab"), vec![1..3]);
+    }
+
+    #[test]
+    fn invalid_regex_and_empty_query() {
+        assert!(Finder::new("(", REGEX).is_err());
+        assert!(Finder::new("(", FindOptions::default()).unwrap().is_some());
+        assert!(Finder::new("", REGEX).unwrap().is_none());
+    }
+
+    #[test]
+    fn case_folding() {
+        let text = "École ÉCOLE école";
+        let folded = compile("école", FindOptions::default());
+        assert_eq!(folded.matches(text).len(), 3);
+        let exact = FindOptions {
+            case_sensitive: true,
+            regex: false,
+        };
+        assert_eq!(compile("école", exact).matches(text), vec![14..20]);
+    }
+
+    #[test]
+    fn attachments_are_not_matched() {
+        let finder = compile("a.b", REGEX);
+        assert_eq!(finder.matches("a\u{FFFC}b axb"), vec![6..9]);
+    }
+
+    #[test]
+    fn search_terms_read_like_the_search() {
+        assert_eq!(
+            search_terms(r#"foo "bar  baz" qu* "" *"#),
+            vec!["foo", "bar  baz", "qu"]
+        );
+        let terms = search_terms("zed alpha");
+        assert_eq!(
+            first_term("an Alpha then zed", &terms),
+            Some(("alpha", 3..8))
+        );
+        assert_eq!(first_term("nothing", &terms), None);
+    }
+}
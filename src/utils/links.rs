@@ -0,0 +1,277 @@
+// Links in notes: finding them, checking targets and writing them. Links to
+// other notes are `[[wiki links]]` naming the note's title.
+
+use crate::markdown::{self, Span, SpanKind};
+use std::ops::Range;
+
+/// A link in a note's text. Ranges are bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// The whole link, markers included
+    pub range: Range<usize>,
+    /// The text shown for the link
+    pub text: Range<usize>,
+    pub url: String,
+}
+
+/// The link at byte `pos` of `text`: a `[text](url)` link, an `<autolink>`
+/// or a bare url.
+pub fn link_at(text: &str, pos: usize) -> Option<Link> {
+    let mut found = None;
+    for block in markdown::parse(text) {
+        block.walk(&mut |span, _| {
+            // A url in a link's text belongs to the link
+            if found.is_none() && span.range.contains(&pos) {
+                found = link_from_span(text, span);
+            }
+        });
+    }
+    found
+}
+
+fn link_from_span(text: &str, span: &Span) -> Option<Link> {
+    let mut markers = span.children.iter().filter(|c| c.kind == SpanKind::Marker);
+    let (link_text, url) = match span.kind {
+        SpanKind::Link => {
+            let (open, close) = (markers.next()?, markers.next()?);
+            let url = span
+                .children
+                .iter()
+                .find(|c| c.kind == SpanKind::LinkUrl)
+                .map_or("", |url| &text[url.range.clone()]);
+            (open.range.end..close.range.start, url)
+        }
+        SpanKind::Autolink => {
+            let (open, close) = (markers.next()?, markers.next()?);
+            let inner = open.range.end..close.range.start;
+            (inner.clone(), &text[inner])
+        }
+        SpanKind::BareUrl => (span.range.clone(), &text[span.range.clone()]),
+        _ => return None,
+    };
+    Some(Link {
+        range: span.range.clone(),
+        text: link_text,
+        url: url.to_string(),
+    })
+}
+
+/// A `[[wiki link]]` in a note's text. `[[Title|shown text]]` links to
+/// `Title` showing other text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    /// The whole link, brackets included, in bytes
+    pub range: Range<usize>,
+    /// The title it links to, trimmed
+    pub title: String,
+    /// The text after the `|`, if there's one
+    pub shown: Option<String>,
+}
+
+/// Every `[[wiki link]]` of `text`, in order.
+pub fn wiki_links(text: &str) -> Vec<WikiLink> {
+    let mut links = Vec::new();
+    for block in markdown::parse(text) {
+        block.walk(&mut |span, _| {
+            if span.kind == SpanKind::WikiLink {
+                let inner = &text[span.range.start + 2..span.range.end - 2];
+                let (title, shown) = match inner.split_once('|') {
+                    Some((title, shown)) => (title, Some(shown.to_string())),
+                    None => (inner, None),
+                };
+                links.push(WikiLink {
+                    range: span.range.clone(),
+                    title: title.trim().to_string(),
+                    shown,
+                });
+            }
+        });
+    }
+    links
+}
+
+/// The titles `text` links to, each once. Titles match case-insensitively,
+/// like note titles in the database.
+pub fn linked_titles(text: &str) -> Vec<String> {
+    let mut titles: Vec<String> = Vec::new();
+    for link in wiki_links(text) {
+        if !titles.iter().any(|t| t.eq_ignore_ascii_case(&link.title)) {
+            titles.push(link.title);
+        }
+    }
+    titles
+}
+
+/// The wiki link at byte `pos` of `text`.
+pub fn wiki_link_at(text: &str, pos: usize) -> Option<WikiLink> {
+    wiki_links(text)
+        .into_iter()
+        .find(|link| link.range.contains(&pos))
+}
+
+/// `text` with its links to the note titled `old` pointing at `new`, or
+/// `None` if it has no such links. Their shown text stays as it is.
+pub fn rename_wiki_links(text: &str, old: &str, new: &str) -> Option<String> {
+    let links: Vec<WikiLink> = wiki_links(text)
+        .into_iter()
+        .filter(|link| link.title.eq_ignore_ascii_case(old.trim()))
+        .collect();
+    if links.is_empty() {
+        return None;
+    }
+    let mut renamed = text.to_string();
+    // Back to front, so earlier ranges stay valid
+    for link in links.iter().rev() {
+        let link_text = match &link.shown {
+            Some(shown) => format!("[[{}|{}]]", new.trim(), shown),
+            None => format!("[[{}]]", new.trim()),
+        };
+        renamed.replace_range(link.range.clone(), &link_text);
+    }
+    Some(renamed)
+}
+
+/// The url to link to for `input`, or `None` if it isn't a usable url.
+/// `www.` addresses get `https://`; anything else needs a scheme, like
+/// `https:`, `mailto:` or the `local:` of attachments.
+pub fn normalize_url(input: &str) -> Option<String> {
+    let url = input.trim();
+    if url.is_empty() || url.contains(|c: char| c.is_whitespace() || matches!(c, '<' | '>')) {
+        return None;
+    }
+    if let Some(host) = url.strip_prefix("www.") {
+        return (!host.is_empty()).then(|| format!("https://{}", url));
+    }
+
+    let (scheme, rest) = url.split_once(':')?;
+    // One-letter schemes would be Windows drive letters
+    let scheme_ok = scheme.len() >= 2
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'));
+    if !scheme_ok || rest.is_empty() {
+        return None;
+    }
+    if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") {
+        let host = rest.strip_prefix("//")?.split(['/', '?', '#']).next()?;
+        if host.is_empty() {
+            return None;
+        }
+    }
+    Some(url.to_string())
+}
+
+/// Markdown for a link to `url` showing `text`. Without a text of its own
+/// the link is an `<autolink>`.
+pub fn markdown_link(text: &str, url: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() || text == url {
+        format!("<{}>", url)
+    } else if url.contains(['(', ')']) {
+        format!("[{}](<{}>)", text, url)
+    } else {
+        format!("[{}]({})", text, url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(text: &str) -> Vec<String> {
+        wiki_links(text)
+            .into_iter()
+            .map(|link| link.title)
+            .collect()
+    }
+
+    #[test]
+    fn wiki_links_are_trimmed_and_skip_code() {
+        let text = "See [[ Alpha ]] and `[[code]]` [[]] [[a\nb]]";
+        assert_eq!(titles(text), vec!["Alpha"]);
+        assert_eq!(wiki_link_at(text, 6).unwrap().range, 4..15);
+        assert_eq!(
+            linked_titles("[[Alpha]] [[alpha]] [[Beta]]"),
+            vec!["Alpha", "Beta"]
+        );
+    }
+
+    #[test]
+    fn aliases_link_to_the_title_before_the_bar() {
+        let link = &wiki_links("[[ Alpha | the first ]]")[0];
+        assert_eq!(link.title, "Alpha");
+        assert_eq!(link.shown.as_deref(), Some(" the first "));
+        assert_eq!(linked_titles("[[Alpha|a]] [[alpha]]"), vec!["Alpha"]);
+    }
+
+    #[test]
+    fn renaming_keeps_the_shown_text() {
+        let text = "[[alpha|the first]] and [[Alpha]] but not [[Alphabet|alpha]]";
+        assert_eq!(
+            rename_wiki_links(text, "Alpha", "Gamma").unwrap(),
+            "[[Gamma|the first]] and [[Gamma]] but not [[Alphabet|alpha]]"
+        );
+        assert!(rename_wiki_links(text, "the first", "x").is_none());
+    }
+
+    #[test]
+    fn nested_brackets_link_the_innermost_pair() {
+        assert_eq!(titles("[[a [[b]] c]]"), vec!["b"]);
+        assert_eq!(titles("[[[a]]]"), vec!["a"]);
+        assert!(titles("[[a]b]]").is_empty());
+        // A wiki link inside a link's text is still one
+        assert_eq!(titles("[x [[a]]](u)"), vec!["a"]);
+    }
+
+    #[test]
+    fn urls_get_a_scheme_or_are_refused() {
+        assert_eq!(
+            normalize_url(" www.x.com "),
+            Some("https://www.x.com".into())
+        );
+        assert_eq!(normalize_url("mailto:a@b.c"), Some("mailto:a@b.c".into()));
+        assert_eq!(
+            normalize_url("HTTPS://x.com/a?b#c"),
+            Some("HTTPS://x.com/a?b#c".into())
+        );
+        for bad in [
+            "",
+            "www.",
+            "https://",
+            "http:x.com",
+            "C:\\foo",
+            "a b",
+            "x.com",
+            "<a:b>",
+        ] {
+            assert_eq!(normalize_url(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn links_at_a_position() {
+        let text = "see https://a.com/x_(y). [t](http://c.io) <mailto:d@e.f>";
+        assert_eq!(link_at(text, 6).unwrap().url, "https://a.com/x_(y)");
+        let link = link_at(text, text.find("[t]").unwrap() + 1).unwrap();
+        assert_eq!((&text[link.text], link.url.as_str()), ("t", "http://c.io"));
+        assert_eq!(
+            link_at(text, text.find("mailto").unwrap()).unwrap().url,
+            "mailto:d@e.f"
+        );
+        assert!(link_at(text, 1).is_none());
+        // A url in a link's text belongs to the link
+        let link = link_at("[see https://x.com](https://y.com)", 8).unwrap();
+        assert_eq!(link.url, "https://y.com");
+    }
+
+    #[test]
+    fn markdown_for_links() {
+        assert_eq!(markdown_link("", "https://x"), "<https://x>");
+        assert_eq!(
+            markdown_link("a\nb", "https://x/(1)"),
+            "[a b](<https://x/(1)>)"
+        );
+        assert_eq!(markdown_link("t", "https://x"), "[t](https://x)");
+    }
+}
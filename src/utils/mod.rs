@@ -1 +1,8 @@
 // Utility functions
+pub mod diff;
+pub mod find;
+pub mod links;
+pub mod paths;
+pub mod recent;
+pub mod tags;
+pub mod tasks;
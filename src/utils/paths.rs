@@ -0,0 +1,167 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "watermelon";
+const DB_FILE: &str = "watermelon.db";
+const DB_ENV: &str = "WATERMELON_DB";
+/// Where attachments are copied to, inside the database's directory.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// `$XDG_DATA_HOME/watermelon`, falling back to `~/.local/share/watermelon`.
+pub fn data_dir() -> PathBuf {
+    let base = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join(APP_DIR)
+}
+
+/// The directory holding the database. Attachment paths are relative to it,
+/// so a database picked with `--db` gets attachments of its own.
+pub fn storage_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+/// Parse `--db <path>` / `--db=<path>` out of the command line. A `--db`
+/// without a path is an `InvalidInput` error rather than being ignored.
+fn db_path_from_args<I: IntoIterator<Item = String>>(args: I) -> io::Result<Option<PathBuf>> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let path = if arg == "--db" {
+            args.next()
+        } else if let Some(path) = arg.strip_prefix("--db=") {
+            Some(path.to_string())
+        } else {
+            continue;
+        };
+        return match path.filter(|p| !p.is_empty()) {
+            Some(path) => Ok(Some(PathBuf::from(path))),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--db needs a path, as in --db <path> or --db=<path>",
+            )),
+        };
+    }
+    Ok(None)
+}
+
+/// Work out where the database lives and make sure its directory exists.
+///
+/// Precedence: `--db` flag, then `WATERMELON_DB`, then the XDG data directory.
+/// Only the default location picks up a legacy `./watermelon.db`. A `--db`
+/// without a path fails with `InvalidInput`.
+pub fn resolve_db_path<I: IntoIterator<Item = String>>(args: I) -> io::Result<PathBuf> {
+    let override_path = db_path_from_args(args)?.or_else(|| {
+        env::var_os(DB_ENV)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    });
+
+    if let Some(path) = override_path {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        return Ok(path);
+    }
+
+    let dir = data_dir();
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(DB_FILE);
+    migrate_legacy_db(Path::new(DB_FILE), &path)?;
+    Ok(path)
+}
+
+/// Move a database left in the working directory by older builds into `target`,
+/// together with its WAL/SHM sidecars. Does nothing once `target` exists.
+fn migrate_legacy_db(legacy: &Path, target: &Path) -> io::Result<()> {
+    if target.exists() || !legacy.is_file() {
+        return Ok(());
+    }
+
+    println!(
+        "Moving legacy database {} to {}",
+        legacy.display(),
+        target.display()
+    );
+
+    for suffix in ["", "-wal", "-shm"] {
+        let from = PathBuf::from(format!("{}{}", legacy.display(), suffix));
+        let to = PathBuf::from(format!("{}{}", target.display(), suffix));
+        if from.is_file() {
+            move_file(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    // rename fails across filesystems, so fall back to copy + remove
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db_path(args: &[&str]) -> io::Result<Option<PathBuf>> {
+        db_path_from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn db_flag_in_either_form() {
+        assert_eq!(
+            db_path(&["watermelon", "--db", "a.db"]).unwrap(),
+            Some(PathBuf::from("a.db"))
+        );
+        assert_eq!(
+            db_path(&["watermelon", "--db=dir/b.db"]).unwrap(),
+            Some(PathBuf::from("dir/b.db"))
+        );
+        assert_eq!(db_path(&["watermelon", "--other"]).unwrap(), None);
+    }
+
+    #[test]
+    fn db_flag_without_a_path() {
+        for args in [&["watermelon", "--db"][..], &["watermelon", "--db="]] {
+            let e = db_path(args).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn storage_dir_is_next_to_the_database() {
+        assert_eq!(storage_dir(Path::new("a/b.db")), PathBuf::from("a"));
+        assert_eq!(storage_dir(Path::new("b.db")), PathBuf::from("."));
+    }
+
+    #[test]
+    fn legacy_database_moves_with_its_sidecars() {
+        let dir = env::temp_dir().join(format!("watermelon-paths-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join("old.db");
+        let target = dir.join("new.db");
+        fs::write(&legacy, "db").unwrap();
+        fs::write(dir.join("old.db-wal"), "wal").unwrap();
+
+        migrate_legacy_db(&legacy, &target).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "db");
+        assert_eq!(fs::read_to_string(dir.join("new.db-wal")).unwrap(), "wal");
+        assert!(!legacy.exists());
+
+        // An existing database is never replaced
+        fs::write(&legacy, "other").unwrap();
+        migrate_legacy_db(&legacy, &target).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "db");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+// Least recently used order, for caches that only keep the last few items.
+
+use std::collections::VecDeque;
+
+/// Up to `capacity` items, least recently used first.
+#[derive(Debug)]
+pub struct Recent<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: PartialEq> Recent<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Marks `item` as the most recently used. Returns the least recently
+    /// used items that no longer fit, for the cache to drop.
+    pub fn touch(&mut self, item: T) -> Vec<T> {
+        self.items.retain(|other| *other != item);
+        self.items.push_back(item);
+        let excess = self.items.len().saturating_sub(self.capacity);
+        self.items.drain(..excess).collect()
+    }
+
+    pub fn remove(&mut self, item: &T) {
+        self.items.retain(|other| other != item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reopening_moves_an_item_to_the_back() {
+        let mut recent = Recent::new(3);
+        for item in [1, 2, 3] {
+            assert!(recent.touch(item).is_empty());
+        }
+        assert!(recent.touch(1).is_empty());
+        assert_eq!(recent.touch(4), vec![2]);
+        assert_eq!(recent.touch(5), vec![3]);
+        assert_eq!(recent.touch(6), vec![1]);
+    }
+
+    #[test]
+    fn the_oldest_item_is_dropped_past_the_capacity() {
+        let mut recent = Recent::new(20);
+        for item in 0..20 {
+            assert!(recent.touch(item).is_empty());
+        }
+        assert_eq!(recent.touch(20), vec![0]);
+        assert_eq!(recent.touch(21), vec![1]);
+    }
+
+    #[test]
+    fn removed_items_make_room() {
+        let mut recent = Recent::new(2);
+        recent.touch(1);
+        recent.touch(2);
+        recent.remove(&1);
+        assert!(recent.touch(3).is_empty());
+        assert_eq!(recent.touch(4), vec![2]);
+    }
+}
@@ -0,0 +1,95 @@
+// #hashtag parsing
+
+use crate::markdown::{self, SpanKind};
+
+/// Byte ranges of `#tag` tokens in `text`, including the `#`.
+///
+/// A tag starts with `#` at the beginning of a word and runs over letters,
+/// digits, `_`, `-` and `/` (for nested tags like `#work/meeting`). It needs at
+/// least one non-digit so issue numbers like `#42` stay plain text. Tags are
+/// found by the Markdown parser, so headings (`# Title`), link targets,
+/// escapes and anything inside code are not tags.
+pub fn tag_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    for block in markdown::parse(text) {
+        block.walk(&mut |span, _| {
+            if span.kind == SpanKind::Tag {
+                spans.push((span.range.start, span.range.end));
+            }
+        });
+    }
+    spans
+}
+
+/// End of the tag whose `#` is at byte `start` of `text`, or `None` if no
+/// valid tag starts there. The caller checks that `start` begins a word.
+pub fn tag_end(text: &str, start: usize) -> Option<usize> {
+    let body_start = start + 1;
+    let run_end = text[body_start..]
+        .find(|c| !is_tag_char(c))
+        .map_or(text.len(), |n| body_start + n);
+    // Trailing separators belong to the sentence, not the tag
+    let body = text[body_start..run_end].trim_end_matches(['-', '/']);
+    let starts_ok = body
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_');
+    (starts_ok && !body.chars().all(|c| c.is_ascii_digit())).then(|| body_start + body.len())
+}
+
+/// Whether a `#` after `prev` is at the beginning of a word.
+pub fn starts_word(prev: char) -> bool {
+    prev.is_whitespace() || "([{,;\"'".contains(prev)
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Tag names used in `text`: lowercased, without the `#`, sorted and unique.
+pub fn extract_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = tag_spans(text)
+        .into_iter()
+        .map(|(start, end)| text[start + 1..end].to_lowercase())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_is_not_tagged() {
+        assert_eq!(extract_tags("a `#code` #real"), vec!["real"]);
+        assert_eq!(extract_tags("```\n#fenced\n```\n#after"), vec!["after"]);
+    }
+
+    #[test]
+    fn urls_are_not_tagged() {
+        let text = "https://x.com/#frag www.y.org/#x [t](#anchor) <https://z.io/#q>";
+        assert!(extract_tags(text).is_empty());
+    }
+
+    #[test]
+    fn headings_are_not_tags_but_may_hold_them() {
+        assert!(extract_tags("# Title").is_empty());
+        assert_eq!(extract_tags("# Title\n#Title"), vec!["title"]);
+        assert_eq!(extract_tags("## Sub #inhead"), vec!["inhead"]);
+    }
+
+    #[test]
+    fn names_are_lowercased_sorted_and_unique() {
+        assert_eq!(
+            extract_tags("#b #Work/Meeting- (#a) #B"),
+            vec!["a", "b", "work/meeting"]
+        );
+    }
+
+    #[test]
+    fn numbers_words_and_escapes_are_not_tags() {
+        assert_eq!(extract_tags("#42 #v2 a#b \\#esc"), vec!["v2"]);
+    }
+}
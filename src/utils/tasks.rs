@@ -0,0 +1,85 @@
+// Task list items across notes
+
+use crate::markdown::{self, SpanKind};
+use std::ops::Range;
+
+/// An unchecked `- [ ]` item of a note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenTask {
+    /// Line of the item in the note's content, from 0
+    pub line: usize,
+    /// The item's text after the task marker
+    pub text: String,
+}
+
+/// Byte ranges of the `[ ]` markers of unchecked tasks in `text`. Found by
+/// the Markdown parser, so task-like lines inside code blocks don't count.
+fn open_markers(text: &str) -> Vec<(Range<usize>, usize)> {
+    let mut markers = Vec::new();
+    for block in markdown::parse(text) {
+        block.walk(&mut |span, parent| {
+            if span.kind == (SpanKind::TaskMarker { checked: false }) {
+                let item_end = parent.map_or(span.range.end, |item| item.range.end);
+                markers.push((span.range.clone(), item_end));
+            }
+        });
+    }
+    markers
+}
+
+/// The unchecked tasks of a note, in order.
+pub fn open_tasks(text: &str) -> Vec<OpenTask> {
+    open_markers(text)
+        .into_iter()
+        .map(|(marker, item_end)| OpenTask {
+            line: text[..marker.start].matches('\n').count(),
+            text: text[marker.end..item_end].trim().to_string(),
+        })
+        .collect()
+}
+
+/// `text` with the unchecked task on `line` checked off, or `None` if that
+/// line has no unchecked task (any more).
+pub fn check_task(text: &str, line: usize) -> Option<String> {
+    let (marker, _) = open_markers(text)
+        .into_iter()
+        .find(|(marker, _)| text[..marker.start].matches('\n').count() == line)?;
+    let mut checked = text.to_string();
+    checked.replace_range(marker.start + 1..marker.end - 1, "x");
+    Some(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "# Todo\n- [ ] one\n- [x] done\n```\n- [ ] code\n```\n  * [ ]  two *b*\n";
+
+    #[test]
+    fn open_tasks_skip_checked_items_and_code() {
+        let tasks: Vec<(usize, String)> = open_tasks(NOTE)
+            .into_iter()
+            .map(|task| (task.line, task.text))
+            .collect();
+        assert_eq!(
+            tasks,
+            vec![(1, "one".to_string()), (6, "two *b*".to_string())]
+        );
+    }
+
+    #[test]
+    fn check_task_checks_only_that_line() {
+        let checked = check_task(NOTE, 6).unwrap();
+        assert!(checked.contains("  * [x]  two *b*"));
+        assert!(checked.contains("- [ ] one"));
+        assert_eq!(checked.len(), NOTE.len());
+    }
+
+    #[test]
+    fn check_task_refuses_lines_without_an_open_task() {
+        // Already checked, inside code, plain text and past the end
+        for line in [2, 4, 0, 99] {
+            assert_eq!(check_task(NOTE, line), None, "line {}", line);
+        }
+    }
+}